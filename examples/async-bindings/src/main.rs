@@ -86,6 +86,7 @@ async fn run_wasm() -> wasmtime::Result<()> {
     let host = ArielOSHost::default();
 
     let mut store = Store::new(&engine, host);
+    store.limiter(|host| host);
 
     // Enough fuel to never run out before the timeout
     store.set_fuel(1_000_000_000)?;
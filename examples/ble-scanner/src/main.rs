@@ -59,6 +59,7 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
 
     let host = ArielOSHost::default();
     let mut store = Store::new(&engine, host);
+    store.limiter(|host| host);
 
     let wasm = include_bytes!("../payload.cwasm").as_slice();
 
@@ -133,14 +134,19 @@ impl EventHandler for ComponentScanner {
         let comp_instance = &self.0.0;
         let mut store_handle = self.0.1.borrow_mut();
         while let Some(Ok(report)) = it.next() {
-            comp_instance
+            let processed = comp_instance
                 .interface0
                 .call_on_single_report(
                     store_handle.as_context_mut(),
                     BdAddr::new(report.addr.into_inner()),
                 )
-                .unwrap()
                 .unwrap();
+            if let Err(error) = processed {
+                info!(
+                    "The capsule could not process a report: {:?}",
+                    defmt::Debug2Format(&error)
+                );
+            }
         }
     }
 }
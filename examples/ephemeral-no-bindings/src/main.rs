@@ -8,7 +8,7 @@ use ariel_os::debug::log::info;
 use ariel_os::debug::{ExitCode, exit};
 
 use ariel_os::time::Timer;
-use wasmtime::component::{Component, Linker, bindgen};
+use wasmtime::component::{Instance, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use coap_handler::Handler;
@@ -26,12 +26,15 @@ bindgen!({
 });
 
 impl CanInstantiate<ArielOSHost> for ExampleEphemeralNoBindings {
-    fn instantiate(
-        linker: &mut Linker<ArielOSHost>,
+    fn link(_linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        Ok(())
+    }
+
+    fn from_instance(
         store: &mut Store<ArielOSHost>,
-        component: Component,
+        instance: &Instance,
     ) -> wasmtime::Result<Self> {
-        ExampleEphemeralNoBindings::instantiate(store, &component, &linker)
+        ExampleEphemeralNoBindings::new(store, instance)
     }
 }
 
@@ -71,7 +74,7 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
 
     let wasm = include_bytes!("../payload.cwasm").as_slice();
 
-    let mut wasmhandler = WasmHandler::new(host);
+    let mut wasmhandler = WasmHandler::with_limiter(host);
     // SAFETY: Data in that file was produced by ./precompile_wasm.rs
     unsafe {
         wasmhandler.start_ff_from_static(wasm, &engine)?;
@@ -8,7 +8,7 @@ use ariel_os::debug::log::info;
 use ariel_os::debug::{ExitCode, exit};
 
 use ariel_os::time::Timer;
-use wasmtime::component::{Component, HasSelf, Linker, bindgen};
+use wasmtime::component::{HasSelf, Instance, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use coap_handler::Handler;
@@ -32,13 +32,15 @@ bindgen!({
 });
 
 impl CanInstantiate<ArielOSHost> for ExampleEphemeralWithBindings {
-    fn instantiate(
-        linker: &mut Linker<ArielOSHost>,
+    fn link(linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        ExampleEphemeralWithBindings::add_to_linker::<_, HasSelf<_>>(linker, |state| state)
+    }
+
+    fn from_instance(
         store: &mut Store<ArielOSHost>,
-        component: Component,
+        instance: &Instance,
     ) -> wasmtime::Result<Self> {
-        ExampleEphemeralWithBindings::add_to_linker::<_, HasSelf<_>>(linker, |state| state)?;
-        ExampleEphemeralWithBindings::instantiate(store, &component, &linker)
+        ExampleEphemeralWithBindings::new(store, instance)
     }
 }
 
@@ -80,7 +82,7 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
 
     let wasm = include_bytes!("../payload.cwasm").as_slice();
 
-    let mut wasmhandler = WasmHandler::new(host);
+    let mut wasmhandler = WasmHandler::with_limiter(host);
     // SAFETY: Data in that file was produced by ./precompile_wasm.rs
     unsafe {
         wasmhandler.start_ff_from_static(wasm, &engine)?;
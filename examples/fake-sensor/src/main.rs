@@ -61,6 +61,7 @@ async fn run_wasm() -> wasmtime::Result<()> {
 
     let host = ArielOSHost::default();
     let mut store = Store::new(&engine, host);
+    store.limiter(|host| host);
 
     let mut linker = Linker::new(&engine);
 
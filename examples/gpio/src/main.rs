@@ -6,10 +6,10 @@ use ariel_os_boards::pins;
 use ariel_os::debug::{ExitCode, exit, log::info};
 use ariel_os::gpio::{Input, Level, Output, Pull};
 
-use wasmtime::component::{Component, HasSelf, Linker, bindgen};
+use wasmtime::component::{Component, bindgen};
 use wasmtime::{Config, Engine, Store};
 
-use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::{ArielOSHost, Capability, CapsuleLinker};
 
 bindgen!({
     world: "example-gpio",
@@ -54,16 +54,16 @@ async fn run_wasm(peris: Peripherals) -> wasmtime::Result<()> {
     // Options relating to async
     config.async_stack_size(4096);
 
-    let led1 = Output::new(peris.leds.led0, Level::Low);
+    let led0 = Output::new(peris.leds.led0, Level::Low);
     let pull = Pull::Up;
 
-    let btn1 = Input::builder(peris.buttons.button0, pull)
+    let button0 = Input::builder(peris.buttons.button0, pull)
         .build_with_interrupt()
         .unwrap();
 
     let mut host = ArielOSHost::default();
-    host.bind_led(led1);
-    host.bind_button(btn1);
+    host.bind_output("led0", led0);
+    host.bind_input("button0", button0);
 
     let engine = Engine::new(&config)?;
 
@@ -73,11 +73,19 @@ async fn run_wasm(peris: Peripherals) -> wasmtime::Result<()> {
         unsafe { Component::deserialize_raw(&engine, component_bytes.as_slice().into()) }?;
 
     let mut store = Store::new(&engine, host);
-
-    let mut linker = Linker::new(&engine);
-
-    ExampleGpio::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state)?;
-    let bindings = ExampleGpio::instantiate_async(&mut store, &component, &mut linker).await?;
+    store.limiter(|host| host);
+
+    // Only hand out the interfaces this capsule needs
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Log)
+        .grant(Capability::Time)
+        .grant(Capability::Gpio)
+        .build(&engine)?;
+
+    let pre = linker.instantiate_pre(&component)?;
+    let bindings = ExampleGpioPre::new(pre)?
+        .instantiate_async(&mut store)
+        .await?;
 
     bindings.call_blinky(&mut store).await?;
 
@@ -5,12 +5,26 @@ edition = "2024"
 
 [dependencies]
 clap = { version = "4.5.40", features = ["derive"] }
+sha2 = { version = "0.10.8" }
 
 ---
+//! Sends a capsule image with the protocol of `ariel_os_bindings::wasm::transfer`.
+//!
+//! Every frame is sent again until the device acknowledges it. Sending the same file again after
+//! an interruption resumes where the device got to.
 
 use clap::Parser;
-use std::net::{UdpSocket, Ipv4Addr};
-use std::io::Read;
+use sha2::{Digest, Sha256};
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::Duration;
+
+const START: u8 = 0x01;
+const DATA: u8 = 0x02;
+const ACK: u8 = 0x81;
+const NACK: u8 = 0x82;
+const DONE: u8 = 0x83;
+const REPLY_LEN: usize = 8;
 
 #[derive(Parser, Debug)]
 #[clap(version)]
@@ -18,51 +32,106 @@ struct Args {
     #[clap(short = 'z', long, help = "path to wasm payload")]
     payload: std::path::PathBuf,
 
-    #[clap(short='s', long, help = "packetization level", default_value_t = 128)]
-    packet_size: usize,
+    #[clap(short='s', long, help = "bytes of the payload per datagram, at most the device's CHUNK_SIZE", default_value_t = 128)]
+    chunk_size: usize,
 
     #[clap(short='i', long, help = "Destination IP", default_value_t = String::from("10.42.0.61"))]
     dest_ip: String,
 
     #[clap(short='p', long, help = "Destination Port", default_value_t = 1234)]
     dest_port: u16,
+
+    #[clap(short='t', long, help = "milliseconds to wait for an answer before sending again", default_value_t = 500)]
+    timeout_ms: u64,
+
+    #[clap(short='r', long, help = "times a frame is sent without an answer before giving up", default_value_t = 10)]
+    retries: u32,
 }
 
+enum Reply {
+    /// The device wants the data at this offset next.
+    Next(u32),
+    Done,
+}
 
+/// Sends `frame` until the device answers for transfer `id`.
+fn exchange(socket: &UdpSocket, frame: &[u8], id: u16, retries: u32) -> io::Result<Reply> {
+    let mut reply = [0; REPLY_LEN + 1];
+    for _ in 0..retries {
+        socket.send(frame)?;
+        loop {
+            let n = match socket.recv(&mut reply) {
+                Ok(n) => n,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) => return Err(e),
+            };
+            // Ignore anything about other transfers, e.g. late answers to an earlier run
+            if n != REPLY_LEN || u16::from_be_bytes([reply[1], reply[2]]) != id {
+                continue;
+            }
+            let value = u32::from_be_bytes(reply[3..7].try_into().unwrap());
+            match (reply[0], reply[7]) {
+                (ACK, _) => return Ok(Reply::Next(value)),
+                // Out of order: a frame or an answer got lost, carry on from where the device is
+                (NACK, 2) => return Ok(Reply::Next(value)),
+                (NACK, 1) => return Err(io::Error::other("the device cannot take a payload this large")),
+                (NACK, 3) => return Err(io::Error::other("the payload got corrupted on the way, send it again")),
+                // The device forgot about the transfer, e.g. because it restarted
+                (NACK, 4) => return Err(io::Error::other("the device does not know about this transfer, send it again")),
+                (DONE, _) => return Ok(Reply::Done),
+                _ => continue,
+            }
+        }
+    }
+    Err(io::Error::other(format!("no answer after {retries} attempts")))
+}
 
 fn main() -> std::io::Result<()> {
     let args = Args::parse();
 
     println!("{:?}", args);
-    let Args {payload, packet_size, dest_ip, dest_port} = args;
+    let Args {payload, chunk_size, dest_ip, dest_port, timeout_ms, retries} = args;
     let ip_addr = dest_ip.parse::<Ipv4Addr>().unwrap();
 
-    let out_addr: (Ipv4Addr, u16) = (ip_addr, dest_port);
-
     println!("Binding Socket");
     let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect((ip_addr, dest_port))?;
+    socket.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
 
-    let mut file_buf = Vec::new();
-    let mut payload_f = std::fs::File::open(payload)?;
-    payload_f.read_to_end(&mut file_buf)?;
+    let file_buf = std::fs::read(payload)?;
+    let Ok(payload_size) = u32::try_from(file_buf.len()) else {
+        return Err(io::Error::other("payload is too large"));
+    };
+    println!("Payload size: {payload_size}");
 
+    // Derived from the digest, so sending the same file again resumes the transfer
+    let digest = Sha256::digest(&file_buf);
+    let id = u16::from_be_bytes([digest[0], digest[1]]);
 
-    let payload_size = file_buf.len() as u32;
-    println!("Payload size: {payload_size}");
-    // Send file size
-    socket.send_to(
-        &payload_size.to_be_bytes(), out_addr
-    )?;
-
-    std::thread::sleep(std::time::Duration::from_millis(10));
-
-    for chunk in file_buf.chunks(packet_size) {
-        println!("Sending chunk of size {}", chunk.len());
-        socket.send_to(
-            chunk, out_addr
-        )?;
-        std::thread::sleep(std::time::Duration::from_millis(100));
+    let mut start = vec![START];
+    start.extend_from_slice(&id.to_be_bytes());
+    start.extend_from_slice(&payload_size.to_be_bytes());
+    start.extend_from_slice(&digest);
+
+    let mut reply = exchange(&socket, &start, id, retries)?;
+    if let Reply::Next(offset) = reply {
+        if offset > 0 {
+            println!("Resuming at offset {offset}");
+        }
     }
+    while let Reply::Next(offset) = reply {
+        let offset_usize = offset as usize;
+        let Some(chunk) = file_buf.get(offset_usize..file_buf.len().min(offset_usize + chunk_size)) else {
+            return Err(io::Error::other(format!("the device asked for offset {offset}, past the payload")));
+        };
+        println!("Sending chunk of size {} at offset {offset}", chunk.len());
+        let mut frame = vec![DATA];
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&offset.to_be_bytes());
+        frame.extend_from_slice(chunk);
+        reply = exchange(&socket, &frame, id, retries)?;
+    }
+    println!("The device received the whole payload");
 
     Ok(())
-}
\ No newline at end of file
+}
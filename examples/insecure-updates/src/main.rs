@@ -19,6 +19,9 @@ use wasmtime::{Config, Engine, Store};
 use embassy_futures::select::{Either, select};
 
 use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::signed::TrustedKey;
+use ariel_os_bindings::wasm::suit::{self, DeviceIdentity};
+use ariel_os_bindings::wasm::transfer::{DATA_HEADER_LEN, Receiver};
 
 bindgen!({
     world: "example-updates",
@@ -35,9 +38,31 @@ bindgen!({
     }
 });
 
-const BUFFER_SIZE: usize = 128;
+/// Largest chunk of the image `send_files.rs` may put in a datagram.
+const CHUNK_SIZE: usize = 128;
+const BUFFER_SIZE: usize = DATA_HEADER_LEN + CHUNK_SIZE;
 const WASM_BUFFER_SIZE: usize = 32 * 1024; // 32 KiB maximum wasm component for now
-const USIZE_BYTES: usize = (usize::BITS / 8) as usize;
+
+/// Public half of `payloads/dev-signing.key`; replace it with your own key from
+/// `sign_capsule.rs keygen`.
+const TRUSTED_KEYS: &[TrustedKey] = &[TrustedKey {
+    id: 0,
+    public_key: [
+        214, 117, 43, 12, 9, 232, 21, 132, 214, 131, 189, 27, 48, 229, 131, 7, 252, 97, 200, 129,
+        63, 61, 218, 236, 161, 127, 86, 111, 64, 160, 26, 194,
+    ],
+}];
+
+/// Identity SUIT manifests must target: UUIDv5 of "ariel-os.org" in the DNS namespace, and of
+/// "insecure-updates" in that vendor namespace.
+const DEVICE: DeviceIdentity = DeviceIdentity {
+    vendor_id: [
+        163, 248, 168, 154, 45, 58, 86, 204, 140, 44, 244, 100, 149, 36, 38, 71,
+    ],
+    class_id: [
+        9, 174, 57, 149, 72, 86, 81, 46, 168, 182, 197, 197, 213, 232, 79, 224,
+    ],
+};
 
 #[ariel_os::task(autostart)]
 async fn main() {
@@ -70,6 +95,13 @@ fn configure_engine() -> wasmtime::Result<Engine> {
     Ok(Engine::new(&config)?)
 }
 
+/// The capsule being run.
+struct Capsule {
+    _component: Component,
+    store: Store<ArielOSHost>,
+    instance: ExampleUpdates,
+}
+
 /// # Errors
 /// Misconfiguration of Wasmtime or of the component
 async fn run_wasm() -> wasmtime::Result<()> {
@@ -105,240 +137,105 @@ async fn run_wasm() -> wasmtime::Result<()> {
         WASM_BUFFER_SIZE
     );
     let mut wasm_buffer = [0_u8; WASM_BUFFER_SIZE];
-
-    let mut current_instance: Option<ExampleUpdates> = None;
-    let mut current_component: Component;
-    let mut current_store: Store<ArielOSHost> = Store::new(&engine, ArielOSHost::default());
-    'outer: loop {
-        // Ugly code for now, who cares
-        if current_instance.is_none() {
-            let mut state = CommunicationState::NotStarted;
-            let packet = socket.recv_from(&mut buf).await;
-            match process_packet(packet, &mut buf, &mut wasm_buffer, &mut state) {
-                Ok(PacketKind::DeclareSize) => {
-                    info!("Someone is trying to send a file");
-                }
-                Ok(PacketKind::Useless) => {
-                    // Restart
-                    continue 'outer;
-                }
-                Err(PacketProcessingError::HadntStartedYet) => {
-                    // Restart
-                    continue 'outer;
-                }
-                Err(PacketProcessingError::SizeTooBig) => {
-                    // Restart
-                    continue 'outer;
-                }
-                Err(PacketProcessingError::ReceiveError) => {
-                    panic!("Socket has an issue, panicking")
-                }
-                // ProgressCan'tStop if it didn't start yet
-                Err(PacketProcessingError::ProgressStopped) => unreachable!(),
-                // Other Oks require a different CommunicationState
-                Ok(_) => unreachable!(),
-            }
-            // Reaching this means that we starting communicating and the state reflects this
-            assert!(matches!(state, CommunicationState::InProgress(_, _)));
-            match state {
-                CommunicationState::InProgress(0, rem) => {
-                    info!("Trying to receive file of size {}", rem)
-                }
-                _ => unreachable!(),
-            }
-            // Receiving the actual file
-            'inner: loop {
-                let packet = socket.recv_from(&mut buf).await;
-                match process_packet(packet, &buf, &mut wasm_buffer, &mut state) {
-                    Ok(PacketKind::PartialFill) => {}
-                    Ok(PacketKind::BufferFiled) => break 'inner,
-                    Ok(PacketKind::Useless) => {}
-                    Ok(PacketKind::DeclareSize) => unreachable!(),
-                    Err(PacketProcessingError::HadntStartedYet) => unreachable!(),
-                    Err(PacketProcessingError::ProgressStopped) => continue 'outer,
-                    Err(PacketProcessingError::SizeTooBig) => continue 'outer,
-                    Err(PacketProcessingError::ReceiveError) => {
-                        panic!("Socket has an issue, panicking")
-                    }
-                }
-            }
-            info!("The File was completely received");
-            // Now the transfer is done, make a component, a store and an instance
-            current_component =
-                unsafe { Component::deserialize_raw(&engine, wasm_buffer.as_slice().into())? };
-            current_store = Store::new(&engine, ArielOSHost::default());
-            let instance =
-                ExampleUpdates::instantiate_async(&mut current_store, &current_component, &linker)
-                    .await?;
-            current_instance = Some(instance);
-        }
-        // Guaranteed to be a Some(_) and everything to be initialized
-
-        let stop_reason = {
-            let mut capsule_work = core::pin::pin!(
-                current_instance
-                    .as_mut()
-                    .unwrap()
-                    .call_run(&mut current_store)
-            );
-            'polling: loop {
-                info!("Running a component and waiting for updates");
+    let mut receiver = Receiver::new(&mut wasm_buffer);
+
+    let mut capsule: Option<Capsule> = None;
+    loop {
+        if let Some(running) = capsule.as_mut() {
+            let mut capsule_work = core::pin::pin!(running.instance.call_run(&mut running.store));
+            info!("Running a component and waiting for updates");
+            loop {
                 match select(socket.recv_from(&mut buf), &mut capsule_work).await {
                     Either::First(packet) => {
-                        let mut state = CommunicationState::NotStarted;
-                        match process_packet(packet, &buf, &mut [], &mut state) {
-                            Ok(PacketKind::DeclareSize) => {
-                                // Stop the capsule
-                                info!(
-                                    "Received the start of a new wasm capsule, cancelling the underlying capsule immediatly"
-                                );
-                                break 'polling StopReason::NewCapsule(state);
-                            }
-                            _ => {
-                                info!("Useless Package gotten");
-                                continue 'polling;
-                            }
+                        // Until a new transfer starts, nothing is written into the buffer the
+                        // capsule runs from
+                        let Some((n, peer)) = received(packet) else {
+                            continue;
+                        };
+                        handle_datagram(&socket, &mut receiver, &buf[..n], peer).await;
+                        if receiver.in_progress() {
+                            info!(
+                                "Received the start of a new wasm capsule, cancelling the underlying capsule immediatly"
+                            );
+                            break;
                         }
                     }
                     Either::Second(_) => {
                         info!("The capsule finished running, you love to see it");
-                        break 'polling StopReason::CapsuleFinished;
+                        break;
                     }
                 }
             }
-        };
-        match stop_reason {
-            StopReason::CapsuleFinished => {
-                current_instance = None;
-            }
-            StopReason::NewCapsule(mut state) => {
-                // The capsule was stopped because a new capsule is incoming
-                match state {
-                    CommunicationState::InProgress(0, rem) => {
-                        info!("Trying to receive file of size {}", rem);
-                    }
-                    _ => unreachable!(),
-                }
-                // Receiving the actual file
-                'inner: loop {
-                    let packet = socket.recv_from(&mut buf).await;
-                    match process_packet(packet, &buf, &mut wasm_buffer, &mut state) {
-                        Ok(PacketKind::PartialFill) => {}
-                        Ok(PacketKind::BufferFiled) => break 'inner,
-                        Ok(PacketKind::Useless) => {}
-                        Ok(PacketKind::DeclareSize) => unreachable!(),
-                        Err(PacketProcessingError::HadntStartedYet) => unreachable!(),
-                        Err(PacketProcessingError::ProgressStopped) => {
-                            current_instance = None;
-                            continue 'outer;
-                        }
-                        Err(PacketProcessingError::SizeTooBig) => {
-                            current_instance = None;
-                            continue 'outer;
-                        }
-                        Err(PacketProcessingError::ReceiveError) => {
-                            panic!("Socket has an issue, panicking")
-                        }
-                    }
-                }
-                info!("The File was completely received");
-                // Now the transfer is done, make a component, a store and an instance
-                current_component =
-                    unsafe { Component::deserialize_raw(&engine, wasm_buffer.as_slice().into())? };
-                current_store = Store::new(&engine, ArielOSHost::default());
-                let instance = ExampleUpdates::instantiate_async(
-                    &mut current_store,
-                    &current_component,
-                    &linker,
-                )
-                .await?;
-                current_instance = Some(instance);
+        }
+        // The capsule runs from the receive buffer, it must be gone before the transfer writes there
+        capsule = None;
+
+        loop {
+            let Some((n, peer)) = received(socket.recv_from(&mut buf).await) else {
+                continue;
+            };
+            if handle_datagram(&socket, &mut receiver, &buf[..n], peer).await {
+                break;
             }
         }
+        info!("The File was completely received");
+        let Some(payload) = verified_payload(receiver.image().unwrap()) else {
+            continue;
+        };
+        // Now the transfer is done, make a component, a store and an instance
+        // SAFETY: The payload is signed by a trusted key, and the buffer is not written to until
+        // the capsule is dropped
+        let component = unsafe { Component::deserialize_raw(&engine, payload.into())? };
+        let mut store = Store::new(&engine, ArielOSHost::default());
+        store.limiter(|host| host);
+        let instance = ExampleUpdates::instantiate_async(&mut store, &component, &linker).await?;
+        capsule = Some(Capsule {
+            _component: component,
+            store,
+            instance,
+        });
     }
 }
 
-#[derive(Debug)]
-enum StopReason {
-    NewCapsule(CommunicationState),
-    CapsuleFinished,
-}
-
-#[derive(Debug)]
-enum PacketKind {
-    DeclareSize,
-    PartialFill,
-    BufferFiled,
-    Useless,
-}
-
-#[derive(Debug, Clone, Copy)]
-enum CommunicationState {
-    NotStarted,
-    InProgress(usize, usize),
-}
-
-impl CommunicationState {
-    fn in_progress(&self) -> bool {
-        match &self {
-            CommunicationState::InProgress(_, _) => true,
-            CommunicationState::NotStarted => false,
+fn received(packet: Result<(usize, UdpMetadata), RecvError>) -> Option<(usize, UdpMetadata)> {
+    match packet {
+        Ok(packet) => Some(packet),
+        Err(e) => {
+            info!("Dropping a datagram: {:?}", defmt::Debug2Format(&e));
+            None
         }
     }
 }
 
-#[derive(Debug)]
-enum PacketProcessingError {
-    ProgressStopped,
-    ReceiveError,
-    SizeTooBig,
-    HadntStartedYet,
+/// Feeds a datagram to the receiver and answers its sender.
+///
+/// Returns whether the datagram completed an image.
+async fn handle_datagram(
+    socket: &UdpSocket<'_>,
+    receiver: &mut Receiver<'_>,
+    datagram: &[u8],
+    peer: UdpMetadata,
+) -> bool {
+    let handled = receiver.handle(datagram);
+    if let Some(reply) = handled.reply {
+        if let Err(e) = socket.send_to(&reply.encode(), peer).await {
+            info!("Failed to answer: {:?}", defmt::Debug2Format(&e));
+        }
+    }
+    handled.complete
 }
 
-use PacketProcessingError::*;
-
-fn process_packet(
-    packet: Result<(usize, UdpMetadata), RecvError>,
-    recv_buffer: &[u8],
-    wasm_buffer: &mut [u8],
-    state: &mut CommunicationState,
-) -> Result<PacketKind, PacketProcessingError> {
-    match packet {
-        Ok((0, _)) => {
-            if state.in_progress() {
-                return Err(ProgressStopped);
-            } else {
-                return Ok(PacketKind::Useless);
-            }
+/// Checks a completely received image, either a SUIT envelope or a signed container, and returns
+/// the capsule it contains.
+fn verified_payload(image: &[u8]) -> Option<&[u8]> {
+    match suit::verify_update(image, TRUSTED_KEYS, &DEVICE) {
+        Ok(update) => {
+            info!("Received capsule version {}", update.version());
+            Some(update.payload())
+        }
+        Err(e) => {
+            info!("Refusing capsule: {}", defmt::Display2Format(&e));
+            None
         }
-        Ok((n, _)) => match state {
-            CommunicationState::InProgress(offset, remaining) => {
-                if *remaining < n {
-                    return Err(SizeTooBig);
-                } else {
-                    wasm_buffer[*offset..*offset + n].copy_from_slice(&recv_buffer[..n]);
-                    *offset += n;
-                    *remaining -= n;
-                }
-                if *remaining == 0 {
-                    return Ok(PacketKind::BufferFiled);
-                } else {
-                    return Ok(PacketKind::PartialFill);
-                }
-            }
-            CommunicationState::NotStarted => {
-                if n != USIZE_BYTES {
-                    return Err(HadntStartedYet);
-                }
-                let remaining = usize::from_be_bytes(recv_buffer[..n].try_into().unwrap());
-                if remaining > WASM_BUFFER_SIZE {
-                    return Err(SizeTooBig);
-                }
-                let offset = 0;
-                *state = CommunicationState::InProgress(offset, remaining);
-                return Ok(PacketKind::DeclareSize);
-            }
-        },
-        Err(_) => return Err(ReceiveError),
     }
 }
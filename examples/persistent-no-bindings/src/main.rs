@@ -10,7 +10,7 @@ use ariel_os::debug::log::{error, info};
 use ariel_os::debug::{ExitCode, exit};
 
 use ariel_os::time::Timer;
-use wasmtime::component::{Component, Linker, bindgen};
+use wasmtime::component::{Instance, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use coap_handler::Handler;
@@ -41,12 +41,15 @@ impl Into<CoAPError> for CoapErr {
 }
 
 impl CanInstantiate<ArielOSHost> for ExamplePersistentNoBindings {
-    fn instantiate(
-        linker: &mut Linker<ArielOSHost>,
+    fn link(_linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        Ok(())
+    }
+
+    fn from_instance(
         store: &mut Store<ArielOSHost>,
-        component: Component,
+        instance: &Instance,
     ) -> wasmtime::Result<Self> {
-        ExamplePersistentNoBindings::instantiate(store, &component, &linker)
+        ExamplePersistentNoBindings::new(store, instance)
     }
 }
 
@@ -83,7 +86,7 @@ impl PersistentCapsule<ArielOSHost> for ExamplePersistentNoBindings {
         {
             Ok(handler_init_rep) => match handler_init_rep {
                 Ok(()) => Ok(()),
-                Err(()) => Err(wasmtime::Error::msg(
+                Err(_) => Err(wasmtime::Error::msg(
                     "Handler initialization failed in the capsule",
                 )),
             },
@@ -144,7 +147,7 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
 
     let wasm = include_bytes!("../payload.cwasm").as_slice();
 
-    let mut wasmhandler = WasmHandler::new(host);
+    let mut wasmhandler = WasmHandler::with_limiter(host);
     // SAFETY: Data in that file was produced by ./precompile_wasm.rs
     unsafe {
         wasmhandler.start_from_static(wasm, &engine)?;
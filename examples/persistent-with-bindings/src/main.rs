@@ -10,7 +10,7 @@ use ariel_os::debug::log::{error, info};
 use ariel_os::debug::{ExitCode, exit};
 
 use ariel_os::time::Timer;
-use wasmtime::component::{Component, HasSelf, Linker, bindgen};
+use wasmtime::component::{HasSelf, Instance, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use coap_handler::Handler;
@@ -46,13 +46,15 @@ impl Into<CoAPError> for CoapErr {
 }
 
 impl CanInstantiate<ArielOSHost> for ExamplePersistentWithBindings {
-    fn instantiate(
-        linker: &mut Linker<ArielOSHost>,
+    fn link(linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        ExamplePersistentWithBindings::add_to_linker::<_, HasSelf<_>>(linker, |state| state)
+    }
+
+    fn from_instance(
         store: &mut Store<ArielOSHost>,
-        component: Component,
+        instance: &Instance,
     ) -> wasmtime::Result<Self> {
-        ExamplePersistentWithBindings::add_to_linker::<_, HasSelf<_>>(linker, |state| state)?;
-        ExamplePersistentWithBindings::instantiate(store, &component, &linker)
+        ExamplePersistentWithBindings::new(store, instance)
     }
 }
 
@@ -89,7 +91,7 @@ impl PersistentCapsule<ArielOSHost> for ExamplePersistentWithBindings {
         {
             Ok(handler_init_rep) => match handler_init_rep {
                 Ok(()) => Ok(()),
-                Err(()) => Err(wasmtime::Error::msg(
+                Err(_) => Err(wasmtime::Error::msg(
                     "Handler initialization failed in the capsule",
                 )),
             },
@@ -152,7 +154,7 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
 
     let wasm = include_bytes!("../payload.cwasm").as_slice();
 
-    let mut wasmhandler = WasmHandler::new(host);
+    let mut wasmhandler = WasmHandler::with_limiter(host);
     // SAFETY: Data in that file was produced by ./precompile_wasm.rs
     unsafe {
         wasmhandler.start_from_static(wasm, &engine)?;
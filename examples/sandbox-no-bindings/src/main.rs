@@ -8,15 +8,18 @@ use ariel_os::coap::coap_run;
 use ariel_os::debug::log::info;
 use ariel_os::debug::{ExitCode, exit};
 
-use ariel_os::time::Timer;
-use wasmtime::component::{Component, Linker, bindgen};
+use ariel_os::time::{Duration, Timer};
+use embassy_sync::once_lock::OnceLock;
+use wasmtime::component::{Instance, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use coap_handler_implementations::{ReportingHandlerBuilder, new_dispatcher};
 
 use ariel_os_bindings::wasm::coap::{CanInstantiate, EphemeralCapsule};
 
-use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::epoch::run_epoch_ticker;
+use ariel_os_bindings::wasm::signed::TrustedKey;
+use ariel_os_bindings::wasm::{ArielOSHost, CapsuleLimits};
 
 use ariel_os_bindings::wasm::coap::sanbdox::Sandbox;
 bindgen!({
@@ -25,12 +28,15 @@ bindgen!({
 });
 
 impl CanInstantiate<ArielOSHost> for ExampleSandboxNoBindings {
-    fn instantiate(
-        linker: &mut Linker<ArielOSHost>,
+    fn link(_linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        Ok(())
+    }
+
+    fn from_instance(
         store: &mut Store<ArielOSHost>,
-        component: Component,
+        instance: &Instance,
     ) -> wasmtime::Result<Self> {
-        ExampleSandboxNoBindings::instantiate(store, &component, &linker)
+        ExampleSandboxNoBindings::new(store, instance)
     }
 }
 
@@ -40,6 +46,35 @@ impl EphemeralCapsule<ArielOSHost, String> for ExampleSandboxNoBindings {
     }
 }
 
+/// Public half of `payloads/dev-signing.key`; replace it with your own key from
+/// `sign_capsule.rs keygen`.
+const TRUSTED_KEYS: &[TrustedKey] = &[TrustedKey {
+    id: 0,
+    public_key: [
+        214, 117, 43, 12, 9, 232, 21, 132, 214, 131, 189, 27, 48, 229, 131, 7, 252, 97, 200, 129,
+        63, 61, 218, 236, 161, 127, 86, 111, 64, 160, 26, 194,
+    ],
+}];
+
+/// Engine shared with the epoch ticker thread.
+static ENGINE: OnceLock<Engine> = OnceLock::new();
+
+/// Epoch ticks a capsule may run for, i.e. a second.
+const EPOCH_DEADLINE: u64 = 100;
+
+/// Advances the epoch every 10ms.
+///
+/// Capsules run synchronously on the executor, so a capsule stuck in a loop keeps every task from
+/// running. This thread has a higher priority than the executor thread, so it preempts such a
+/// capsule and the epoch deadline stops it.
+#[ariel_os::thread(autostart, priority = 10)]
+fn epoch_ticker() {
+    ariel_os::blocker::block_on(async {
+        let engine = ENGINE.get().await.clone();
+        run_epoch_ticker(engine, Duration::from_millis(10)).await
+    })
+}
+
 #[ariel_os::task(autostart)]
 async fn main() {
     let res = run_wasm_coap_server().await;
@@ -59,14 +94,26 @@ async fn run_wasm_coap_server() -> wasmtime::Result<()> {
     config.memory_reservation(0);
     config.memory_init_cow(false);
     config.memory_may_move(false);
+    // Capsules must be precompiled with `--epoch`
+    config.epoch_interruption(true);
 
     // Options that can be changed without changing the payload
     config.max_wasm_stack(2048);
     config.memory_reservation_for_growth(0);
 
-    let engine = Engine::new(&config).unwrap();
+    let engine = ENGINE.get_or_init(|| Engine::new(&config).unwrap());
+
+    // Anyone allowed to PUT can upload a capsule, so keep each one to its initial memory
+    const CAPSULE_LIMITS: CapsuleLimits = CapsuleLimits {
+        memory_size: 32 * 1024,
+        ..CapsuleLimits::DEFAULT
+    };
 
-    let sandbox: Sandbox<'_, ArielOSHost, String, ExampleSandboxNoBindings> = Sandbox::new(&engine);
+    let sandbox: Sandbox<'_, ArielOSHost, String, ExampleSandboxNoBindings> =
+        Sandbox::with_host(engine, || ArielOSHost::with_limits(CAPSULE_LIMITS))
+            .with_limiter()
+            .with_trusted_keys(TRUSTED_KEYS)
+            .with_epoch_deadline(EPOCH_DEADLINE);
 
     let handler = sandbox.to_handler(new_dispatcher()).with_wkc();
 
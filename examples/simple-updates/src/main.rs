@@ -99,6 +99,7 @@ async fn run_wasm(peris: Peripherals) -> wasmtime::Result<()> {
         match current {
             Two => {
                 current_store = Store::new(&engine, ArielOSHost::default());
+                current_store.limiter(|host| host);
                 let instance =
                     ExampleUpdates::instantiate_async(&mut current_store, &component1, &mut linker)
                         .await?;
@@ -107,6 +108,7 @@ async fn run_wasm(peris: Peripherals) -> wasmtime::Result<()> {
             }
             One => {
                 current_store = Store::new(&engine, ArielOSHost::default());
+                current_store.limiter(|host| host);
                 let instance =
                     ExampleUpdates::instantiate_async(&mut current_store, &component2, &mut linker)
                         .await?;
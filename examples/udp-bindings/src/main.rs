@@ -5,11 +5,12 @@ use ariel_os::debug::{ExitCode, exit, log::info};
 use ariel_os::time::Timer;
 
 use ariel_os::net;
-use ariel_os::reexports::embassy_net::udp::PacketMetadata;
+use static_cell::StaticCell;
 use wasmtime::component::{Component, HasSelf, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::udp::{UdpSocketBuffers, UdpSocketPool};
 
 bindgen!({
     world: "example-udp",
@@ -18,9 +19,12 @@ bindgen!({
         "ariel:wasm-bindings/log-api": ariel_os_bindings::wasm::log,
         "ariel:wasm-bindings/udp-api": ariel_os_bindings::wasm::udp,
     },
+    imports: { default: async },
+    exports: { default: async },
 });
 
-static BUFFER_SIZE: usize = 128;
+const BUFFER_SIZE: usize = 128;
+const SOCKET_COUNT: usize = 2;
 
 #[ariel_os::task(autostart)]
 async fn main() {
@@ -48,6 +52,9 @@ async fn run_wasm() -> wasmtime::Result<()> {
     config.max_wasm_stack(2048);
     config.memory_reservation_for_growth(0);
 
+    // Options relating to async
+    config.async_stack_size(4096);
+
     // Use fuel instrumentation to prevent indefinite execution
     config.consume_fuel(true);
 
@@ -61,33 +68,27 @@ async fn run_wasm() -> wasmtime::Result<()> {
 
     let stack = net::network_stack().await.unwrap();
 
-    let mut rx_buffer = [0; BUFFER_SIZE];
-    let mut rx_meta = [PacketMetadata::EMPTY; 1];
-    let mut tx_buffer = [0; BUFFER_SIZE];
-    let mut tx_meta = [PacketMetadata::EMPTY; 1];
-
-    unsafe {
-        host.initialize_socket(
-            stack,
-            &mut rx_meta,
-            &mut rx_buffer,
-            &mut tx_meta,
-            &mut tx_buffer,
-        );
-    }
+    // Room for SOCKET_COUNT sockets opened by the capsule at the same time
+    static BUFFERS: StaticCell<[UdpSocketBuffers<1, BUFFER_SIZE>; SOCKET_COUNT]> =
+        StaticCell::new();
+    static POOL: StaticCell<UdpSocketPool> = StaticCell::new();
+    let pool = BUFFERS
+        .init([const { UdpSocketBuffers::new() }; SOCKET_COUNT])
+        .iter_mut()
+        .fold(UdpSocketPool::new(stack), UdpSocketPool::with_buffers);
+    host.set_udp_pool(POOL.init(pool));
 
     let mut store = Store::new(&engine, host);
+    store.limiter(|host| host);
 
     store.set_fuel(1_000_000)?;
 
     let mut linker = Linker::new(&engine);
 
     ExampleUdp::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state)?;
-    let bindings = ExampleUdp::instantiate(&mut store, &component, &linker)?;
-    bindings.call_bind_socket(&mut store, 1234)?;
-    loop {
-        // This function might never return but it will stop because of fuel exhaustation
-        bindings.call_run(&mut store)?;
-        Timer::after_millis(10).await;
-    }
+    let bindings = ExampleUdp::instantiate_async(&mut store, &component, &linker).await?;
+    bindings.call_bind_socket(&mut store, 1234).await?;
+    // The capsule is suspended while waiting for datagrams, so this only consumes fuel when
+    // packets are actually echoed back.
+    bindings.call_run(&mut store).await
 }
@@ -2,35 +2,93 @@
 #![no_std]
 extern crate alloc;
 
-use alloc::boxed::Box;
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::pin::pin;
+use core::ptr::NonNull;
+
 use ariel_os::coap::coap_run;
-use ariel_os::debug::log::{Debug2Format, info};
+use ariel_os::debug::log::{Display2Format, info};
+use ariel_os::time::{Duration, Timer};
 
-use coap_handler::Handler;
 use coap_handler_implementations::{HandlerBuilder, ReportingHandlerBuilder, new_dispatcher};
 
-use coap_message::{Code, OptionNumber};
-
-use coap_message_utils::Error as CoapError;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::signal::Signal;
 
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either, Either3, select, select3};
 
 use wasmtime::component::{Component, HasSelf, Linker, bindgen};
 use wasmtime::{Config, Engine, Store};
 
 use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::coap::{
+    CapsuleManager, CapsuleRunner, CapsuleStatus, ReceivedCapsule,
+};
+use ariel_os_bindings::wasm::signed::TrustedKey;
+use ariel_os_bindings::wasm::slots::{CapsuleSlots, FlashVersionStore};
+use ariel_os_bindings::wasm::state::StateTransfer;
+use ariel_os_bindings::wasm::suit::DeviceIdentity;
 
 #[derive(Debug)]
 enum UpdateMsg {
-    Install(Vec<u8>),
+    Install(ReceivedCapsule),
     Stop,
 }
 
 static UPDATE: Signal<CriticalSectionRawMutex, UpdateMsg> = Signal::new();
 
+/// Kept up to date by `runner_task` for GET requests to `/vm-control`.
+static STATUS: Mutex<CriticalSectionRawMutex, RefCell<CapsuleStatus>> =
+    Mutex::new(RefCell::new(CapsuleStatus {
+        version: None,
+        size: 0,
+        running: false,
+        last_error: None,
+    }));
+
+fn update_status(f: impl FnOnce(&mut CapsuleStatus)) {
+    STATUS.lock(|status| f(&mut status.borrow_mut()));
+}
+
+/// Largest upload `/vm-control` accepts, envelope included.
+const MAX_UPLOAD_SIZE: usize = 32 * 1024;
+
+/// Public half of `payloads/dev-signing.key`; replace it with your own key from
+/// `sign_capsule.rs keygen`.
+const TRUSTED_KEYS: &[TrustedKey] = &[TrustedKey {
+    id: 0,
+    public_key: [
+        214, 117, 43, 12, 9, 232, 21, 132, 214, 131, 189, 27, 48, 229, 131, 7, 252, 97, 200, 129,
+        63, 61, 218, 236, 161, 127, 86, 111, 64, 160, 26, 194,
+    ],
+}];
+
+/// Identity SUIT manifests must target: UUIDv5 of "ariel-os.org" in the DNS namespace, and of
+/// "updatable-async" in that vendor namespace.
+const DEVICE: DeviceIdentity = DeviceIdentity {
+    vendor_id: [
+        163, 248, 168, 154, 45, 58, 86, 204, 140, 44, 244, 100, 149, 36, 38, 71,
+    ],
+    class_id: [
+        6, 12, 103, 142, 57, 7, 87, 164, 139, 28, 35, 186, 150, 216, 13, 163,
+    ],
+};
+
+/// Version of the capsule built into the firmware; updates must be newer.
+const BUILT_IN_VERSION: u64 = 1;
+
+/// Flash key of the highest confirmed version, which updates must not go below even after a
+/// reboot.
+const CONFIRMED_VERSION_KEY: &str = "updatable-async/confirmed-version";
+
+type Slots = CapsuleSlots<FlashVersionStore>;
+
+/// How long a new capsule has to run without trapping before it replaces the previous one.
+const TRIAL_PERIOD: Duration = Duration::from_secs(10);
+
 bindgen!({
     world: "example-async",
     path: "../../wit/",
@@ -43,143 +101,46 @@ bindgen!({
     require_store_data_send: true,
 });
 
-enum Payload {
-    Static(&'static [u8]),
-    Owned(Box<[u8]>),
-}
-
-impl Payload {
-    fn as_bytes(&self) -> &[u8] {
-        match self {
-            Payload::Static(bytes) => bytes,
-            Payload::Owned(bytes) => bytes,
-        }
-    }
-}
-
-struct VmControl {
-    program: Vec<u8>,
+/// Why the current capsule stopped running.
+enum Outcome {
+    Update(ReceivedCapsule),
+    Stopped,
+    /// The capsule could not be instantiated or trapped.
+    Failed,
 }
 
-impl VmControl {
-    fn new() -> Self {
-        Self {
-            program: Vec::new(),
+impl From<UpdateMsg> for Outcome {
+    fn from(msg: UpdateMsg) -> Self {
+        match msg {
+            UpdateMsg::Install(update) => Outcome::Update(update),
+            UpdateMsg::Stop => Outcome::Stopped,
         }
     }
 }
 
-impl Handler for VmControl {
-    type RequestData = (Option<u32>, u8);
-
-    type ExtractRequestError = coap_message_utils::Error;
-    type BuildResponseError<M: coap_message::MinimalWritableMessage> = coap_message_utils::Error;
-
-    fn extract_request_data<M: coap_message::ReadableMessage>(
-        &mut self,
-        request: &M,
-    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
-        use coap_message::MessageOption;
-        use coap_message_utils::OptionsExt;
-
-        match request.code().into() {
-            coap_numbers::code::DELETE => {
-                info!("Received DELETE request for program ");
-                request.options().ignore_elective_others()?;
-
-                self.program.clear();
-                UPDATE.signal(UpdateMsg::Stop);
-
-                Ok((None, coap_numbers::code::DELETED))
-            }
-
-            coap_numbers::code::PUT => {
-                info!("Received PUT request for program ");
-                let mut block1: Option<u32> = None;
-
-                request
-                    .options()
-                    .filter(|o| {
-                        if o.number() == coap_numbers::option::BLOCK1
-                            && let Some(n) = o.value_uint()
-                            && block1.is_none()
-                        {
-                            block1 = Some(n);
-                            false
-                        } else {
-                            true
-                        }
-                    })
-                    .ignore_elective_others()?;
-
-                // This is a bit of a simplification, but ignoring the block size and just
-                // appending is really kind'a fine IMO.
-                let block1 = block1.unwrap_or(0);
-
-                // FIXME there's probably a Size1 option; if so, reallocate to fail early.
-
-                let szx = block1 & 0x7;
-                let blocksize = 1usize << (4 + szx);
-                let offset = (block1 >> 4) as usize * blocksize;
-
-                if offset == 0 {
-                    self.program.clear();
-                }
-                if self.program.len() != offset {
-                    return Ok((None, coap_numbers::code::REQUEST_ENTITY_INCOMPLETE));
-                }
+/// Hands capsules received on `/vm-control` over to `runner_task`.
+struct Runner;
 
-                let payload = request.payload();
-                self.program.try_reserve_exact(payload.len()).map_err(|e| {
-                    info!(
-                        "Failed to reserve memory for program: {:?}",
-                        Debug2Format(&e)
-                    );
-                    CoapError::internal_server_error()
-                })?;
-                self.program.extend_from_slice(payload);
-
-                if (block1 & 0x8) == 0x8 {
-                    Ok((Some(block1), coap_numbers::code::CONTINUE))
-                } else {
-                    let image = core::mem::take(&mut self.program);
-                    UPDATE.signal(UpdateMsg::Install(image));
-                    Ok((Some(block1), coap_numbers::code::CHANGED))
-                }
-            }
-
-            _ => Err(CoapError::method_not_allowed()),
-        }
+impl CapsuleRunner for Runner {
+    fn install(&mut self, capsule: ReceivedCapsule) {
+        UPDATE.signal(UpdateMsg::Install(capsule));
     }
 
-    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
-        1
+    fn stop(&mut self) {
+        UPDATE.signal(UpdateMsg::Stop);
     }
 
-    fn build_response<M: coap_message::MutableWritableMessage>(
-        &mut self,
-        response: &mut M,
-        request: Self::RequestData,
-    ) -> Result<(), Self::BuildResponseError<M>> {
-        let (block1, code) = request;
-        response.set_code(M::Code::new(code).map_err(CoapError::from_unionerror)?);
-
-        if let Some(block1) = block1 {
-            response
-                .add_option_uint(
-                    M::OptionNumber::new(coap_numbers::option::BLOCK1)
-                        .map_err(CoapError::from_unionerror)?,
-                    block1 as u32,
-                )
-                .map_err(CoapError::from_unionerror)?;
-        }
-        Ok(())
+    fn status(&self) -> CapsuleStatus {
+        STATUS.lock(|status| status.borrow().clone())
     }
 }
 
 #[ariel_os::task(autostart)]
 async fn coap_task() {
-    let control = VmControl::new();
+    let control = CapsuleManager::new(Runner)
+        .with_max_size(MAX_UPLOAD_SIZE)
+        .with_trusted_keys(TRUSTED_KEYS)
+        .with_device_identity(DEVICE);
 
     let handler = new_dispatcher()
         .at_with_attributes(&["vm-control"], &[], control)
@@ -193,19 +154,44 @@ async fn coap_task() {
 async fn runner_task() {
     let engine = make_engine();
     let initial_payload = include_bytes!("../async-payload.cwasm").as_slice();
-    let mut payload = Payload::Static(initial_payload);
+    let versions = FlashVersionStore::new(CONFIRMED_VERSION_KEY);
+    let mut slots = CapsuleSlots::new(BUILT_IN_VERSION, initial_payload, versions)
+        .await
+        .unwrap();
 
     info!("Initial payload size: {} bytes", initial_payload.len());
 
     let mut linker = Linker::new(&engine);
     ExampleAsync::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state).unwrap();
 
+    // State handed over from the previous capsule, kept until its successor is confirmed so a
+    // rollback gets it back
+    let mut carried_state = None;
+
     loop {
-        info!("New payload size: {} bytes", payload.as_bytes().len());
-        let payload_option = run_payload(&engine, &linker, &payload).await;
-        payload = match payload_option {
-            Some(payload) => payload,
-            None => wait_for_payload().await,
+        let update = match run_current(&engine, &linker, &mut slots, &mut carried_state).await {
+            Outcome::Update(update) => update,
+            Outcome::Failed if slots.in_trial() => {
+                let version = slots.rollback().unwrap();
+                update_status(|status| {
+                    let error = status.last_error.take().unwrap_or_default();
+                    status.last_error = Some(alloc::format!(
+                        "version {version} rolled back after failing its trial: {error}"
+                    ));
+                });
+                info!(
+                    "Capsule version {} failed its trial, rolling back to version {}",
+                    version,
+                    slots.current().version()
+                );
+                continue;
+            }
+            Outcome::Failed | Outcome::Stopped => wait_for_payload().await,
+        };
+        // On failure, the current capsule is simply started again
+        if let Err(e) = slots.stage(update.version.unwrap_or_default(), update.code) {
+            info!("Refusing capsule: {}", Display2Format(&e));
+            update_status(|status| status.last_error = Some(e.to_string()));
         }
     }
 }
@@ -229,47 +215,164 @@ fn make_engine() -> Engine {
     Engine::new(&cfg).unwrap()
 }
 
-async fn wait_for_payload() -> Payload {
+async fn wait_for_payload() -> ReceivedCapsule {
     loop {
         match UPDATE.wait().await {
-            UpdateMsg::Install(new_img) => {
-                info!("Accepted new capsule image ({} bytes)", new_img.len());
-                return Payload::Owned(new_img.into_boxed_slice());
-            }
+            UpdateMsg::Install(update) => return update,
             UpdateMsg::Stop => {}
         }
     }
 }
 
-async fn run_payload(
+/// Runs the current capsule of `slots`, confirming it once it passed its trial.
+async fn run_current(
     engine: &Engine,
     linker: &Linker<ArielOSHost>,
-    payload: &Payload,
-) -> Option<Payload> {
-    let bytes = payload.as_bytes();
-    let mem = core::ptr::NonNull::from(bytes);
-    let component = unsafe { Component::deserialize_raw(engine, mem.into()) }.unwrap();
+    slots: &mut Slots,
+    carried_state: &mut Option<Vec<u8>>,
+) -> Outcome {
+    let slot = slots.current();
+    let trial = slots.in_trial();
+    info!(
+        "Running capsule version {} ({} bytes), on trial: {}",
+        slot.version(),
+        slot.image().len(),
+        trial
+    );
+    update_status(|status| {
+        status.version = Some(slot.version());
+        status.size = slot.image().len();
+        status.running = true;
+    });
+    let outcome = run_slot(engine, linker, slots, trial, carried_state).await;
+    update_status(|status| status.running = false);
+    outcome
+}
+
+/// Runs the current capsule; [`run_current`] keeps `STATUS` up to date around it.
+///
+/// If the capsule exports the `state-transfer-api` interface, it gets `carried_state` right after
+/// instantiation, and its own state is put there when it is stopped or replaced.
+async fn run_slot(
+    engine: &Engine,
+    linker: &Linker<ArielOSHost>,
+    slots: &mut Slots,
+    trial: bool,
+    carried_state: &mut Option<Vec<u8>>,
+) -> Outcome {
+    let image = NonNull::from(slots.current().image());
+    // SAFETY: The image is either built into the firmware or its signature was checked by the
+    // `CapsuleManager`. It is only dropped after this function returns.
+    let component = match unsafe { Component::deserialize_raw(engine, image) } {
+        Ok(component) => component,
+        Err(e) => return failed("Failed to load capsule", e),
+    };
 
     let host = ArielOSHost::default();
     let mut store = Store::new(engine, host);
+    store.limiter(|host| host);
 
-    let bindings = ExampleAsync::instantiate_async(&mut store, &component, linker)
-        .await
-        .unwrap();
+    let instance = match linker.instantiate_async(&mut store, &component).await {
+        Ok(instance) => instance,
+        Err(e) => return failed("Failed to instantiate capsule", e),
+    };
+    let bindings = match ExampleAsync::new(&mut store, &instance) {
+        Ok(bindings) => bindings,
+        Err(e) => return failed("Failed to instantiate capsule", e),
+    };
+    let state_transfer = match StateTransfer::lookup(&mut store, &instance) {
+        Ok(state_transfer) => state_transfer,
+        Err(e) => return failed("Failed to instantiate capsule", e),
+    };
 
-    info!("Running payload");
+    match (&state_transfer, carried_state.as_ref()) {
+        (Some(state_transfer), Some(state)) => {
+            info!("Importing {} bytes of state", state.len());
+            if let Err(e) = state_transfer
+                .import_state_async(&mut store, state.clone())
+                .await
+            {
+                return failed("Capsule failed to import state", e);
+            }
+        }
+        (None, Some(_)) => info!("Capsule does not import state, starting afresh"),
+        _ => {}
+    }
 
-    let run_fut = bindings.run.call_async(&mut store, &[], &mut []);
-    let update_fut = UPDATE.wait();
+    let outcome = run(&mut store, &bindings, slots, trial, carried_state).await;
 
-    let next = match select(update_fut, run_fut).await {
-        Either::First(UpdateMsg::Install(new_img)) => {
-            Some(Payload::Owned(new_img.into_boxed_slice()))
-        }
-        Either::First(UpdateMsg::Stop) => None,
-        Either::Second(_) => None,
-    };
+    // Only a capsule that was interrupted rather than failed can still hand over its state
+    if !matches!(outcome, Outcome::Failed) {
+        *carried_state = match state_transfer {
+            Some(state_transfer) => match state_transfer.export_state_async(&mut store).await {
+                Ok(state) => {
+                    info!("Exported {} bytes of state", state.len());
+                    Some(state)
+                }
+                Err(e) => {
+                    info!("Failed to export capsule state: {}", Display2Format(&e));
+                    None
+                }
+            },
+            None => None,
+        };
+    }
 
     info!("Payload done!");
-    next
+    outcome
+}
+
+/// Runs an instantiated capsule until it stops, fails, or an update comes in.
+async fn run(
+    store: &mut Store<ArielOSHost>,
+    bindings: &ExampleAsync,
+    slots: &mut Slots,
+    trial: bool,
+    carried_state: &mut Option<Vec<u8>>,
+) -> Outcome {
+    let mut run_fut = pin!(bindings.run.call_async(store, &[], &mut []));
+
+    if trial {
+        match select3(UPDATE.wait(), &mut run_fut, Timer::after(TRIAL_PERIOD)).await {
+            Either3::First(msg) => return msg.into(),
+            Either3::Second(Err(e)) => return failed("Capsule trapped", e),
+            // Running to completion passes the trial too
+            Either3::Second(Ok(())) => {
+                return match confirm(slots, carried_state).await {
+                    Ok(()) => Outcome::Stopped,
+                    Err(outcome) => outcome,
+                };
+            }
+            Either3::Third(()) => {
+                if let Err(outcome) = confirm(slots, carried_state).await {
+                    return outcome;
+                }
+            }
+        }
+    }
+
+    match select(UPDATE.wait(), run_fut).await {
+        Either::First(msg) => msg.into(),
+        Either::Second(Ok(())) => Outcome::Stopped,
+        Either::Second(Err(e)) => failed("Capsule trapped", e),
+    }
+}
+
+/// Confirms the capsule on trial; it fails its trial if its version cannot be stored.
+async fn confirm(slots: &mut Slots, carried_state: &mut Option<Vec<u8>>) -> Result<(), Outcome> {
+    match slots.confirm().await {
+        Ok(version) => {
+            info!("Capsule version {} confirmed", version);
+            *carried_state = None;
+            Ok(())
+        }
+        Err(e) => Err(failed("Failed to confirm capsule", e)),
+    }
+}
+
+/// Logs and records why the capsule failed.
+fn failed(what: &str, error: impl core::fmt::Display) -> Outcome {
+    info!("{}: {}", what, Display2Format(&error));
+    update_status(|status| status.last_error = Some(alloc::format!("{what}: {error}")));
+    Outcome::Failed
 }
@@ -15,6 +15,7 @@ generate!({
     world: "example-ble-scanner",
     path: "../../wit",
     generate_all,
+    std_feature,
 });
 
 use alloc::collections::btree_map::BTreeMap;
@@ -23,7 +24,8 @@ use alloc::vec::Vec;
 use core::cell::RefCell;
 
 use ariel::wasm_bindings::log_api::info;
-use exports::ariel::wasm_bindings::ble_api::{BdAddr, Guest};
+use exports::ariel::wasm_bindings::ble_api::{BdAddr, Guest, ReportError};
+use exports::ariel::wasm_bindings::state_transfer_api::Guest as StateTransferGuest;
 struct MyComponent;
 
 /// SAFETY: WASM is single threaded
@@ -33,6 +35,12 @@ unsafe impl<T> Sync for SendCell<T> {}
 
 static SEEN: SendCell<BTreeMap<[u8; 6], u64>> = SendCell(RefCell::new(BTreeMap::new()));
 
+/// Most devices kept track of, so that the statistics fit in the component's memory.
+const MAX_DEVICES: usize = 128;
+
+/// Bytes per device in the exported state: the address followed by the little-endian count.
+const STATE_ENTRY_LEN: usize = 6 + 8;
+
 impl core::fmt::Display for BdAddr {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
@@ -81,9 +89,13 @@ impl Default for BdAddr {
 }
 
 impl Guest for MyComponent {
-    fn on_single_report(addr: BdAddr) -> Result<(), ()> {
+    fn on_single_report(addr: BdAddr) -> Result<(), ReportError> {
         let mut addr_collection = SEEN.0.borrow_mut();
-        let count = addr_collection.entry(addr.into_inner()).or_default();
+        let key = addr.into_inner();
+        if addr_collection.len() >= MAX_DEVICES && !addr_collection.contains_key(&key) {
+            return Err(ReportError::CapacityExceeded);
+        }
+        let count = addr_collection.entry(key).or_default();
         if *count == 0 {
             let discovered = format!("discovered: {}", addr);
             info(&discovered);
@@ -101,6 +113,31 @@ impl Guest for MyComponent {
     }
 }
 
+impl StateTransferGuest for MyComponent {
+    fn export_state() -> Vec<u8> {
+        let mut state = Vec::new();
+        for (addr, count) in SEEN.0.borrow().iter() {
+            state.extend_from_slice(addr);
+            state.extend_from_slice(&count.to_le_bytes());
+        }
+        state
+    }
+
+    fn import_state(state: Vec<u8>) {
+        let mut addr_collection = SEEN.0.borrow_mut();
+        // Trailing bytes can only come from a different format; they are ignored
+        for entry in state.chunks_exact(STATE_ENTRY_LEN).take(MAX_DEVICES) {
+            let (addr, count) = entry.split_at(6);
+            addr_collection.insert(
+                addr.try_into().unwrap(),
+                u64::from_le_bytes(count.try_into().unwrap()),
+            );
+        }
+        let imported = format!("carried over {} devices", addr_collection.len());
+        info(&imported);
+    }
+}
+
 export!(MyComponent);
 
 #[panic_handler]
@@ -16,6 +16,7 @@ generate!({
     world: "example-ephemeral-with-bindings ",
     path: "../../wit",
     generate_all,
+    std_feature,
 });
 
 use ariel::wasm_bindings::log_api::info;
@@ -26,8 +27,8 @@ pub struct MyComponent;
 
 impl Guest for MyComponent {
     fn mess_with_temperature() {
-        trigger_measurements(Some(Category::Temperature)).unwrap();
-        for (sample, reading_channel) in wait_for_reading(Some(Label::Temperature)).unwrap() {
+        trigger_measurements(Some(&Category::Temperature)).unwrap();
+        for (sample, reading_channel) in wait_for_reading(Some(&Label::Temperature)).unwrap() {
             match reading_channel.label {
                 Label::Temperature => {}
                 _ => unreachable!(),
@@ -17,9 +17,10 @@ generate!({
     world: "example-gpio",
     path: "../../wit",
     generate_all,
+    std_feature,
 });
 
-use ariel::wasm_bindings::gpio_api::{toggle_led, wait_for_button_low};
+use ariel::wasm_bindings::gpio_api::{Input, Output};
 use ariel::wasm_bindings::log_api::info;
 use ariel::wasm_bindings::time_api::sleep;
 struct MyComponent;
@@ -27,10 +28,15 @@ struct MyComponent;
 impl Guest for MyComponent {
     fn blinky() -> () {
         info("In the capsule");
+        let led = Output::open("led0").unwrap();
+        let button = Input::open("button0").unwrap();
         loop {
             info("Waiting for a button to be pressed");
-            wait_for_button_low().unwrap();
-            toggle_led().unwrap();
+            if button.wait_for_low().is_err() {
+                info("Could not read the button");
+            } else if led.toggle().is_err() {
+                info("Could not toggle the LED");
+            }
             sleep(100);
         }
     }
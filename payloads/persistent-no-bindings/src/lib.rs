@@ -46,7 +46,7 @@ impl Guest for MyComponent {
         coap_run(code, observed_len, message)
     }
 
-    fn initialize_handler() -> Result<(), ()> {
+    fn initialize_handler() -> Result<(), CoapErr> {
         initialize_handler()
     }
 
@@ -96,7 +96,7 @@ fn coap_run(
 }
 
 #[define_opaque(HandlerType)]
-fn initialize_handler() -> Result<(), ()> {
+fn initialize_handler() -> Result<(), CoapErr> {
     match HANDLER.0.borrow_mut() {
         mut h if h.is_none() => {
             *h = Some(build_handler());
@@ -29,6 +29,7 @@ generate!({
     world: "example-persistent-with-bindings",
     path: "../../wit",
     generate_all,
+    std_feature,
 });
 
 use ariel::wasm_bindings::log_api::info;
@@ -51,7 +52,7 @@ impl Guest for MyComponent {
         coap_run(code, observed_len, message)
     }
 
-    fn initialize_handler() -> Result<(), ()> {
+    fn initialize_handler() -> Result<(), CoapErr> {
         initialize_handler()
     }
 
@@ -101,7 +102,7 @@ fn coap_run(
 }
 
 #[define_opaque(HandlerType)]
-fn initialize_handler() -> Result<(), ()> {
+fn initialize_handler() -> Result<(), CoapErr> {
     match HANDLER.0.borrow_mut() {
         mut h if h.is_none() => {
             *h = Some(build_handler());
@@ -146,8 +147,8 @@ fn report_resource() -> Result<Vec<String>, CoapErr> {
 }
 
 fn mess_with_temperature() -> String {
-    trigger_measurements(Some(Category::Temperature)).unwrap();
-    let (sample, reading_channel) = wait_for_reading(Some(Label::Temperature))
+    trigger_measurements(Some(&Category::Temperature)).unwrap();
+    let (sample, reading_channel) = wait_for_reading(Some(&Label::Temperature))
         .unwrap()
         .into_iter()
         .next()
@@ -13,31 +13,51 @@ generate!({
     world: "example-udp",
     path: "../../wit",
     generate_all,
+    std_feature,
 });
 
 use ariel::wasm_bindings::log_api::info;
 use ariel::wasm_bindings::udp_api::UdpSocket;
+
+use core::cell::RefCell;
+
 struct MyComponent;
 
+/// SAFETY: WASM is single threaded
+pub struct SendCell<T>(RefCell<T>);
+unsafe impl<T> Send for SendCell<T> {}
+unsafe impl<T> Sync for SendCell<T> {}
+
+static SOCKET: SendCell<Option<UdpSocket>> = SendCell(RefCell::new(None));
+
 impl Guest for MyComponent {
     fn bind_socket(port: u16) {
         info("Hello from inside the capsule");
-        UdpSocket::bind(port).unwrap();
+        let socket = UdpSocket::open().unwrap();
+        socket.bind(port).unwrap();
+        *SOCKET.0.borrow_mut() = Some(socket);
     }
 
     fn run() -> () {
-        match UdpSocket::try_recv() {
-            Ok(Some((data, endpoint))) => {
-                info("Received a packet, echoing it back");
-                UdpSocket::send(&data, endpoint).unwrap();
-            }
-            Ok(None) => {
-                // Not packet were ready to be received
-                // info("N");
-            }
-            Err(_) => {
-                info("Something's wrong with the network configuration");
-                panic!()
+        let socket = SOCKET.0.borrow();
+        let Some(socket) = socket.as_ref() else {
+            info("The socket was not bound before running");
+            panic!()
+        };
+        loop {
+            // Suspends the capsule until a packet arrives
+            match socket.recv(None) {
+                Ok(Some((data, endpoint))) => {
+                    info("Received a packet, echoing it back");
+                    socket.send(&data, endpoint).unwrap();
+                }
+                Ok(None) => {
+                    // Only happens on timeouts, which we don't use
+                }
+                Err(_) => {
+                    info("Something's wrong with the network configuration");
+                    panic!()
+                }
             }
         }
     }
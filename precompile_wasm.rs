@@ -69,6 +69,10 @@ struct Args {
     #[arg(short, long)]
     fuel: bool,
 
+    /// Turn epoch-based interruption on, a cheaper alternative to fuel for stopping runaway code
+    #[arg(short, long)]
+    epoch: bool,
+
     /// Override default opt-level
     #[arg(short = 'O', long = "opt-level", value_enum, default_value_t = CLIOptLevel::S)]
     opt_level: CLIOptLevel,
@@ -103,7 +107,7 @@ enum Error {
 fn main() -> miette::Result<()> {
     let args = Args::parse();
 
-    let Args { path, config, toolchain, additional, fuel, output, wasm_tools, module, opt_level, target, conserve } = args;
+    let Args { path, config, toolchain, additional, fuel, epoch, output, wasm_tools, module, opt_level, target, conserve } = args;
 
     // Check that the path exists
     assert!(fs::exists(&path).map_err(Error::from)?);
@@ -239,10 +243,10 @@ fn main() -> miette::Result<()> {
     }
 
     if !module {
-        precompile("temp.wasm", &target, fuel, out, module)?;
+        precompile("temp.wasm", &target, fuel, epoch, out, module)?;
         std::fs::remove_file("temp.wasm").map_err(Error::from)?;
     } else {
-        precompile(&new_path, &target, fuel, out, module)?;
+        precompile(&new_path, &target, fuel, epoch, out, module)?;
     }
     if std::fs::exists("temp").map_err(Error::from)? {
         std::fs::remove_dir_all("temp").map_err(Error::from)?;
@@ -251,7 +255,7 @@ fn main() -> miette::Result<()> {
     Ok(())
 }
 
-fn precompile<P: AsRef<Path>>(path: P, target: &str, fuel: bool, out: PathBuf, module: bool) -> miette::Result<()> {
+fn precompile<P: AsRef<Path>>(path: P, target: &str, fuel: bool, epoch: bool, out: PathBuf, module: bool) -> miette::Result<()> {
     std::println!("Precompiling Wasm Module/Component");
     let mut config = Config::new();
 
@@ -277,6 +281,10 @@ fn precompile<P: AsRef<Path>>(path: P, target: &str, fuel: bool, out: PathBuf, m
     // Enable fuel intstrumentation to prevent malevolent code from running indefinitely in the VM
     config.consume_fuel(fuel);
 
+    // Checks the engine's epoch at function entries and loop headers, which the runtime must also
+    // enable with `Config::epoch_interruption(true)`
+    config.epoch_interruption(epoch);
+
     if module { // Ensures that the runtime using this doesn't try to use the component Model
         config.wasm_component_model(false);
     }
@@ -18,4 +18,19 @@ cargo +nightly-2026-01-20 -Z script precompile_wasm.rs --path payloads/simple-up
 cp examples/simple-updates/*.cwasm examples/insecure-updates/
 cp examples/async-bindings/payload.cwasm examples/updatable-async/async-payload.cwasm
 
+# Images sent to examples that only accept signed updates
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs sign --path examples/insecure-updates/payload1.cwasm --key payloads/dev-signing.key --key-id 0 --version 1
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs sign --path examples/insecure-updates/payload2.cwasm --key payloads/dev-signing.key --key-id 0 --version 2
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs sign --path examples/updatable-async/async-payload.cwasm --key payloads/dev-signing.key --key-id 0 --version 2
+for f in examples/sandbox-no-bindings/fib-10.cwasm examples/sandbox-no-bindings/fib-30.cwasm
+do
+    cargo +nightly-2026-01-20 -Z script sign_capsule.rs sign --path ${f} --key payloads/dev-signing.key --key-id 0 --version 1
+done
+
+# The same as SUIT envelopes, for the device identities in the examples
+VENDOR=a3f8a89a-2d3a-56cc-8c2c-f46495242647
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs suit --path examples/insecure-updates/payload1.cwasm --key payloads/dev-signing.key --sequence-number 1 --vendor-id ${VENDOR} --class-id 09ae3995-4856-512e-a8b6-c5c5d5e84fe0
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs suit --path examples/insecure-updates/payload2.cwasm --key payloads/dev-signing.key --sequence-number 2 --vendor-id ${VENDOR} --class-id 09ae3995-4856-512e-a8b6-c5c5d5e84fe0
+cargo +nightly-2026-01-20 -Z script sign_capsule.rs suit --path examples/updatable-async/async-payload.cwasm --key payloads/dev-signing.key --sequence-number 2 --vendor-id ${VENDOR} --class-id 060c678e-3907-57a4-8b1c-23ba96d80da3
+
 cargo +nightly-2026-01-20 -Z script precompile_wasm.rs --path payloads/sensors/Cargo.toml -o examples/fake-sensor/payload.cwasm --config payloads/.cargo/config.toml --toolchain +nightly-2026-01-20
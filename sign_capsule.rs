@@ -0,0 +1,237 @@
+#!/usr/bin/env -S cargo +nightly -Zscript
+---cargo
+[package]
+edition = "2024"
+[dependencies]
+clap = { version = "4.5.40", features = ["derive"] }
+ed25519-dalek = { version = "2.1.1", features = ["rand_core"] }
+rand_core = { version = "0.6.4", features = ["getrandom"] }
+sha2 = { version = "0.10.8" }
+minicbor = { version = "2.2.2", features = ["std"] }
+miette = { version = "7.2.0", features = ["fancy"] }
+
+---
+//! Wraps precompiled capsules into the signed container checked by
+//! `ariel_os_bindings::wasm::signed::verify`, or into the SUIT envelope processed by
+//! `ariel_os_bindings::wasm::suit::process`.
+use std::{fs, path::{Path, PathBuf}};
+
+use clap::{Parser, Subcommand};
+use ed25519_dalek::{Signer, SigningKey};
+use miette::{IntoDiagnostic, WrapErr, bail};
+use minicbor::Encoder;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+const MAGIC: [u8; 4] = *b"trvm";
+const FORMAT: u8 = 1;
+const HEADER_LEN: usize = 128;
+const SIGNED_LEN: usize = 64;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a new signing key and print its public half for the firmware
+    Keygen {
+        /// Where to store the secret key
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Sign a precompiled capsule
+    Sign {
+        /// Path to the .cwasm file
+        #[arg(short, long)]
+        path: PathBuf,
+
+        /// Secret key created by `keygen`
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// Id of the key, as given to the firmware's `TrustedKey`
+        #[arg(short = 'i', long)]
+        key_id: u8,
+
+        /// Version of the capsule
+        #[arg(short, long)]
+        version: u32,
+
+        /// Output file, defaults to the input path with a `.signed` extension added
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Wrap a precompiled capsule into a signed SUIT envelope
+    Suit {
+        /// Path to the .cwasm file
+        #[arg(short, long)]
+        path: PathBuf,
+
+        /// Secret key created by `keygen`
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// Sequence number of the manifest, which serves as the capsule version
+        #[arg(short, long)]
+        sequence_number: u64,
+
+        /// Vendor identifier UUID of the targeted devices
+        #[arg(long)]
+        vendor_id: String,
+
+        /// Class identifier UUID of the targeted devices
+        #[arg(long)]
+        class_id: String,
+
+        /// Output file, defaults to the input path with a `.suit` extension added
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Key of the integrated payload in the envelope, and URI of the capsule in the manifest.
+const PAYLOAD_URI: &str = "#capsule";
+
+fn read_key(path: &Path) -> miette::Result<SigningKey> {
+    let secret = fs::read(path).into_diagnostic().wrap_err("Failed to read the key")?;
+    let Ok(secret) = <[u8; 32]>::try_from(secret.as_slice()) else {
+        bail!("{} is not an Ed25519 secret key", path.display());
+    };
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+fn parse_uuid(uuid: &str) -> miette::Result<[u8; 16]> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    let mut bytes = [0; 16];
+    if hex.len() != 32 || !hex.is_ascii() {
+        bail!("{uuid} is not a UUID");
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).into_diagnostic()?;
+    }
+    Ok(bytes)
+}
+
+/// Encodes a SUIT_Digest using SHA-256.
+fn suit_digest(bytes: &[u8]) -> Vec<u8> {
+    let mut e = Encoder::new(Vec::new());
+    e.array(2).unwrap().i64(-16).unwrap().bytes(&Sha256::digest(bytes)).unwrap();
+    e.into_writer()
+}
+
+fn suit_envelope(
+    key: &SigningKey,
+    sequence_number: u64,
+    vendor_id: &[u8; 16],
+    class_id: &[u8; 16],
+    payload: &[u8],
+) -> Vec<u8> {
+    // Shared sequence: set the parameters, then check vendor and class
+    let mut shared = Encoder::new(Vec::new());
+    shared.array(6).unwrap();
+    shared.u8(20).unwrap().map(5).unwrap();
+    shared.u8(1).unwrap().bytes(vendor_id).unwrap();
+    shared.u8(2).unwrap().bytes(class_id).unwrap();
+    shared.u8(3).unwrap().bytes(&suit_digest(payload)).unwrap();
+    shared.u8(14).unwrap().u64(payload.len() as u64).unwrap();
+    shared.u8(21).unwrap().str(PAYLOAD_URI).unwrap();
+    shared.u8(1).unwrap().u8(15).unwrap();
+    shared.u8(2).unwrap().u8(15).unwrap();
+
+    let mut common = Encoder::new(Vec::new());
+    common.map(2).unwrap();
+    common.u8(2).unwrap().array(1).unwrap().array(1).unwrap().bytes(b"capsule").unwrap();
+    common.u8(4).unwrap().bytes(&shared.into_writer()).unwrap();
+
+    // Install sequence: fetch the integrated payload and check it
+    let mut install = Encoder::new(Vec::new());
+    install.array(4).unwrap();
+    install.u8(21).unwrap().u8(2).unwrap();
+    install.u8(3).unwrap().u8(15).unwrap();
+
+    let mut manifest = Encoder::new(Vec::new());
+    manifest.map(4).unwrap();
+    manifest.u8(1).unwrap().u8(1).unwrap();
+    manifest.u8(2).unwrap().u64(sequence_number).unwrap();
+    manifest.u8(3).unwrap().bytes(&common.into_writer()).unwrap();
+    manifest.u8(17).unwrap().bytes(&install.into_writer()).unwrap();
+    let manifest = manifest.into_writer();
+    let manifest_digest = suit_digest(&manifest);
+
+    // COSE_Sign1 with EdDSA over the detached manifest digest
+    let mut protected = Encoder::new(Vec::new());
+    protected.map(1).unwrap().u8(1).unwrap().i64(-8).unwrap();
+    let protected = protected.into_writer();
+    let mut to_be_signed = Encoder::new(Vec::new());
+    to_be_signed.array(4).unwrap().str("Signature1").unwrap();
+    to_be_signed.bytes(&protected).unwrap().bytes(&[]).unwrap().bytes(&manifest_digest).unwrap();
+    let signature = key.sign(&to_be_signed.into_writer());
+    let mut cose = Encoder::new(Vec::new());
+    cose.array(4).unwrap().bytes(&protected).unwrap().map(0).unwrap().null().unwrap();
+    cose.bytes(&signature.to_bytes()).unwrap();
+
+    let mut authentication = Encoder::new(Vec::new());
+    authentication.array(2).unwrap().bytes(&manifest_digest).unwrap();
+    authentication.bytes(&cose.into_writer()).unwrap();
+
+    let mut envelope = Encoder::new(Vec::new());
+    envelope.tag(minicbor::data::Tag::new(107)).unwrap().map(3).unwrap();
+    envelope.u8(2).unwrap().bytes(&authentication.into_writer()).unwrap();
+    envelope.u8(3).unwrap().bytes(&manifest).unwrap();
+    envelope.str(PAYLOAD_URI).unwrap().bytes(payload).unwrap();
+    envelope.into_writer()
+}
+
+fn main() -> miette::Result<()> {
+    match Args::parse().command {
+        Command::Keygen { output } => {
+            if output.exists() {
+                bail!("{} already exists, not overwriting it", output.display());
+            }
+            let key = SigningKey::generate(&mut OsRng);
+            fs::write(&output, key.to_bytes()).into_diagnostic()?;
+            println!("Secret key written to {}", output.display());
+            println!("public_key: {:?}", key.verifying_key().to_bytes());
+        }
+        Command::Sign { path, key, key_id, version, output } => {
+            let key = read_key(&key)?;
+
+            let payload = fs::read(&path).into_diagnostic().wrap_err("Failed to read the capsule")?;
+            let Ok(len) = u32::try_from(payload.len()) else {
+                bail!("Capsule is too large");
+            };
+
+            let mut image = vec![0; HEADER_LEN];
+            image[0..4].copy_from_slice(&MAGIC);
+            image[4] = FORMAT;
+            image[5] = key_id;
+            image[8..12].copy_from_slice(&version.to_le_bytes());
+            image[12..16].copy_from_slice(&len.to_le_bytes());
+            image[16..48].copy_from_slice(&Sha256::digest(&payload));
+            let signature = key.sign(&image[..SIGNED_LEN]);
+            image[SIGNED_LEN..HEADER_LEN].copy_from_slice(&signature.to_bytes());
+            image.extend_from_slice(&payload);
+
+            let output = output.unwrap_or_else(|| path.with_added_extension("signed"));
+            fs::write(&output, image).into_diagnostic()?;
+            println!("Signed capsule version {version} written to {}", output.display());
+        }
+        Command::Suit { path, key, sequence_number, vendor_id, class_id, output } => {
+            let key = read_key(&key)?;
+            let vendor_id = parse_uuid(&vendor_id)?;
+            let class_id = parse_uuid(&class_id)?;
+            let payload = fs::read(&path).into_diagnostic().wrap_err("Failed to read the capsule")?;
+
+            let envelope = suit_envelope(&key, sequence_number, &vendor_id, &class_id, &payload);
+
+            let output = output.unwrap_or_else(|| path.with_added_extension("suit"));
+            fs::write(&output, envelope).into_diagnostic()?;
+            println!("SUIT envelope with sequence number {sequence_number} written to {}", output.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::ArielOSHost;
+
+use wasmtime::component::{Resource, ResourceTable, bindgen};
+
+bindgen!({
+    world: "ariel:wasm-bindings/adc",
+    path: "../../wit/",
+    with: {
+        "ariel:wasm-bindings/adc-api.adc-channel": AdcChannelHandle,
+    },
+    imports: { default: trappable },
+});
+
+pub use ariel::wasm_bindings::adc_api::{
+    AdcError, AdcReading, Host, HostAdcChannel, HostWithStore, add_to_linker,
+};
+
+/// Analog input that can be handed to capsules.
+///
+/// Ariel OS does not abstract over ADCs, so boards implement this on top of their HAL.
+pub trait AdcChannel: Send {
+    /// Performs a single conversion and returns the raw counts.
+    fn read(&mut self) -> u32;
+
+    /// Voltage corresponding to one count, in microvolts.
+    fn microvolts_per_count(&self) -> f32;
+}
+
+/// Host side of an `adc-channel` resource: index of the channel in [`ArielAdcHost::channels`].
+pub struct AdcChannelHandle(usize);
+
+#[derive(Default)]
+pub(crate) struct ArielAdcHost {
+    channels: Vec<(String, Box<dyn AdcChannel>)>,
+    table: ResourceTable,
+}
+
+impl Host for ArielAdcHost {}
+
+impl HostAdcChannel for ArielAdcHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<AdcChannelHandle>, AdcError>> {
+        let Some(index) = self.channels.iter().position(|(n, _)| *n == name) else {
+            return Ok(Err(AdcError::NotFound));
+        };
+        Ok(Ok(self.table.push(AdcChannelHandle(index))?))
+    }
+
+    fn read(&mut self, channel: Resource<AdcChannelHandle>) -> wasmtime::Result<AdcReading> {
+        let AdcChannelHandle(index) = *self.table.get(&channel)?;
+        let channel = &mut self.channels[index].1;
+        Ok(AdcReading {
+            raw: channel.read(),
+            microvolts_per_count: channel.microvolts_per_count(),
+        })
+    }
+
+    fn drop(&mut self, channel: Resource<AdcChannelHandle>) -> wasmtime::Result<()> {
+        self.table.delete(channel)?;
+        Ok(())
+    }
+}
+
+impl Host for ArielOSHost {}
+
+impl HostAdcChannel for ArielOSHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<AdcChannelHandle>, AdcError>> {
+        self.adc_host.open(name)
+    }
+
+    fn read(&mut self, channel: Resource<AdcChannelHandle>) -> wasmtime::Result<AdcReading> {
+        self.adc_host.read(channel)
+    }
+
+    fn drop(&mut self, channel: Resource<AdcChannelHandle>) -> wasmtime::Result<()> {
+        self.adc_host.drop(channel)
+    }
+}
+
+impl ArielOSHost {
+    /// Makes an analog input available to capsules under the given name.
+    pub fn bind_adc_channel(&mut self, name: &str, channel: impl AdcChannel + 'static) {
+        self.adc_host
+            .channels
+            .push((String::from(name), Box::new(channel)));
+    }
+}
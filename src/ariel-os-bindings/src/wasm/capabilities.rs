@@ -0,0 +1,226 @@
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use wasmtime::Engine;
+use wasmtime::component::{Component, HasSelf, InstancePre, Linker};
+
+use super::ArielOSHost;
+
+/// Package all the Ariel OS interfaces live in.
+const PACKAGE: &str = "ariel:wasm-bindings/";
+
+/// Host interface that can be granted to a capsule.
+///
+/// Only the interfaces enabled through cargo features are available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    #[cfg(feature = "log")]
+    Log,
+    #[cfg(feature = "time")]
+    Time,
+    #[cfg(feature = "rng")]
+    Rng,
+    #[cfg(feature = "udp")]
+    Udp,
+    #[cfg(feature = "gpio")]
+    Gpio,
+    #[cfg(feature = "i2c")]
+    I2c,
+    #[cfg(feature = "spi")]
+    Spi,
+    #[cfg(feature = "uart")]
+    Uart,
+    #[cfg(feature = "adc")]
+    Adc,
+    #[cfg(feature = "pwm")]
+    Pwm,
+    #[cfg(feature = "storage")]
+    Storage,
+    #[cfg(feature = "sensors")]
+    Sensors,
+}
+
+impl Capability {
+    /// Name of the WIT interface, without package and version.
+    pub const fn interface(self) -> &'static str {
+        match self {
+            #[cfg(feature = "log")]
+            Capability::Log => "log-api",
+            #[cfg(feature = "time")]
+            Capability::Time => "time-api",
+            #[cfg(feature = "rng")]
+            Capability::Rng => "rng-api",
+            #[cfg(feature = "udp")]
+            Capability::Udp => "udp-api",
+            #[cfg(feature = "gpio")]
+            Capability::Gpio => "gpio-api",
+            #[cfg(feature = "i2c")]
+            Capability::I2c => "i2c-api",
+            #[cfg(feature = "spi")]
+            Capability::Spi => "spi-api",
+            #[cfg(feature = "uart")]
+            Capability::Uart => "uart-api",
+            #[cfg(feature = "adc")]
+            Capability::Adc => "adc-api",
+            #[cfg(feature = "pwm")]
+            Capability::Pwm => "pwm-api",
+            #[cfg(feature = "storage")]
+            Capability::Storage => "storage-api",
+            #[cfg(feature = "sensors")]
+            Capability::Sensors => "sensors-api",
+        }
+    }
+
+    fn add_to_linker(self, linker: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        match self {
+            #[cfg(feature = "log")]
+            Capability::Log => super::log::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "time")]
+            Capability::Time => super::time::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "rng")]
+            Capability::Rng => super::rng::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "udp")]
+            Capability::Udp => super::udp::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "gpio")]
+            Capability::Gpio => super::gpio::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "i2c")]
+            Capability::I2c => super::i2c::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "spi")]
+            Capability::Spi => super::spi::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "uart")]
+            Capability::Uart => super::uart::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "adc")]
+            Capability::Adc => super::adc::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "pwm")]
+            Capability::Pwm => super::pwm::add_to_linker::<_, HasSelf<_>>(linker, |state| state),
+            #[cfg(feature = "storage")]
+            Capability::Storage => {
+                super::storage::add_to_linker::<_, HasSelf<_>>(linker, |state| state)
+            }
+            #[cfg(feature = "sensors")]
+            Capability::Sensors => {
+                super::sensors::add_to_linker::<_, HasSelf<_>>(linker, |state| state)
+            }
+        }
+    }
+}
+
+/// Error returned when a capsule imports an Ariel OS interface it was not granted.
+#[derive(Debug)]
+pub struct NotGranted {
+    /// Full name of the offending import, e.g. `ariel:wasm-bindings/udp-api@0.0.1`.
+    pub import: String,
+}
+
+impl core::fmt::Display for NotGranted {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "capsule imports `{}`, which was not granted to it",
+            self.import
+        )
+    }
+}
+
+impl core::error::Error for NotGranted {}
+
+/// Builds a [`CapsuleLinker`] that only provides the granted interfaces.
+#[derive(Debug, Default, Clone)]
+pub struct LinkerBuilder {
+    granted: Vec<Capability>,
+}
+
+impl LinkerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets capsules use the given interface.
+    pub fn grant(mut self, capability: Capability) -> Self {
+        if !self.granted.contains(&capability) {
+            self.granted.push(capability);
+        }
+        self
+    }
+
+    pub fn build(self, engine: &Engine) -> wasmtime::Result<CapsuleLinker> {
+        let mut linker = Linker::new(engine);
+        for capability in &self.granted {
+            capability.add_to_linker(&mut linker)?;
+        }
+        Ok(CapsuleLinker {
+            linker,
+            granted: self.granted,
+        })
+    }
+}
+
+/// A [`Linker`] restricted to a chosen subset of the Ariel OS interfaces.
+///
+/// This allows running third-party capsules with least privilege:
+///
+/// ```ignore
+/// let linker = CapsuleLinker::builder()
+///     .grant(Capability::Log)
+///     .grant(Capability::Time)
+///     .build(&engine)?;
+/// // Fails if the capsule imports e.g. `udp-api`
+/// linker.check(&component)?;
+/// ```
+pub struct CapsuleLinker {
+    linker: Linker<ArielOSHost>,
+    granted: Vec<Capability>,
+}
+
+impl CapsuleLinker {
+    pub fn builder() -> LinkerBuilder {
+        LinkerBuilder::new()
+    }
+
+    pub fn linker(&self) -> &Linker<ArielOSHost> {
+        &self.linker
+    }
+
+    /// Gives access to the linker, e.g. to define firmware-specific imports.
+    pub fn linker_mut(&mut self) -> &mut Linker<ArielOSHost> {
+        &mut self.linker
+    }
+
+    pub fn granted(&self) -> &[Capability] {
+        &self.granted
+    }
+
+    /// Makes sure the component only imports the Ariel OS interfaces that were granted.
+    ///
+    /// Imports from other packages are left to the linker.
+    pub fn check(&self, component: &Component) -> Result<(), NotGranted> {
+        let engine = self.linker.engine();
+        for (import, _) in component.component_type().imports(engine) {
+            let Some(interface) = import.strip_prefix(PACKAGE) else {
+                continue;
+            };
+            // Strip the version
+            let interface = interface.split('@').next().unwrap_or(interface);
+            if !self
+                .granted
+                .iter()
+                .any(|capability| capability.interface() == interface)
+            {
+                return Err(NotGranted {
+                    import: String::from(import),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the component's imports, then links it.
+    pub fn instantiate_pre(
+        &self,
+        component: &Component,
+    ) -> wasmtime::Result<InstancePre<ArielOSHost>> {
+        self.check(component)?;
+        self.linker.instantiate_pre(component)
+    }
+}
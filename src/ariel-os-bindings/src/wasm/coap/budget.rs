@@ -0,0 +1,57 @@
+//! Per-call fuel budgets and epoch deadlines, and the resource limiters of stores.
+//!
+//! Fuel only works with an engine created with `consume_fuel(true)`, running payloads that were
+//! precompiled with `--fuel`. Likewise, epoch deadlines need `epoch_interruption(true)` and
+//! payloads precompiled with `--epoch`, plus something incrementing the engine's epoch.
+
+use wasmtime::{ResourceLimiter, Store, Trap};
+
+/// Refills the store with the budget before a call into the capsule.
+///
+/// Leaves the store untouched when there is no budget.
+pub(crate) fn refuel<T>(store: &mut Store<T>, budget: Option<u64>) -> wasmtime::Result<()> {
+    if let Some(budget) = budget {
+        store.set_fuel(budget)?;
+    }
+    Ok(())
+}
+
+/// Fuel consumed since the last [`refuel`].
+pub(crate) fn consumed<T>(store: &Store<T>, budget: Option<u64>) -> Option<u64> {
+    let left = store.get_fuel().ok()?;
+    Some(budget?.saturating_sub(left))
+}
+
+/// Makes the capsule trap once the engine's epoch has been incremented `ticks` more times.
+pub(crate) fn arm_deadline<T>(store: &mut Store<T>, ticks: Option<u64>) {
+    if let Some(ticks) = ticks {
+        store.set_epoch_deadline(ticks);
+    }
+}
+
+/// Gets the resource limiter out of the store data, as taken by `Store::limiter`.
+pub(crate) type Limiter<T> = fn(&mut T) -> &mut dyn ResourceLimiter;
+
+/// Limiter of store data that bounds itself, as `ArielOSHost` does.
+pub(crate) fn own_limiter<T: ResourceLimiter>(data: &mut T) -> &mut dyn ResourceLimiter {
+    data
+}
+
+/// Bounds the resources the capsule may allocate in the store.
+///
+/// Leaves the store unbounded when there is no limiter.
+pub(crate) fn install_limiter<T>(store: &mut Store<T>, limiter: Option<Limiter<T>>) {
+    if let Some(limiter) = limiter {
+        store.limiter(limiter);
+    }
+}
+
+/// Whether a call into a capsule failed because it ran out of fuel.
+pub fn is_out_of_fuel(error: &wasmtime::Error) -> bool {
+    error.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel)
+}
+
+/// Whether a call into a capsule failed because it ran past its epoch deadline.
+pub fn is_interrupted(error: &wasmtime::Error) -> bool {
+    error.downcast_ref::<Trap>() == Some(&Trap::Interrupt)
+}
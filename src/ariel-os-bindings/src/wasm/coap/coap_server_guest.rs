@@ -1,9 +1,10 @@
 use core::fmt::Debug;
 
-use ariel_os_debug::log::defmt::Format;
+use ariel_os_debug::log::defmt::{Display2Format, Format};
+use ariel_os_debug::log::info;
 use wasmtime::{
-    Store,
-    component::{Component, Linker},
+    ResourceLimiter, Store,
+    component::{Component, Instance, Linker},
 };
 
 use coap_handler::{Attribute, Handler, Record, Reporting};
@@ -16,11 +17,20 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use super::budget::{Limiter, arm_deadline, consumed, install_limiter, own_limiter, refuel};
 pub use super::coap_traits::{CanInstantiate, EphemeralCapsule, PersistentCapsule};
+use crate::wasm::state::StateTransfer;
 
 enum WasmHandlerState<T: 'static, G> {
-    Running { store: Store<T>, instance: G },
-    NotRunning { store_data: T },
+    Running {
+        store: Store<T>,
+        instance: G,
+        /// Present if the capsule exports the `state-transfer-api` interface.
+        state_transfer: Option<StateTransfer>,
+    },
+    NotRunning {
+        store_data: T,
+    },
     // This is mainly used so we don't have to resort to take_mut tricks, and can process data from
     // one state into the next one.
     //
@@ -54,6 +64,16 @@ pub struct WasmHandler<T: 'static, G> {
     /// This needs to stay unchanged as long as an instance is `Some`; this is a guarantee used to
     /// satisfy the `Component::deserialize_raw` requirements.
     program: Vec<u8>,
+    /// Fuel the capsule gets for every call into it.
+    fuel_budget: Option<u64>,
+    /// Fuel consumed by the last call into the capsule.
+    fuel_consumed: Option<u64>,
+    /// Epoch ticks the capsule may run for on every call into it.
+    epoch_deadline: Option<u64>,
+    /// State exported by the last capsule on [`Self::stop`], until the next one imports it.
+    carried_state: Option<Vec<u8>>,
+    /// Bounds the resources of the capsule, see [`Self::with_limiter`].
+    limiter: Option<Limiter<T>>,
 }
 
 pub struct WasmHandlerWrapped<'w, T: 'static, G>(pub &'w core::cell::RefCell<WasmHandler<T, G>>);
@@ -64,15 +84,59 @@ impl<'w, T: 'static, G> Clone for WasmHandlerWrapped<'w, T, G> {
     }
 }
 
+impl<T: 'static + ResourceLimiter, G: CanInstantiate<T>> WasmHandler<T, G> {
+    /// Creates a handler whose capsules are bounded by the store data, e.g. by the
+    /// [`CapsuleLimits`](crate::wasm::CapsuleLimits) of an `ArielOSHost`.
+    ///
+    /// Handlers created with [`Self::new`] do not limit their capsules.
+    pub fn with_limiter(store_data: T) -> Self {
+        let mut handler = Self::new(store_data);
+        handler.limiter = Some(own_limiter::<T>);
+        handler
+    }
+}
+
 impl<T: 'static, G: CanInstantiate<T>> WasmHandler<T, G> {
     pub fn new(store_data: T) -> Self {
         WasmHandler {
             state: WasmHandlerState::NotRunning { store_data },
             program: Vec::new(),
             paths: Vec::new(),
+            fuel_budget: None,
+            fuel_consumed: None,
+            epoch_deadline: None,
+            carried_state: None,
+            limiter: None,
         }
     }
 
+    /// Limits how much fuel the capsule may consume per call: each run of an ephemeral capsule,
+    /// the startup of a persistent one, and each CoAP request it handles.
+    ///
+    /// The engine must be created with `consume_fuel(true)` and the payload precompiled with
+    /// `--fuel`. A capsule running out of fuel fails the call, which [`is_out_of_fuel`] detects;
+    /// CoAP requests then get a 5.03 response.
+    ///
+    /// [`is_out_of_fuel`]: super::is_out_of_fuel
+    pub fn set_fuel_budget(&mut self, budget: Option<u64>) {
+        self.fuel_budget = budget;
+    }
+
+    /// Fuel consumed by the last call into the capsule, if there is a fuel budget.
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        self.fuel_consumed
+    }
+
+    /// Stops calls into the capsule that run for longer than the given number of epoch ticks.
+    ///
+    /// The engine must be created with `epoch_interruption(true)`, the payload precompiled with
+    /// `--epoch`, and the epoch incremented, e.g. by `wasm::epoch::run_epoch_ticker`. Interrupted
+    /// instantiations fail with an error [`is_interrupted`](super::is_interrupted) detects, while
+    /// interrupted requests surface through the capsule's own error type.
+    pub fn set_epoch_deadline(&mut self, ticks: Option<u64>) {
+        self.epoch_deadline = ticks;
+    }
+
     /// Start running a CoAP server from 'static code (which is typically shipped with the firmware
     /// and resides in flash)
     ///
@@ -179,16 +243,16 @@ impl<T: 'static, G: CanInstantiate<T>> WasmHandler<T, G> {
             panic!("Starting from non-stopped state.");
         };
 
-        let mut store = Store::new(&engine, store_data);
-        let component = unsafe { Component::deserialize_raw(&engine, wasm)? };
-        let mut linker = Linker::<T>::new(&engine);
-        let mut instance = G::instantiate(&mut linker, &mut store, component)?;
-
-        let result = instance.run(&mut store);
+        let mut store = Store::new(engine, store_data);
+        install_limiter(&mut store, self.limiter);
+        // SAFETY: The requirements are forwarded.
+        let result = unsafe { self.instantiate(&mut store, wasm, engine) }
+            .and_then(|(mut instance, _)| instance.run(&mut store));
+        self.fuel_consumed = consumed(&store, self.fuel_budget);
         self.state = WasmHandlerState::NotRunning {
             store_data: store.into_data(),
         };
-        return result;
+        result
     }
 
     /// Starts running a CoAP server from a provided instance.
@@ -215,29 +279,103 @@ impl<T: 'static, G: CanInstantiate<T>> WasmHandler<T, G> {
             panic!("Starting from non-stopped state.");
         };
 
-        let mut store = Store::new(&engine, store_data);
-        let component = unsafe { Component::deserialize_raw(&engine, wasm)? };
-        let mut linker = Linker::<T>::new(&engine);
-        let mut instance = G::instantiate(&mut linker, &mut store, component)?;
-
-        instance.initialize_handler(&mut store).unwrap();
-        self.paths = instance
-            .report_resources(&mut store)
-            .map_err(|e| e.into())
-            .unwrap()
-            .into_iter()
-            .map(|s| StringRecord(s))
-            .collect();
-        self.state = WasmHandlerState::Running { store, instance };
+        let mut store = Store::new(engine, store_data);
+        install_limiter(&mut store, self.limiter);
+        // SAFETY: The requirements are forwarded.
+        let started = unsafe { self.instantiate(&mut store, wasm, engine) }.and_then(
+            |(mut instance, component_instance)| {
+                let state_transfer = StateTransfer::lookup(&mut store, &component_instance)?;
+                // Only cleared once started, so a failed start can be retried with the same state
+                if let Some(state) = &self.carried_state {
+                    match &state_transfer {
+                        Some(state_transfer) => {
+                            state_transfer.import_state(&mut store, state.clone())?
+                        }
+                        None => info!("New capsule does not import state, starting afresh"),
+                    }
+                }
+
+                instance.initialize_handler(&mut store)?;
+                let paths = instance
+                    .report_resources(&mut store)
+                    .map_err(|_| wasmtime::Error::msg("capsule failed to report its resources"))?;
+                Ok((instance, state_transfer, paths))
+            },
+        );
+        self.fuel_consumed = consumed(&store, self.fuel_budget);
+
+        let (instance, state_transfer, paths) = match started {
+            Ok(started) => started,
+            Err(e) => {
+                // Give the store data back, so the handler can be started again
+                self.state = WasmHandlerState::NotRunning {
+                    store_data: store.into_data(),
+                };
+                return Err(e);
+            }
+        };
+        self.carried_state = None;
+        self.paths = paths.into_iter().map(StringRecord).collect();
+        self.state = WasmHandlerState::Running {
+            store,
+            instance,
+            state_transfer,
+        };
 
         Ok(())
     }
 
+    /// Deserializes the capsule and instantiates it in `store`, with a fresh budget.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Self::start_raw`].
+    unsafe fn instantiate(
+        &self,
+        store: &mut Store<T>,
+        wasm: core::ptr::NonNull<[u8]>,
+        engine: &wasmtime::Engine,
+    ) -> wasmtime::Result<(G, Instance)> {
+        refuel(store, self.fuel_budget)?;
+        arm_deadline(store, self.epoch_deadline);
+        let component = unsafe { Component::deserialize_raw(engine, wasm)? };
+        let mut linker = Linker::<T>::new(engine);
+        G::instantiate(&mut linker, store, component)
+    }
+
+    /// Stops the capsule.
+    ///
+    /// If it exports the `state-transfer-api` interface, its state is exported first and handed to
+    /// the next capsule started, provided that one exports the interface too.
     pub fn stop(&mut self) {
+        if let WasmHandlerState::Running {
+            store,
+            state_transfer: Some(state_transfer),
+            ..
+        } = &mut self.state
+        {
+            // A capsule that ran out of fuel on its last request still gets to hand over its state
+            let exported = refuel(store, self.fuel_budget).and_then(|()| {
+                arm_deadline(store, self.epoch_deadline);
+                state_transfer.export_state(&mut *store)
+            });
+            self.carried_state = match exported {
+                Ok(state) => Some(state),
+                Err(e) => {
+                    info!("Failed to export capsule state: {}", Display2Format(&e));
+                    None
+                }
+            };
+        }
         self.state.stop();
         self.paths.clear();
     }
 
+    /// Drops the state exported by the last capsule, so the next one starts afresh.
+    pub fn discard_state(&mut self) {
+        self.carried_state = None;
+    }
+
     fn require_stopped(&self) -> Result<(), StopFirst> {
         match self.state {
             WasmHandlerState::Running { .. } => Err(StopFirst),
@@ -262,11 +400,9 @@ pub struct StopFirst;
 
 impl<'w, T: 'static, G: PersistentCapsule<T>> WasmHandlerWrapped<'w, T, G> {
     pub fn to_handler(self) -> impl Handler + Reporting {
-        let handler = new_dispatcher()
+        new_dispatcher()
             .below(&["vm"], self.clone())
-            .at(&["hello"], SimpleRendered("Hello from the host"));
-
-        return handler;
+            .at(&["hello"], SimpleRendered("Hello from the host"))
     }
 }
 
@@ -312,7 +448,9 @@ impl<'w, T: 'static, G: PersistentCapsule<T>> Handler for WasmHandlerWrapped<'w,
         let s = &mut *self.0.borrow_mut();
 
         match &mut s.state {
-            WasmHandlerState::Running { store, instance } => {
+            WasmHandlerState::Running {
+                store, instance, ..
+            } => {
                 let mut incoming_code: u8 = request.code().into();
                 // info!("HOST incoming request with payload {:?}", request.payload());
                 // for o in request.options() {
@@ -325,9 +463,19 @@ impl<'w, T: 'static, G: PersistentCapsule<T>> Handler for WasmHandlerWrapped<'w,
                 reencoded.set_from_message2(request).unwrap();
                 let incoming_len = reencoded.finish();
 
-                instance
-                    .coap_run(store, incoming_code, incoming_len as u32, buffer)
-                    .map_err(|e| e.into())
+                refuel(store, s.fuel_budget).map_err(|_| CoAPError::internal_server_error())?;
+                arm_deadline(store, s.epoch_deadline);
+                let result = instance.coap_run(store, incoming_code, incoming_len as u32, buffer);
+                s.fuel_consumed = consumed(store, s.fuel_budget);
+
+                result.map_err(|e| {
+                    // The capsule's own error type cannot tell, but an empty tank can
+                    if s.fuel_budget.is_some() && store.get_fuel().is_ok_and(|left| left == 0) {
+                        CoAPError::service_unavailable()
+                    } else {
+                        e.into()
+                    }
+                })
             }
             _other => Err(CoAPError::service_unavailable()),
         }
@@ -1,7 +1,7 @@
 extern crate alloc;
 use alloc::{string::String, vec::Vec};
 
-use wasmtime::component::{Component, Linker};
+use wasmtime::component::{Component, Instance, Linker};
 use wasmtime::{Result as wasm_result, Store};
 
 pub use coap_message_utils::Error as CoAPError;
@@ -34,13 +34,30 @@ pub trait PersistentCapsule<T>: CanInstantiate<T> {
 ///   this module have to use the single bindgen output anyway, and thus all the bindgen could move
 ///   into this module.)
 pub trait CanInstantiate<T> {
-    /// Runs Self::add_to_linker and Self::instantiate (which are bindgen generated methods without
-    /// a type)
+    /// Runs Self::add_to_linker (a bindgen generated method without a type), if the world has any
+    /// imports
+    fn link(linker: &mut Linker<T>) -> wasm_result<()>;
+
+    /// Runs Self::new (a bindgen generated method without a type)
+    fn from_instance(store: &mut Store<T>, instance: &Instance) -> wasm_result<Self>
+    where
+        Self: Sized;
+
+    /// Links and instantiates the component.
+    ///
+    /// The [`Instance`] is returned too, for looking up exports outside of the world such as
+    /// [`state-transfer-api`](crate::wasm::state).
     fn instantiate(
         linker: &mut Linker<T>,
         store: &mut Store<T>,
         component: Component,
-    ) -> wasm_result<Self>
+    ) -> wasm_result<(Self, Instance)>
     where
-        Self: Sized;
+        Self: Sized,
+    {
+        Self::link(linker)?;
+        let instance = linker.instantiate(&mut *store, &component)?;
+        let bindings = Self::from_instance(store, &instance)?;
+        Ok((bindings, instance))
+    }
 }
@@ -0,0 +1,324 @@
+//! A CoAP resource to update, stop and monitor a long-running capsule.
+
+use core::fmt::Write;
+use core::marker::PhantomData;
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use ariel_os_debug::log::info;
+
+use coap_handler::Handler;
+
+use coap_handler_implementations::helpers::block2_write;
+
+use coap_message::MessageOption;
+
+use coap_message_utils::Error as CoAPError;
+use coap_message_utils::OptionsExt;
+use coap_message_utils::option_value::Block2RequestData;
+
+#[cfg(feature = "signed")]
+use crate::wasm::signed::TrustedKey;
+#[cfg(feature = "suit")]
+use crate::wasm::suit::DeviceIdentity;
+
+#[cfg(feature = "signed")]
+use super::upload::Verifier;
+use super::upload::{BlockwiseUpload, Progress, UploadError};
+
+/// A capsule uploaded to a [`CapsuleManager`].
+#[derive(Debug)]
+pub struct ReceivedCapsule {
+    /// Version from the signed container or SUIT manifest; `None` if the manager is unverified.
+    pub version: Option<u64>,
+    /// The precompiled capsule, without any container or envelope around it.
+    pub code: Vec<u8>,
+}
+
+/// What a [`CapsuleManager`] reports on GET.
+#[derive(Debug, Clone, Default)]
+pub struct CapsuleStatus {
+    pub version: Option<u64>,
+    /// Size of the precompiled capsule in bytes.
+    pub size: usize,
+    pub running: bool,
+    /// Why the last capsule stopped or could not be started, e.g. because it trapped.
+    pub last_error: Option<String>,
+}
+
+impl core::fmt::Display for CapsuleStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.version {
+            Some(version) => writeln!(f, "version: {version}")?,
+            None => writeln!(f, "version: unknown")?,
+        }
+        writeln!(f, "size: {}", self.size)?;
+        writeln!(f, "running: {}", self.running)?;
+        match &self.last_error {
+            Some(error) => write!(f, "last error: {error}"),
+            None => write!(f, "last error: none"),
+        }
+    }
+}
+
+/// The part of the firmware that runs the capsule of a [`CapsuleManager`].
+///
+/// This is typically a handle to a task running the capsule, e.g. signalling it new capsules and
+/// reading the status it keeps up to date.
+pub trait CapsuleRunner {
+    /// Replaces the current capsule with a completely received (and verified) one.
+    fn install(&mut self, capsule: ReceivedCapsule);
+
+    /// Stops the current capsule.
+    fn stop(&mut self);
+
+    fn status(&self) -> CapsuleStatus;
+}
+
+/// A CoAP resource managing the capsule of a [`CapsuleRunner`].
+///
+/// * PUT uploads a new capsule, in blocks (Block1) if needed. Uploads announcing their size
+///   through Size1 are refused with 4.13 before their first block is stored if they are too large.
+/// * DELETE stops the capsule.
+/// * GET reports the [`CapsuleStatus`], and why the last upload was refused if it was.
+///
+/// ```ignore
+/// let manager = CapsuleManager::new(runner)
+///     .with_max_size(32 * 1024)
+///     .with_trusted_keys(TRUSTED_KEYS);
+/// let handler = new_dispatcher().at(&["vm-control"], manager);
+/// ```
+pub struct CapsuleManager<'a, C: CapsuleRunner> {
+    runner: C,
+    upload: BlockwiseUpload,
+    /// Checks uploads, unless the manager was created unverified and given no trusted keys.
+    #[cfg(feature = "signed")]
+    verifier: Option<Verifier<'a>>,
+    /// Why the last upload was refused, until one is accepted.
+    refused: Option<String>,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a, C: CapsuleRunner> CapsuleManager<'a, C> {
+    /// Creates a manager that only hands signed capsules to the runner.
+    #[cfg(feature = "signed")]
+    pub fn new(runner: C) -> Self {
+        // SAFETY: Verification is turned on before any upload.
+        let mut manager = unsafe { Self::new_unverified(runner) };
+        manager.verifier = Some(Verifier::default());
+        manager
+    }
+
+    /// Creates a manager that hands every upload to the runner without checking where it comes
+    /// from.
+    ///
+    /// # Safety
+    ///
+    /// Runners typically pass the capsules to [`wasmtime::component::Component::deserialize`], and
+    /// arbitrary data may execute arbitrary code. Only clients trusted to run code on the device
+    /// may be allowed to PUT, e.g. through the CoAP server's access policy. With the `signed`
+    /// feature, capsules are checked after all once [`Self::with_trusted_keys`] is called.
+    pub unsafe fn new_unverified(runner: C) -> Self {
+        Self {
+            runner,
+            upload: BlockwiseUpload::new(None),
+            #[cfg(feature = "signed")]
+            verifier: None,
+            refused: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Refuses uploads larger than `bytes`, container or envelope included, with 4.13.
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.upload.set_max_size(Some(bytes));
+        self
+    }
+
+    /// Only accepts capsules signed with one of these keys.
+    ///
+    /// Without any trusted key, every upload is refused with 4.03.
+    #[cfg(feature = "signed")]
+    pub fn with_trusted_keys(mut self, keys: &'a [TrustedKey]) -> Self {
+        self.verifier.get_or_insert_default().trusted_keys = keys;
+        self
+    }
+
+    /// Also accepts capsules in SUIT envelopes, if their manifest targets this device.
+    #[cfg(feature = "suit")]
+    pub fn with_device_identity(mut self, device: DeviceIdentity) -> Self {
+        self.verifier.get_or_insert_default().device = Some(device);
+        self
+    }
+
+    /// Checks a completely received upload and hands it to the runner.
+    fn install(&mut self) -> Result<(), CoAPError> {
+        #[cfg_attr(not(feature = "signed"), allow(unused_mut))]
+        let mut code = self.upload.take();
+
+        #[cfg(feature = "signed")]
+        let version = match &self.verifier {
+            Some(verifier) => {
+                let (version, range) = match verifier.verify(&code) {
+                    Ok((version, payload)) => (version, payload_range(&code, payload)),
+                    Err(reason) => {
+                        self.refused = Some(reason);
+                        return Err(CoAPError::forbidden());
+                    }
+                };
+                // Only keep the verified payload
+                code.truncate(range.end);
+                code.drain(..range.start);
+                Some(version)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "signed"))]
+        let version = None;
+
+        info!("Received capsule of {} bytes", code.len());
+        self.refused = None;
+        self.runner.install(ReceivedCapsule { version, code });
+        Ok(())
+    }
+}
+
+/// Where `payload` lies within `image`.
+#[cfg(feature = "signed")]
+fn payload_range(image: &[u8], payload: &[u8]) -> core::ops::Range<usize> {
+    let start = payload.as_ptr() as usize - image.as_ptr() as usize;
+    start..start + payload.len()
+}
+
+impl<C: CapsuleRunner> Handler for CapsuleManager<'_, C> {
+    // Code, Block1 option to respond with and block2 option of a status request
+    type RequestData = (u8, Option<u32>, Option<Block2RequestData>);
+
+    type ExtractRequestError = CoAPError;
+
+    type BuildResponseError<M: coap_message::MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: coap_message::ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        use coap_numbers::option::{BLOCK1, BLOCK2, SIZE1};
+
+        let mut block1: Option<u32> = None;
+        let mut size1: Option<u32> = None;
+        let mut block2: Option<Block2RequestData> = None;
+
+        request
+            .options()
+            .filter(|o| {
+                if o.number() == BLOCK1
+                    && block1.is_none()
+                    && let Some(n) = o.value_uint()
+                {
+                    block1 = Some(n);
+                    false
+                } else if o.number() == SIZE1
+                    && size1.is_none()
+                    && let Some(n) = o.value_uint()
+                {
+                    size1 = Some(n);
+                    false
+                } else if o.number() == BLOCK2
+                    && block2.is_none()
+                    && let Ok(n) = Block2RequestData::from_option(o)
+                {
+                    block2 = Some(n);
+                    false
+                } else {
+                    true
+                }
+            })
+            .ignore_elective_others()?;
+
+        match request.code().into() {
+            coap_numbers::code::PUT => {
+                match self.upload.receive(block1, size1, request.payload()) {
+                    Ok(Progress::More(block1)) => {
+                        Ok((coap_numbers::code::CONTINUE, Some(block1), None))
+                    }
+                    Ok(Progress::Complete(block1)) => {
+                        self.install()?;
+                        Ok((coap_numbers::code::CHANGED, Some(block1), None))
+                    }
+                    Err(UploadError::BadRequest) => Err(CoAPError::bad_request()),
+                    Err(e) => {
+                        if e == UploadError::TooLarge {
+                            self.refused = Some("capsule too large".to_string());
+                        }
+                        Ok((e.code(), None, None))
+                    }
+                }
+            }
+            coap_numbers::code::DELETE => {
+                info!("Stopping the capsule");
+                self.upload.clear();
+                self.runner.stop();
+                Ok((coap_numbers::code::DELETED, None, None))
+            }
+            coap_numbers::code::GET => Ok((
+                coap_numbers::code::CONTENT,
+                None,
+                Some(block2.unwrap_or_default()),
+            )),
+            _ => Err(CoAPError::method_not_allowed()),
+        }
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        1280 - 40 - 4
+    }
+
+    fn build_response<M: coap_message::MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        use coap_message::{Code, OptionNumber};
+
+        let (code, block1, block2) = request;
+
+        response.set_code(M::Code::new(code).map_err(CoAPError::from_unionerror)?);
+
+        if let Some(block1) = block1 {
+            response
+                .add_option_uint(
+                    M::OptionNumber::new(coap_numbers::option::BLOCK1)
+                        .map_err(CoAPError::from_unionerror)?,
+                    block1,
+                )
+                .map_err(CoAPError::from_unionerror)?;
+        }
+        // Tell the client how much it may upload (RFC 7959 Section 4)
+        if code == coap_numbers::code::REQUEST_ENTITY_TOO_LARGE
+            && let Some(max) = self.upload.max_size()
+        {
+            response
+                .add_option_uint(
+                    M::OptionNumber::new(coap_numbers::option::SIZE1)
+                        .map_err(CoAPError::from_unionerror)?,
+                    u32::try_from(max).unwrap_or(u32::MAX),
+                )
+                .map_err(CoAPError::from_unionerror)?;
+        }
+        if let Some(block2) = block2 {
+            let status = self.runner.status();
+            let refused = &self.refused;
+            block2_write(block2, response, |w| -> core::fmt::Result {
+                write!(w, "{status}")?;
+                if let Some(reason) = refused {
+                    write!(w, "\nrefused upload: {reason}")?;
+                }
+                Ok(())
+            })
+            .map_err(|_| CoAPError::internal_server_error())?;
+        }
+        Ok(())
+    }
+}
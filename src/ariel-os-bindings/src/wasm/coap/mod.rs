@@ -2,8 +2,18 @@ mod coap_traits;
 
 mod coap_server_guest;
 
+mod budget;
+
+pub use budget::{is_interrupted, is_out_of_fuel};
+
+mod upload;
+
 pub mod sanbdox;
 
 pub use sanbdox::Sandbox;
 
+pub mod manager;
+
+pub use manager::{CapsuleManager, CapsuleRunner, CapsuleStatus, ReceivedCapsule};
+
 pub use coap_server_guest::*;
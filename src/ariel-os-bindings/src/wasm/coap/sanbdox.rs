@@ -4,7 +4,6 @@ use core::marker::PhantomData;
 extern crate alloc;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
-use alloc::vec::Vec;
 
 use ariel_os_debug::log::info;
 
@@ -20,41 +19,173 @@ use coap_message_utils::OptionsExt;
 use coap_message_utils::option_value::Block2RequestData;
 
 use wasmtime::component::{Component, Linker};
-use wasmtime::{Engine, Store};
+use wasmtime::{Engine, ResourceLimiter, Store};
 
+#[cfg(feature = "signed")]
+use crate::wasm::signed::TrustedKey;
+#[cfg(feature = "suit")]
+use crate::wasm::suit::DeviceIdentity;
+
+use super::budget::{
+    Limiter, arm_deadline, consumed, install_limiter, is_interrupted, is_out_of_fuel, own_limiter,
+    refuel,
+};
 use super::coap_traits::EphemeralCapsule;
+#[cfg(feature = "signed")]
+use super::upload::Verifier;
+use super::upload::{BlockwiseUpload, Progress, UploadError, is_first_block};
 
 enum SandboxError {
     WebAssembly,
     NotFound,
+    OutOfFuel,
+    Interrupted,
+    #[cfg(feature = "signed")]
+    Unverified,
+}
+
+impl From<wasmtime::Error> for SandboxError {
+    fn from(error: wasmtime::Error) -> Self {
+        if is_out_of_fuel(&error) {
+            SandboxError::OutOfFuel
+        } else if is_interrupted(&error) {
+            SandboxError::Interrupted
+        } else {
+            SandboxError::WebAssembly
+        }
+    }
 }
 
 /// A Sandbox that instantiates, runs and then deletes simple wasm capsules
-pub struct Sandbox<'a, T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> {
+pub struct Sandbox<'a, T: 'static, R: Debug, G: EphemeralCapsule<T, R>> {
     instances: BTreeMap<String, (Store<T>, G)>,
     engine: &'a Engine,
+    /// Creates the store data of each new capsule.
+    new_host: fn() -> T,
+    /// Fuel each capsule gets for every run.
+    fuel_budget: Option<u64>,
+    /// Epoch ticks each capsule may run for.
+    epoch_deadline: Option<u64>,
+    /// Bounds the resources of each capsule, see [`Self::with_limiter`].
+    limiter: Option<Limiter<T>>,
+    /// Checks uploads, unless the sandbox was created unverified and given no trusted keys.
+    #[cfg(feature = "signed")]
+    verifier: Option<Verifier<'a>>,
     _marker: PhantomData<R>,
-    last_received_vector: Vec<u8>,
+    upload: BlockwiseUpload,
 }
 
-impl<'a, T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'a, T, R, G> {
-    /// Creates a Sandbox using the provided engine
-    pub fn new(engine: &'a Engine) -> Self {
+impl<'a, T: 'static, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'a, T, R, G> {
+    /// Creates a Sandbox using the provided engine, which only accepts signed capsules.
+    #[cfg(feature = "signed")]
+    pub fn new(engine: &'a Engine) -> Self
+    where
+        T: Default,
+    {
+        Self::with_host(engine, T::default)
+    }
+
+    /// Creates a Sandbox whose capsules each get the store data returned by `new_host`, e.g.
+    /// `|| ArielOSHost::with_limits(LIMITS)` to change the bounds [`Self::with_limiter`] applies.
+    ///
+    /// It only accepts signed capsules.
+    #[cfg(feature = "signed")]
+    pub fn with_host(engine: &'a Engine, new_host: fn() -> T) -> Self {
+        // SAFETY: Verification is turned on before any upload.
+        let mut sandbox = unsafe { Self::with_host_unverified(engine, new_host) };
+        sandbox.verifier = Some(Verifier::default());
+        sandbox
+    }
+
+    /// Like [`Self::with_host_unverified`], with the default store data.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Self::with_host_unverified`].
+    pub unsafe fn new_unverified(engine: &'a Engine) -> Self
+    where
+        T: Default,
+    {
+        // SAFETY: The requirements are forwarded.
+        unsafe { Self::with_host_unverified(engine, T::default) }
+    }
+
+    /// Creates a Sandbox that runs uploaded capsules without checking where they come from.
+    ///
+    /// # Safety
+    ///
+    /// Uploads are passed to [`wasmtime::component::Component::deserialize`] unchecked, and
+    /// arbitrary data may execute arbitrary code. Only clients trusted to run code on the device
+    /// may be allowed to PUT, e.g. through the CoAP server's access policy. With the `signed`
+    /// feature, capsules are checked after all once [`Self::with_trusted_keys`] is called.
+    pub unsafe fn with_host_unverified(engine: &'a Engine, new_host: fn() -> T) -> Self {
         Self {
             engine,
             instances: BTreeMap::new(),
+            new_host,
+            fuel_budget: None,
+            epoch_deadline: None,
+            limiter: None,
+            #[cfg(feature = "signed")]
+            verifier: None,
             _marker: PhantomData,
-            last_received_vector: Vec::new(),
+            upload: BlockwiseUpload::new(None),
         }
     }
 
+    /// Limits how much fuel a capsule may consume per run (and for its instantiation).
+    ///
+    /// The engine must be created with `consume_fuel(true)` and payloads precompiled with
+    /// `--fuel`. Runs that exhaust the budget get a 5.03 response.
+    pub fn with_fuel_budget(mut self, budget: u64) -> Self {
+        self.fuel_budget = Some(budget);
+        self
+    }
+
+    /// Stops capsule runs (and instantiations) that take longer than the given number of epoch
+    /// ticks, answering them with 5.03.
+    ///
+    /// The engine must be created with `epoch_interruption(true)`, payloads precompiled with
+    /// `--epoch`, and the epoch incremented, e.g. by `wasm::epoch::run_epoch_ticker`.
+    pub fn with_epoch_deadline(mut self, ticks: u64) -> Self {
+        self.epoch_deadline = Some(ticks);
+        self
+    }
+
+    /// Only accepts capsules signed with one of these keys.
+    ///
+    /// Without any trusted key, every upload is refused with 4.03.
+    #[cfg(feature = "signed")]
+    pub fn with_trusted_keys(mut self, keys: &'a [TrustedKey]) -> Self {
+        self.verifier.get_or_insert_default().trusted_keys = keys;
+        self
+    }
+
+    /// Also accepts capsules in SUIT envelopes, if their manifest targets this device.
+    #[cfg(feature = "suit")]
+    pub fn with_device_identity(mut self, device: DeviceIdentity) -> Self {
+        self.verifier.get_or_insert_default().device = Some(device);
+        self
+    }
+
+    /// Fuel consumed by the last run of the capsule at that path, if there is a fuel budget.
+    pub fn fuel_consumed(&self, uri_path: &str) -> Option<u64> {
+        let (store, _) = self.instances.get(uri_path)?;
+        consumed(store, self.fuel_budget)
+    }
+
     /// Looks up a capsule and executes it and returns the result
     fn execute_capsule(&mut self, uri_path: &str) -> Result<R, SandboxError> {
-        if let Some((store, instance)) = self.instances.get_mut(uri_path) {
-            instance.run(store).map_err(|_| SandboxError::WebAssembly)
-        } else {
-            Err(SandboxError::NotFound)
+        let Some((store, instance)) = self.instances.get_mut(uri_path) else {
+            return Err(SandboxError::NotFound);
+        };
+        refuel(store, self.fuel_budget).map_err(|_| SandboxError::WebAssembly)?;
+        arm_deadline(store, self.epoch_deadline);
+        let result = instance.run(store);
+        if let Some(fuel) = consumed(store, self.fuel_budget) {
+            info!("Capsule {} consumed {} fuel", uri_path, fuel);
         }
+        result.map_err(SandboxError::from)
     }
 
     /// Instantiates a capsule at the given path from the already present bytecode
@@ -62,18 +193,33 @@ impl<'a, T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'a,
     /// # Safety
     ///
     /// The requirements of [`wasmtime::Component::deserialize`] apply. (Paraphrasing: This needs
-    /// to be wasmtime prepared code; arbitrary data may execute arbitrary code).
+    /// to be wasmtime prepared code; arbitrary data may execute arbitrary code). This is ensured
+    /// by checking the signature of the bytecode, unless the sandbox was created unverified.
     unsafe fn instantiate_capsule(&mut self, uri_path: String) -> Result<(), SandboxError> {
+        #[cfg(feature = "signed")]
+        let code = match &self.verifier {
+            Some(verifier) => {
+                let (_, code) = verifier
+                    .verify(self.upload.received())
+                    .map_err(|_| SandboxError::Unverified)?;
+                code
+            }
+            None => self.upload.received(),
+        };
+        #[cfg(not(feature = "signed"))]
+        let code = self.upload.received();
+
+        let mut store = Store::new(self.engine, (self.new_host)());
+        install_limiter(&mut store, self.limiter);
+        refuel(&mut store, self.fuel_budget).map_err(|_| SandboxError::WebAssembly)?;
+        arm_deadline(&mut store, self.epoch_deadline);
         // SAFETY:
-        // * The requirement on code content is forwarded.
-        let mut store = Store::new(self.engine, T::default());
+        // * The requirement on code content is forwarded, or checked through the signature.
         let comp = unsafe {
-            Component::deserialize(self.engine, self.last_received_vector.as_slice())
-                .map_err(|_| SandboxError::WebAssembly)?
+            Component::deserialize(self.engine, code).map_err(|_| SandboxError::WebAssembly)?
         };
         let mut linker = Linker::new(self.engine);
-        let instance =
-            G::instantiate(&mut linker, &mut store, comp).map_err(|_| SandboxError::WebAssembly)?;
+        let (instance, _) = G::instantiate(&mut linker, &mut store, comp)?;
         self.instances.insert(uri_path, (store, instance));
         Ok(())
     }
@@ -82,79 +228,44 @@ impl<'a, T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'a,
         &mut self,
         uri_path: String,
         block1: Option<u32>,
+        size1: Option<u32>,
         payload: &[u8],
     ) -> Result<(Option<u32>, u8), CoAPError> {
-        // This is a bit of a simplification, but ignoring the block size and just
-        // appending is really kind'a fine IMO.
-        let block1 = block1.unwrap_or_default();
-
-        // FIXME there's probably a Size1 option; if so, reallocate to fail early.
-
-        let szx = block1 & 0x7;
-        if szx == 7 {
-            return Err(CoAPError::bad_request());
-        }
-
-        let blocksize = 1 << (4 + szx);
-        let m = block1 & 0x8 == 0x8;
-        let offset = (block1 >> 4) * blocksize;
-
         // Means that this is the first block of the body of a new capsule
-        if offset == 0 {
+        if is_first_block(block1) {
             // Remove the instance if there is one to avoid unecessary RAM usage
             let _ = self.instances.remove(&uri_path);
-            self.last_received_vector.truncate(0);
-        }
-
-        // If we had any of the content signed, we'd have to take care not to let any of
-        // the calculations truncate / overflow, lest someone might send a wrappingly large
-        // file that only after wrapping is malicious, but as long as all trust is in a
-        // single authenticated peer, this does not matter yet.
-        if self.last_received_vector.len() != offset as usize {
-            // FIXME: CoAPError should have such a constructor too (but there's no harm in
-            // returning an error through the Ok path).
-            return Ok((None, coap_numbers::code::REQUEST_ENTITY_INCOMPLETE));
-        }
-
-        // If this isn't the last block, the implied block size and received
-        // block size must be the same
-        if m && blocksize as usize != payload.len() {
-            return Ok((None, coap_numbers::code::REQUEST_ENTITY_INCOMPLETE));
         }
 
-        if self
-            .last_received_vector
-            .try_reserve(payload.len())
-            .is_err()
-        {
-            self.last_received_vector.truncate(0);
+        let block1 = match self.upload.receive(block1, size1, payload) {
+            // Transfer isn't complete yet
+            Ok(Progress::More(block1)) => return Ok((Some(block1), coap_numbers::code::CONTINUE)),
+            Ok(Progress::Complete(block1)) => block1,
+            Err(UploadError::BadRequest) => return Err(CoAPError::bad_request()),
             // FIXME: CoAPError should have such a constructor too (but there's no harm in
             // returning an error through the Ok path).
-            return Ok((None, coap_numbers::code::REQUEST_ENTITY_TOO_LARGE));
-        }
-
-        // Add the received bytes to the payload
-        self.last_received_vector.extend_from_slice(payload);
+            Err(e) => return Ok((None, e.code())),
+        };
 
-        if m {
-            // Transfer isn't complete yet
-            Ok((Some(block1), coap_numbers::code::CONTINUE))
-        } else {
-            // Transfer is done, instantiate the capsule and return
-            // SAFETY:
-            // * We trust our authenticated users
-            match unsafe { self.instantiate_capsule(uri_path) } {
-                Err(SandboxError::WebAssembly) => return Err(CoAPError::bad_request()),
-                Err(_) => unreachable!(),
-                Ok(_) => {
-                    info!(
-                        "Instantiated capsule based on program of {} bytes.",
-                        self.last_received_vector.len()
-                    );
-                    self.last_received_vector.truncate(0);
-                }
+        // Transfer is done, instantiate the capsule and return
+        // SAFETY:
+        // * Either the signature is checked, or the sandbox was created unverified, whose caller
+        //   vouched for everyone allowed to upload
+        let result = unsafe { self.instantiate_capsule(uri_path) };
+        let len = self.upload.received().len();
+        self.upload.clear();
+        match result {
+            Err(SandboxError::WebAssembly) => Err(CoAPError::bad_request()),
+            #[cfg(feature = "signed")]
+            Err(SandboxError::Unverified) => Err(CoAPError::forbidden()),
+            Err(SandboxError::OutOfFuel | SandboxError::Interrupted) => {
+                Err(CoAPError::service_unavailable())
+            }
+            Err(SandboxError::NotFound) => Err(CoAPError::not_found()),
+            Ok(_) => {
+                info!("Instantiated capsule based on program of {} bytes.", len);
+                Ok((Some(block1), coap_numbers::code::CREATED))
             }
-            Ok((Some(block1), coap_numbers::code::CREATED))
         }
     }
 
@@ -168,7 +279,18 @@ impl<'a, T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'a,
     }
 }
 
-impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sandbox<'_, T, R, G> {
+impl<T: 'static + ResourceLimiter, R: Debug, G: EphemeralCapsule<T, R>> Sandbox<'_, T, R, G> {
+    /// Bounds each capsule by its store data, e.g. by the
+    /// [`CapsuleLimits`](crate::wasm::CapsuleLimits) of an `ArielOSHost`.
+    ///
+    /// Without it, capsules may allocate as much memory as the heap has.
+    pub fn with_limiter(mut self) -> Self {
+        self.limiter = Some(own_limiter::<T>);
+        self
+    }
+}
+
+impl<T: 'static, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sandbox<'_, T, R, G> {
     // Block1 option to respond with, code and block2 option to respond with;
     type RequestData = (Option<u32>, u8, Option<(Block2RequestData, String)>);
 
@@ -180,10 +302,11 @@ impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sand
         &mut self,
         request: &M,
     ) -> Result<Self::RequestData, Self::ExtractRequestError> {
-        use coap_numbers::option::{BLOCK1, BLOCK2, URI_PATH};
+        use coap_numbers::option::{BLOCK1, BLOCK2, SIZE1, URI_PATH};
 
         // Process options once
         let mut block1: Option<u32> = None;
+        let mut size1: Option<u32> = None;
         let mut path: Option<String> = None;
         let mut block2: Option<Block2RequestData> = None;
 
@@ -202,6 +325,12 @@ impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sand
                 {
                     block1 = Some(n);
                     false
+                } else if o.number() == SIZE1
+                    && size1.is_none()
+                    && let Some(n) = o.value_uint()
+                {
+                    size1 = Some(n);
+                    false
                 } else if o.number() == BLOCK2
                     && block2.is_none()
                     && let Ok(n) = Block2RequestData::from_option(o)
@@ -225,7 +354,8 @@ impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sand
         match request.code().into() {
             // Request to instantiate a new capsule
             coap_numbers::code::PUT => {
-                let (b1opt, code) = self.process_put_request(path, block1, request.payload())?;
+                let (b1opt, code) =
+                    self.process_put_request(path, block1, size1, request.payload())?;
                 Ok((b1opt, code, None))
             }
             coap_numbers::code::GET => Ok((
@@ -270,6 +400,11 @@ impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sand
             let result = match self.execute_capsule(&path) {
                 Err(SandboxError::NotFound) => Err(CoAPError::not_found()),
                 Err(SandboxError::WebAssembly) => Err(CoAPError::internal_server_error()),
+                #[cfg(feature = "signed")]
+                Err(SandboxError::Unverified) => Err(CoAPError::internal_server_error()),
+                Err(SandboxError::OutOfFuel | SandboxError::Interrupted) => {
+                    Err(CoAPError::service_unavailable())
+                }
                 Ok(r) => Ok(r),
             }?;
             block2_write(block2, response, |w| {
@@ -280,7 +415,7 @@ impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Handler for Sand
     }
 }
 
-impl<T: 'static + Default, R: Debug, G: EphemeralCapsule<T, R>> Reporting for Sandbox<'_, T, R, G> {
+impl<T: 'static, R: Debug, G: EphemeralCapsule<T, R>> Reporting for Sandbox<'_, T, R, G> {
     type Record<'res>
         = StringRef<'res>
     where
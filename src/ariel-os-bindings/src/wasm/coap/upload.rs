@@ -0,0 +1,205 @@
+//! Reassembling and checking capsules uploaded with Block1 PUT requests.
+
+extern crate alloc;
+#[cfg(feature = "signed")]
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[cfg(feature = "signed")]
+use ariel_os_debug::log::{defmt::Display2Format, info};
+
+#[cfg(feature = "signed")]
+use crate::wasm::signed::{self, TrustedKey};
+#[cfg(feature = "suit")]
+use crate::wasm::suit::{self, DeviceIdentity};
+
+/// Where a Block1 upload stands after a request.
+pub(crate) enum Progress {
+    /// More blocks are expected; answer 2.31 with this Block1 option.
+    More(u32),
+    /// The upload is complete; answer with this Block1 option.
+    Complete(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UploadError {
+    /// Invalid Block1 option.
+    BadRequest,
+    /// The block is not the next one; the upload must start over.
+    Incomplete,
+    /// The capsule is larger than allowed, or than memory allows.
+    TooLarge,
+}
+
+impl UploadError {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            UploadError::BadRequest => coap_numbers::code::BAD_REQUEST,
+            UploadError::Incomplete => coap_numbers::code::REQUEST_ENTITY_INCOMPLETE,
+            UploadError::TooLarge => coap_numbers::code::REQUEST_ENTITY_TOO_LARGE,
+        }
+    }
+}
+
+/// Whether a request with this Block1 option starts a new upload.
+pub(crate) fn is_first_block(block1: Option<u32>) -> bool {
+    block1.unwrap_or_default() >> 4 == 0
+}
+
+/// A capsule being received block by block (RFC 7959).
+pub(crate) struct BlockwiseUpload {
+    received: Vec<u8>,
+    max_size: Option<usize>,
+}
+
+impl BlockwiseUpload {
+    pub(crate) fn new(max_size: Option<usize>) -> Self {
+        Self {
+            received: Vec::new(),
+            max_size,
+        }
+    }
+
+    pub(crate) fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    pub(crate) fn set_max_size(&mut self, max_size: Option<usize>) {
+        self.max_size = max_size;
+    }
+
+    /// Adds the payload of a PUT request with the given Block1 and Size1 options.
+    ///
+    /// The first block announcing its total size through Size1 is refused right away if that
+    /// size is too large, before anything is stored. On error, the upload is dropped.
+    pub(crate) fn receive(
+        &mut self,
+        block1: Option<u32>,
+        size1: Option<u32>,
+        payload: &[u8],
+    ) -> Result<Progress, UploadError> {
+        let result = self.append(block1, size1, payload);
+        if result.is_err() {
+            self.clear();
+        }
+        result
+    }
+
+    fn append(
+        &mut self,
+        block1: Option<u32>,
+        size1: Option<u32>,
+        payload: &[u8],
+    ) -> Result<Progress, UploadError> {
+        // This is a bit of a simplification, but ignoring the block size and just
+        // appending is really kind'a fine IMO.
+        let block1 = block1.unwrap_or_default();
+
+        let szx = block1 & 0x7;
+        if szx == 7 {
+            return Err(UploadError::BadRequest);
+        }
+
+        let blocksize = 1usize << (4 + szx);
+        let more = block1 & 0x8 == 0x8;
+        // Checked, lest a huge block number wraps around to an offset that looks right
+        let offset = ((block1 >> 4) as usize)
+            .checked_mul(blocksize)
+            .ok_or(UploadError::TooLarge)?;
+
+        if offset == 0 {
+            self.received.clear();
+            if let Some(size1) = size1 {
+                let size1 = size1 as usize;
+                if self.max_size.is_some_and(|max| size1 > max) {
+                    return Err(UploadError::TooLarge);
+                }
+                self.received
+                    .try_reserve_exact(size1)
+                    .map_err(|_| UploadError::TooLarge)?;
+            }
+        }
+        if self.received.len() != offset {
+            return Err(UploadError::Incomplete);
+        }
+        // If this isn't the last block, the implied block size and received
+        // block size must be the same
+        if more && blocksize != payload.len() {
+            return Err(UploadError::Incomplete);
+        }
+
+        let len = offset + payload.len();
+        if self.max_size.is_some_and(|max| len > max) {
+            return Err(UploadError::TooLarge);
+        }
+        self.received
+            .try_reserve(payload.len())
+            .map_err(|_| UploadError::TooLarge)?;
+        self.received.extend_from_slice(payload);
+
+        if more {
+            Ok(Progress::More(block1))
+        } else {
+            Ok(Progress::Complete(block1))
+        }
+    }
+
+    pub(crate) fn received(&self) -> &[u8] {
+        &self.received
+    }
+
+    /// Hands out the received bytes, leaving the upload empty.
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.received)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.received = Vec::new();
+    }
+}
+
+/// Checks uploaded images, either signed containers or SUIT envelopes.
+#[cfg(feature = "signed")]
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Verifier<'a> {
+    /// Keys uploaded capsules must be signed with.
+    pub(crate) trusted_keys: &'a [TrustedKey],
+    /// Identity SUIT manifests must target; without it, only signed containers are accepted.
+    #[cfg(feature = "suit")]
+    pub(crate) device: Option<DeviceIdentity>,
+}
+
+#[cfg(feature = "signed")]
+impl Verifier<'_> {
+    /// Returns the version and the capsule contained in `image`, or why it was refused.
+    pub(crate) fn verify<'i>(&self, image: &'i [u8]) -> Result<(u64, &'i [u8]), String> {
+        #[cfg(feature = "suit")]
+        if let Some(device) = &self.device {
+            return match suit::verify_update(image, self.trusted_keys, device) {
+                Ok(update) => {
+                    info!("Capsule version {}", update.version());
+                    Ok((update.version(), update.payload()))
+                }
+                Err(e) => {
+                    info!("Refusing capsule: {}", Display2Format(&e));
+                    Err(e.to_string())
+                }
+            };
+        }
+
+        match signed::verify(image, self.trusted_keys) {
+            Ok(capsule) => {
+                info!(
+                    "Capsule version {} signed by key {}",
+                    capsule.version(),
+                    capsule.key_id()
+                );
+                Ok((capsule.version().into(), capsule.payload()))
+            }
+            Err(e) => {
+                info!("Refusing capsule: {}", Display2Format(&e));
+                Err(e.to_string())
+            }
+        }
+    }
+}
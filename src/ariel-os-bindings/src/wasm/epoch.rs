@@ -0,0 +1,36 @@
+//! Drives epoch-based interruption from an embassy timer.
+//!
+//! Epoch interruption is a cheaper alternative to fuel for stopping runaway capsules: instead of
+//! counting instructions, the compiled code only checks the engine's epoch at function entries and
+//! loop headers. Capsules then trap once the epoch passes the deadline of their store, see
+//! `Store::set_epoch_deadline`.
+
+use ariel_os_embassy::api::time::{Duration, Ticker};
+use wasmtime::Engine;
+
+/// Increments the epoch of `engine` every `period`, forever.
+///
+/// The engine must be created with `epoch_interruption(true)`, and payloads precompiled with
+/// `--epoch`. A store deadline of `n` ticks then stops a capsule after roughly `n * period`.
+///
+/// This must not run on the executor the capsules run on: a synchronous capsule stuck in a loop
+/// keeps every other task of its executor from running, so the epoch would never advance. Run it
+/// in a thread with a higher priority than the executor thread (`executor-thread` laze module)
+/// instead, as the sandbox example does:
+///
+/// ```ignore
+/// #[ariel_os::thread(autostart, priority = 10)]
+/// fn epoch_ticker() {
+///     ariel_os::blocker::block_on(async {
+///         let engine = ENGINE.get().await.clone();
+///         run_epoch_ticker(engine, Duration::from_millis(10)).await
+///     })
+/// }
+/// ```
+pub async fn run_epoch_ticker(engine: Engine, period: Duration) -> ! {
+    let mut ticker = Ticker::every(period);
+    loop {
+        ticker.next().await;
+        engine.increment_epoch();
+    }
+}
@@ -1,54 +1,399 @@
 extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::future::Future;
+use core::pin::Pin;
 
 use super::ArielOSHost;
-use ariel_os_hal::gpio::{IntEnabledInput, Output};
+use ariel_os_hal::gpio::IntEnabledInput;
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin, StatefulOutputPin};
 
-use wasmtime::component::bindgen;
+use wasmtime::component::{Resource, ResourceTable, bindgen};
 
 bindgen!({
     world: "ariel:wasm-bindings/gpio",
     path: "../../wit/",
+    with: {
+        "ariel:wasm-bindings/gpio-api.output": OutputHandle,
+        "ariel:wasm-bindings/gpio-api.input": InputHandle,
+    },
     imports: {
-        "ariel:wasm-bindings/gpio-api.wait-for-button-low": async
+        "ariel:wasm-bindings/gpio-api.[method]input.wait-for-high": async | trappable,
+        "ariel:wasm-bindings/gpio-api.[method]input.wait-for-low": async | trappable,
+        "ariel:wasm-bindings/gpio-api.[method]input.wait-for-rising-edge": async | trappable,
+        "ariel:wasm-bindings/gpio-api.[method]input.wait-for-falling-edge": async | trappable,
+        "ariel:wasm-bindings/gpio-api.[method]input.wait-for-any-edge": async | trappable,
+        default: trappable,
     }
 });
 
-pub use ariel::wasm_bindings::gpio_api::{Host, HostWithStore, add_to_linker};
+pub use ariel::wasm_bindings::gpio_api::{
+    GpioError, Host, HostInput, HostOutput, HostWithStore, add_to_linker,
+};
+
+/// An input pin that capsules can read and wait on.
+///
+/// Implemented for Ariel OS's interrupt-enabled inputs. Other pins, e.g. virtual ones in tests,
+/// can be bound by implementing it too. Errors are reported to the capsule as `hardware`.
+pub trait GpioInput: ErrorType + Send + 'static {
+    fn is_high(&mut self) -> Result<bool, Self::Error>;
+
+    fn wait_for_high(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn wait_for_low(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn wait_for_rising_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn wait_for_falling_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    fn wait_for_any_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+impl GpioInput for IntEnabledInput {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        InputPin::is_high(self)
+    }
+
+    fn wait_for_high(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let wait = IntEnabledInput::wait_for_high(self);
+        async move {
+            wait.await;
+            Ok(())
+        }
+    }
+
+    fn wait_for_low(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let wait = IntEnabledInput::wait_for_low(self);
+        async move {
+            wait.await;
+            Ok(())
+        }
+    }
+
+    fn wait_for_rising_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let wait = IntEnabledInput::wait_for_rising_edge(self);
+        async move {
+            wait.await;
+            Ok(())
+        }
+    }
+
+    fn wait_for_falling_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let wait = IntEnabledInput::wait_for_falling_edge(self);
+        async move {
+            wait.await;
+            Ok(())
+        }
+    }
+
+    fn wait_for_any_edge(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let wait = IntEnabledInput::wait_for_any_edge(self);
+        async move {
+            wait.await;
+            Ok(())
+        }
+    }
+}
+
+type PinFuture<'a> = Pin<Box<dyn Future<Output = Result<(), GpioError>> + Send + 'a>>;
+
+/// Any error of a pin driver, as the capsule sees it.
+fn pin_failed<E>(_: E) -> GpioError {
+    GpioError::Hardware
+}
+
+/// Object-safe view of a [`GpioInput`], so inputs of different types can be bound together.
+trait DynInput: Send {
+    fn is_high(&mut self) -> Result<bool, GpioError>;
+
+    fn wait_for_high(&mut self) -> PinFuture<'_>;
+
+    fn wait_for_low(&mut self) -> PinFuture<'_>;
+
+    fn wait_for_rising_edge(&mut self) -> PinFuture<'_>;
+
+    fn wait_for_falling_edge(&mut self) -> PinFuture<'_>;
+
+    fn wait_for_any_edge(&mut self) -> PinFuture<'_>;
+}
+
+impl<P: GpioInput> DynInput for P {
+    fn is_high(&mut self) -> Result<bool, GpioError> {
+        GpioInput::is_high(self).map_err(pin_failed)
+    }
+
+    fn wait_for_high(&mut self) -> PinFuture<'_> {
+        let wait = GpioInput::wait_for_high(self);
+        Box::pin(async move { wait.await.map_err(pin_failed) })
+    }
+
+    fn wait_for_low(&mut self) -> PinFuture<'_> {
+        let wait = GpioInput::wait_for_low(self);
+        Box::pin(async move { wait.await.map_err(pin_failed) })
+    }
+
+    fn wait_for_rising_edge(&mut self) -> PinFuture<'_> {
+        let wait = GpioInput::wait_for_rising_edge(self);
+        Box::pin(async move { wait.await.map_err(pin_failed) })
+    }
+
+    fn wait_for_falling_edge(&mut self) -> PinFuture<'_> {
+        let wait = GpioInput::wait_for_falling_edge(self);
+        Box::pin(async move { wait.await.map_err(pin_failed) })
+    }
+
+    fn wait_for_any_edge(&mut self) -> PinFuture<'_> {
+        let wait = GpioInput::wait_for_any_edge(self);
+        Box::pin(async move { wait.await.map_err(pin_failed) })
+    }
+}
+
+/// Object-safe view of an embedded-hal output pin, so outputs of different types can be bound
+/// together.
+trait DynOutput: Send {
+    fn set_high(&mut self) -> Result<(), GpioError>;
+
+    fn set_low(&mut self) -> Result<(), GpioError>;
+
+    fn toggle(&mut self) -> Result<(), GpioError>;
+
+    fn is_set_high(&mut self) -> Result<bool, GpioError>;
+}
+
+impl<P: StatefulOutputPin + Send> DynOutput for P {
+    fn set_high(&mut self) -> Result<(), GpioError> {
+        OutputPin::set_high(self).map_err(pin_failed)
+    }
+
+    fn set_low(&mut self) -> Result<(), GpioError> {
+        OutputPin::set_low(self).map_err(pin_failed)
+    }
+
+    fn toggle(&mut self) -> Result<(), GpioError> {
+        StatefulOutputPin::toggle(self).map_err(pin_failed)
+    }
+
+    fn is_set_high(&mut self) -> Result<bool, GpioError> {
+        StatefulOutputPin::is_set_high(self).map_err(pin_failed)
+    }
+}
+
+/// Host side of an `output` resource: index of the pin in [`ArielGpioHost::outputs`].
+pub struct OutputHandle(usize);
+
+/// Host side of an `input` resource: index of the pin in [`ArielGpioHost::inputs`].
+pub struct InputHandle(usize);
 
 #[derive(Default)]
 pub(crate) struct ArielGpioHost {
-    pub(crate) led: Option<Output>,
-    pub(crate) button: Option<IntEnabledInput>,
+    outputs: Vec<(String, Box<dyn DynOutput>)>,
+    inputs: Vec<(String, Box<dyn DynInput>)>,
+    table: ResourceTable,
 }
 
-impl Host for ArielGpioHost {
-    fn toggle_led(&mut self) -> Result<(), ()> {
-        self.led.as_mut().map(|led| led.toggle()).ok_or(())
+impl ArielGpioHost {
+    fn output(&mut self, pin: &Resource<OutputHandle>) -> wasmtime::Result<&mut dyn DynOutput> {
+        let OutputHandle(index) = *self.table.get(pin)?;
+        Ok(self.outputs[index].1.as_mut())
     }
 
-    async fn wait_for_button_low(&mut self) -> Result<(), ()> {
-        match self.button.as_mut() {
-            Some(b) => Ok(b.wait_for_low().await),
-            None => Err(()),
-        }
+    fn input(&mut self, pin: &Resource<InputHandle>) -> wasmtime::Result<&mut dyn DynInput> {
+        let InputHandle(index) = *self.table.get(pin)?;
+        Ok(self.inputs[index].1.as_mut())
+    }
+}
+
+impl Host for ArielGpioHost {}
+
+impl HostOutput for ArielGpioHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<OutputHandle>, GpioError>> {
+        let Some(index) = self.outputs.iter().position(|(n, _)| *n == name) else {
+            return Ok(Err(GpioError::NotFound));
+        };
+        Ok(Ok(self.table.push(OutputHandle(index))?))
+    }
+
+    fn set_high(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.output(&pin)?.set_high())
+    }
+
+    fn set_low(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.output(&pin)?.set_low())
+    }
+
+    fn toggle(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.output(&pin)?.toggle())
+    }
+
+    fn is_high(
+        &mut self,
+        pin: Resource<OutputHandle>,
+    ) -> wasmtime::Result<Result<bool, GpioError>> {
+        Ok(self.output(&pin)?.is_set_high())
+    }
+
+    fn drop(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<()> {
+        self.table.delete(pin)?;
+        Ok(())
+    }
+}
+
+impl HostInput for ArielGpioHost {
+    fn open(&mut self, name: String) -> wasmtime::Result<Result<Resource<InputHandle>, GpioError>> {
+        let Some(index) = self.inputs.iter().position(|(n, _)| *n == name) else {
+            return Ok(Err(GpioError::NotFound));
+        };
+        Ok(Ok(self.table.push(InputHandle(index))?))
+    }
+
+    fn is_high(&mut self, pin: Resource<InputHandle>) -> wasmtime::Result<Result<bool, GpioError>> {
+        Ok(self.input(&pin)?.is_high())
+    }
+
+    async fn wait_for_high(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.input(&pin)?.wait_for_high().await)
+    }
+
+    async fn wait_for_low(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.input(&pin)?.wait_for_low().await)
+    }
+
+    async fn wait_for_rising_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.input(&pin)?.wait_for_rising_edge().await)
+    }
+
+    async fn wait_for_falling_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.input(&pin)?.wait_for_falling_edge().await)
+    }
+
+    async fn wait_for_any_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        Ok(self.input(&pin)?.wait_for_any_edge().await)
+    }
+
+    fn drop(&mut self, pin: Resource<InputHandle>) -> wasmtime::Result<()> {
+        self.table.delete(pin)?;
+        Ok(())
     }
 }
 
-impl Host for ArielOSHost {
-    fn toggle_led(&mut self) -> Result<(), ()> {
-        self.gpio_host.toggle_led()
+impl Host for ArielOSHost {}
+
+impl HostOutput for ArielOSHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<OutputHandle>, GpioError>> {
+        HostOutput::open(&mut self.gpio_host, name)
+    }
+
+    fn set_high(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.set_high(pin)
+    }
+
+    fn set_low(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.set_low(pin)
+    }
+
+    fn toggle(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.toggle(pin)
+    }
+
+    fn is_high(
+        &mut self,
+        pin: Resource<OutputHandle>,
+    ) -> wasmtime::Result<Result<bool, GpioError>> {
+        HostOutput::is_high(&mut self.gpio_host, pin)
     }
 
-    async fn wait_for_button_low(&mut self) -> Result<(), ()> {
-        self.gpio_host.wait_for_button_low().await
+    fn drop(&mut self, pin: Resource<OutputHandle>) -> wasmtime::Result<()> {
+        HostOutput::drop(&mut self.gpio_host, pin)
+    }
+}
+
+impl HostInput for ArielOSHost {
+    fn open(&mut self, name: String) -> wasmtime::Result<Result<Resource<InputHandle>, GpioError>> {
+        HostInput::open(&mut self.gpio_host, name)
+    }
+
+    fn is_high(&mut self, pin: Resource<InputHandle>) -> wasmtime::Result<Result<bool, GpioError>> {
+        HostInput::is_high(&mut self.gpio_host, pin)
+    }
+
+    async fn wait_for_high(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.wait_for_high(pin).await
+    }
+
+    async fn wait_for_low(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.wait_for_low(pin).await
+    }
+
+    async fn wait_for_rising_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.wait_for_rising_edge(pin).await
+    }
+
+    async fn wait_for_falling_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.wait_for_falling_edge(pin).await
+    }
+
+    async fn wait_for_any_edge(
+        &mut self,
+        pin: Resource<InputHandle>,
+    ) -> wasmtime::Result<Result<(), GpioError>> {
+        self.gpio_host.wait_for_any_edge(pin).await
+    }
+
+    fn drop(&mut self, pin: Resource<InputHandle>) -> wasmtime::Result<()> {
+        HostInput::drop(&mut self.gpio_host, pin)
     }
 }
 
 impl ArielOSHost {
-    pub fn bind_led(&mut self, led: Output) {
-        self.gpio_host.led = Some(led);
+    /// Makes an output pin available to capsules under the given name.
+    ///
+    /// Errors of the pin are reported to the capsule as `hardware`.
+    pub fn bind_output(&mut self, name: &str, pin: impl StatefulOutputPin + Send + 'static) {
+        self.gpio_host
+            .outputs
+            .push((String::from(name), Box::new(pin)));
     }
-    pub fn bind_button(&mut self, button: IntEnabledInput) {
-        self.gpio_host.button = Some(button);
+
+    /// Makes an input pin available to capsules under the given name.
+    pub fn bind_input(&mut self, name: &str, pin: impl GpioInput) {
+        self.gpio_host
+            .inputs
+            .push((String::from(name), Box::new(pin)));
     }
 }
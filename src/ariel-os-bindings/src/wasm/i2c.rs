@@ -0,0 +1,121 @@
+extern crate alloc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::ArielOSHost;
+use ariel_os_embassy::i2c::controller::I2cDevice;
+
+use embedded_hal_async::i2c::{Error as _, ErrorKind, I2c as _};
+
+use wasmtime::component::bindgen;
+
+bindgen!({
+    world: "ariel:wasm-bindings/i2c",
+    path: "../../wit/",
+    imports: { default: async },
+});
+
+pub use ariel::wasm_bindings::i2c_api::{Host, HostWithStore, I2cError, add_to_linker};
+
+/// Longest single read or write a capsule may request.
+pub const MAX_TRANSFER_LEN: usize = 256;
+
+impl From<ErrorKind> for I2cError {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Bus => I2cError::Bus,
+            ErrorKind::ArbitrationLoss => I2cError::ArbitrationLoss,
+            ErrorKind::NoAcknowledge(_) => I2cError::NoAcknowledge,
+            ErrorKind::Overrun => I2cError::Overrun,
+            _ => I2cError::Other,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ArielI2cHost {
+    device: Option<I2cDevice>,
+    allowed_addresses: Vec<u8>,
+}
+
+impl ArielI2cHost {
+    /// Returns the bus if the capsule may talk to that address.
+    fn device_for(&mut self, address: u8) -> Result<&mut I2cDevice, I2cError> {
+        if !self.allowed_addresses.contains(&address) {
+            return Err(I2cError::AddressNotAllowed);
+        }
+        self.device.as_mut().ok_or(I2cError::NotBound)
+    }
+}
+
+fn check_len(len: usize) -> Result<(), I2cError> {
+    if len > MAX_TRANSFER_LEN {
+        return Err(I2cError::TransferTooLong);
+    }
+    Ok(())
+}
+
+impl Host for ArielI2cHost {
+    async fn read(&mut self, address: u8, len: u32) -> Result<Vec<u8>, I2cError> {
+        check_len(len as usize)?;
+        let mut buffer = vec![0; len as usize];
+        self.device_for(address)?
+            .read(address, &mut buffer)
+            .await
+            .map_err(|e| e.kind())?;
+        Ok(buffer)
+    }
+
+    async fn write(&mut self, address: u8, data: Vec<u8>) -> Result<(), I2cError> {
+        check_len(data.len())?;
+        self.device_for(address)?
+            .write(address, &data)
+            .await
+            .map_err(|e| e.kind().into())
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        data: Vec<u8>,
+        len: u32,
+    ) -> Result<Vec<u8>, I2cError> {
+        check_len(data.len())?;
+        check_len(len as usize)?;
+        let mut buffer = vec![0; len as usize];
+        self.device_for(address)?
+            .write_read(address, &data, &mut buffer)
+            .await
+            .map_err(|e| e.kind())?;
+        Ok(buffer)
+    }
+}
+
+impl Host for ArielOSHost {
+    async fn read(&mut self, address: u8, len: u32) -> Result<Vec<u8>, I2cError> {
+        self.i2c_host.read(address, len).await
+    }
+
+    async fn write(&mut self, address: u8, data: Vec<u8>) -> Result<(), I2cError> {
+        self.i2c_host.write(address, data).await
+    }
+
+    async fn write_read(
+        &mut self,
+        address: u8,
+        data: Vec<u8>,
+        len: u32,
+    ) -> Result<Vec<u8>, I2cError> {
+        self.i2c_host.write_read(address, data, len).await
+    }
+}
+
+impl ArielOSHost {
+    /// Gives capsules access to an I2C bus.
+    ///
+    /// Capsules can only address the devices in `allowed_addresses`.
+    pub fn bind_i2c(&mut self, device: I2cDevice, allowed_addresses: &[u8]) {
+        self.i2c_host.device = Some(device);
+        self.i2c_host.allowed_addresses = allowed_addresses.to_vec();
+    }
+}
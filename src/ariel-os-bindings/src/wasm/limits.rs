@@ -0,0 +1,105 @@
+use wasmtime::{
+    DEFAULT_INSTANCE_LIMIT, DEFAULT_MEMORY_LIMIT, DEFAULT_TABLE_LIMIT, ResourceLimiter,
+};
+
+use super::ArielOSHost;
+
+/// Caps on the resources a single capsule may allocate from the host heap.
+///
+/// A `memory.grow` or `table.grow` past a cap fails inside the capsule (returning -1) instead of
+/// exhausting the heap, and a memory or table whose initial size is already past its cap makes
+/// instantiation fail.
+///
+/// The [`Default`] is [`Self::DEFAULT`], so every host is bounded unless it opts out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapsuleLimits {
+    /// Largest size any linear memory may have, in bytes.
+    pub memory_size: usize,
+    /// Largest number of elements any table may have.
+    pub table_elements: usize,
+    /// Number of core instances, a component usually consists of several.
+    pub instances: usize,
+    pub tables: usize,
+    pub memories: usize,
+}
+
+impl CapsuleLimits {
+    /// Room for the capsules built from `payloads/`, which start with 32 KiB of memory, to grow
+    /// to twice that.
+    pub const DEFAULT: Self = Self {
+        memory_size: 64 * 1024,
+        table_elements: 1024,
+        instances: 16,
+        tables: 8,
+        memories: 4,
+    };
+
+    /// Only wasmtime's default counts apply.
+    pub const UNLIMITED: Self = Self {
+        memory_size: usize::MAX,
+        table_elements: usize::MAX,
+        instances: DEFAULT_INSTANCE_LIMIT,
+        tables: DEFAULT_TABLE_LIMIT,
+        memories: DEFAULT_MEMORY_LIMIT,
+    };
+}
+
+impl Default for CapsuleLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Only takes effect once installed with `store.limiter(|host| host)`, which
+/// `WasmHandler::with_limiter` and `Sandbox::with_limiter` do; stores created elsewhere need to
+/// install it themselves.
+impl ResourceLimiter for ArielOSHost {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(desired <= self.limits.memory_size)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(desired <= self.limits.table_elements)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories
+    }
+}
+
+impl ArielOSHost {
+    /// Creates a host whose capsule is bound by the given limits.
+    pub fn with_limits(limits: CapsuleLimits) -> Self {
+        #[allow(clippy::needless_update, reason = "Conditional compilation")]
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: CapsuleLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &CapsuleLimits {
+        &self.limits
+    }
+}
@@ -7,6 +7,9 @@ pub mod rng;
 #[cfg(feature = "time")]
 pub mod time;
 
+#[cfg(feature = "epoch")]
+pub mod epoch;
+
 #[cfg(feature = "udp")]
 pub mod udp;
 
@@ -16,11 +19,52 @@ pub mod coap;
 #[cfg(feature = "gpio")]
 pub mod gpio;
 
+#[cfg(feature = "i2c")]
+pub mod i2c;
+
+#[cfg(feature = "spi")]
+pub mod spi;
+
+#[cfg(feature = "uart")]
+pub mod uart;
+
+#[cfg(feature = "adc")]
+pub mod adc;
+
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
+#[cfg(feature = "storage")]
+pub mod storage;
+
 #[cfg(feature = "sensors")]
 pub mod sensors;
 
+#[cfg(feature = "signed")]
+pub mod signed;
+
+#[cfg(feature = "suit")]
+pub mod suit;
+
+#[cfg(feature = "transfer")]
+pub mod transfer;
+
+pub mod capabilities;
+
+pub use capabilities::{Capability, CapsuleLinker};
+
+pub mod limits;
+
+pub use limits::CapsuleLimits;
+
+pub mod slots;
+
+pub mod state;
+
 #[derive(Default)]
 pub struct ArielOSHost {
+    limits: CapsuleLimits,
+
     #[cfg(feature = "rng")]
     rng_host: crate::wasm::rng::ArielRNGHost,
 
@@ -29,4 +73,22 @@ pub struct ArielOSHost {
 
     #[cfg(feature = "gpio")]
     gpio_host: crate::wasm::gpio::ArielGpioHost,
+
+    #[cfg(feature = "i2c")]
+    i2c_host: crate::wasm::i2c::ArielI2cHost,
+
+    #[cfg(feature = "spi")]
+    spi_host: crate::wasm::spi::ArielSpiHost,
+
+    #[cfg(feature = "uart")]
+    uart_host: crate::wasm::uart::ArielUartHost,
+
+    #[cfg(feature = "adc")]
+    adc_host: crate::wasm::adc::ArielAdcHost,
+
+    #[cfg(feature = "pwm")]
+    pwm_host: crate::wasm::pwm::ArielPwmHost,
+
+    #[cfg(feature = "storage")]
+    storage_host: crate::wasm::storage::ArielStorageHost,
 }
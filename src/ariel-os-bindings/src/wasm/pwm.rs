@@ -0,0 +1,187 @@
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::ArielOSHost;
+
+use embedded_hal::pwm::SetDutyCycle;
+
+use wasmtime::component::{Resource, ResourceTable, bindgen};
+
+bindgen!({
+    world: "ariel:wasm-bindings/pwm",
+    path: "../../wit/",
+    with: {
+        "ariel:wasm-bindings/pwm-api.pwm-channel": PwmChannelHandle,
+    },
+    imports: { default: trappable },
+});
+
+pub use ariel::wasm_bindings::pwm_api::{
+    Host, HostPwmChannel, HostWithStore, PwmError, add_to_linker,
+};
+
+/// The channel cannot run at the requested frequency.
+#[derive(Debug)]
+pub struct UnsupportedFrequency;
+
+impl From<UnsupportedFrequency> for PwmError {
+    fn from(_: UnsupportedFrequency) -> Self {
+        PwmError::UnsupportedFrequency
+    }
+}
+
+/// A bound channel along with the closure changing its frequency.
+struct Channel<C, F> {
+    channel: C,
+    set_frequency: F,
+}
+
+/// Object-safe view of a [`Channel`], so channels of different types can be bound together.
+trait PwmChannel: Send {
+    fn set_duty_cycle_fraction(&mut self, numerator: u16, denominator: u16)
+    -> Result<(), PwmError>;
+
+    fn set_frequency(&mut self, hertz: u32) -> Result<(), PwmError>;
+}
+
+impl<C, F> PwmChannel for Channel<C, F>
+where
+    C: SetDutyCycle + Send,
+    F: FnMut(&mut C, u32) -> Result<(), UnsupportedFrequency> + Send,
+{
+    fn set_duty_cycle_fraction(
+        &mut self,
+        numerator: u16,
+        denominator: u16,
+    ) -> Result<(), PwmError> {
+        self.channel
+            .set_duty_cycle_fraction(numerator, denominator)
+            .map_err(|_| PwmError::Other)
+    }
+
+    fn set_frequency(&mut self, hertz: u32) -> Result<(), PwmError> {
+        Ok((self.set_frequency)(&mut self.channel, hertz)?)
+    }
+}
+
+/// Host side of a `pwm-channel` resource: index of the channel in [`ArielPwmHost::channels`].
+pub struct PwmChannelHandle(usize);
+
+#[derive(Default)]
+pub(crate) struct ArielPwmHost {
+    channels: Vec<(String, Box<dyn PwmChannel>)>,
+    table: ResourceTable,
+}
+
+impl ArielPwmHost {
+    fn channel(
+        &mut self,
+        channel: &Resource<PwmChannelHandle>,
+    ) -> wasmtime::Result<&mut dyn PwmChannel> {
+        let PwmChannelHandle(index) = *self.table.get(channel)?;
+        Ok(self.channels[index].1.as_mut())
+    }
+}
+
+impl Host for ArielPwmHost {}
+
+impl HostPwmChannel for ArielPwmHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<PwmChannelHandle>, PwmError>> {
+        let Some(index) = self.channels.iter().position(|(n, _)| *n == name) else {
+            return Ok(Err(PwmError::NotFound));
+        };
+        Ok(Ok(self.table.push(PwmChannelHandle(index))?))
+    }
+
+    fn set_duty_cycle(
+        &mut self,
+        channel: Resource<PwmChannelHandle>,
+        numerator: u16,
+        denominator: u16,
+    ) -> wasmtime::Result<Result<(), PwmError>> {
+        if denominator == 0 || numerator > denominator {
+            return Ok(Err(PwmError::InvalidDutyCycle));
+        }
+        Ok(self
+            .channel(&channel)?
+            .set_duty_cycle_fraction(numerator, denominator))
+    }
+
+    fn set_frequency(
+        &mut self,
+        channel: Resource<PwmChannelHandle>,
+        hertz: u32,
+    ) -> wasmtime::Result<Result<(), PwmError>> {
+        Ok(self.channel(&channel)?.set_frequency(hertz))
+    }
+
+    fn drop(&mut self, channel: Resource<PwmChannelHandle>) -> wasmtime::Result<()> {
+        self.table.delete(channel)?;
+        Ok(())
+    }
+}
+
+impl Host for ArielOSHost {}
+
+impl HostPwmChannel for ArielOSHost {
+    fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<PwmChannelHandle>, PwmError>> {
+        self.pwm_host.open(name)
+    }
+
+    fn set_duty_cycle(
+        &mut self,
+        channel: Resource<PwmChannelHandle>,
+        numerator: u16,
+        denominator: u16,
+    ) -> wasmtime::Result<Result<(), PwmError>> {
+        self.pwm_host
+            .set_duty_cycle(channel, numerator, denominator)
+    }
+
+    fn set_frequency(
+        &mut self,
+        channel: Resource<PwmChannelHandle>,
+        hertz: u32,
+    ) -> wasmtime::Result<Result<(), PwmError>> {
+        self.pwm_host.set_frequency(channel, hertz)
+    }
+
+    fn drop(&mut self, channel: Resource<PwmChannelHandle>) -> wasmtime::Result<()> {
+        self.pwm_host.drop(channel)
+    }
+}
+
+impl ArielOSHost {
+    /// Makes a PWM output available to capsules under the given name.
+    ///
+    /// The channel keeps the frequency it was configured with; capsules trying to change it get
+    /// `unsupported-frequency`.
+    pub fn bind_pwm_channel(&mut self, name: &str, channel: impl SetDutyCycle + Send + 'static) {
+        self.bind_pwm_channel_with_frequency(name, channel, |_, _| Err(UnsupportedFrequency));
+    }
+
+    /// Like [`Self::bind_pwm_channel`], letting capsules change the frequency through
+    /// `set_frequency`, as embedded-hal has no trait for that.
+    pub fn bind_pwm_channel_with_frequency<C: SetDutyCycle + Send + 'static>(
+        &mut self,
+        name: &str,
+        channel: C,
+        set_frequency: impl FnMut(&mut C, u32) -> Result<(), UnsupportedFrequency> + Send + 'static,
+    ) {
+        self.pwm_host.channels.push((
+            String::from(name),
+            Box::new(Channel {
+                channel,
+                set_frequency,
+            }),
+        ));
+    }
+}
@@ -1,11 +1,16 @@
 extern crate alloc;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 
 use super::ArielOSHost;
 
 use ariel_os_sensors::{
-    Category, Label, MeasurementUnit, Reading as _,
-    sensor::{ReadingChannel, ReadingError, Sample, SampleMetadata},
+    Category, Label, MeasurementUnit, Reading as _, Sensor,
+    sensor::{
+        Mode, ReadingChannel, ReadingError, Sample, SampleMetadata, Samples, SetModeError, State,
+        TriggerMeasurementError,
+    },
 };
 use ariel_os_sensors_registry::REGISTRY;
 
@@ -15,8 +20,11 @@ use wasmtime::component::bindgen;
 bindgen!({
     world: "ariel:wasm-bindings/sensors@0.0.1",
     path: "../../wit/",
+    // Lets the host filter by category and label
+    additional_derives: [PartialEq, Eq],
     imports: {
         "ariel:wasm-bindings/sensors-api.wait-for-reading": async,
+        "ariel:wasm-bindings/sensors-api.read": async,
     }
 });
 
@@ -24,32 +32,54 @@ bindgen!({
 bindgen!({
     world: "ariel:wasm-bindings/sensors@0.0.1",
     path: "../../wit/",
+    // Lets the host filter by category and label
+    additional_derives: [PartialEq, Eq],
 });
 
 #[cfg(not(feature = "sensors-async"))]
 use embassy_futures::block_on;
 
 pub use ariel::wasm_bindings::sensors_api as comp_sensor;
-pub use ariel::wasm_bindings::sensors_api::{Host, HostWithStore, add_to_linker};
+pub use ariel::wasm_bindings::sensors_api::{Host, HostWithStore, SensorError, add_to_linker};
+
+/// Appends the samples to `results`, optionally keeping only the channels with the given label.
+fn collect_samples(
+    samples: Samples,
+    label: Option<&comp_sensor::Label>,
+    results: &mut Vec<(comp_sensor::Sample, comp_sensor::Channel)>,
+) {
+    for (reading_channel, sample) in samples.samples() {
+        if label.is_none_or(|label| comp_sensor::Label::from(reading_channel.label()) == *label) {
+            results.push((sample.into(), reading_channel.into()));
+        }
+    }
+}
+
+fn sensor_by_handle(handle: u32) -> Result<&'static dyn Sensor, SensorError> {
+    REGISTRY
+        .sensors()
+        .nth(handle as usize)
+        .ok_or(SensorError::InvalidHandle)
+}
 
 impl Host for ArielOSHost {
-    fn trigger_measurements(&mut self, category: Option<comp_sensor::Category>) -> Result<(), ()> {
+    fn trigger_measurements(
+        &mut self,
+        category: Option<comp_sensor::Category>,
+    ) -> Result<(), SensorError> {
         match category {
             Some(cat) => {
-                for sensor in REGISTRY
-                    .sensors()
-                    .filter(|s| s.categories().contains(&cat.into()))
-                {
-                    if sensor.trigger_measurement().is_err() {
-                        return Err(());
-                    }
+                for sensor in REGISTRY.sensors().filter(|s| {
+                    s.categories()
+                        .iter()
+                        .any(|&c| comp_sensor::Category::from(c) == cat)
+                }) {
+                    sensor.trigger_measurement()?;
                 }
             }
             None => {
                 for sensor in REGISTRY.sensors() {
-                    if sensor.trigger_measurement().is_err() {
-                        return Err(());
-                    }
+                    sensor.trigger_measurement()?;
                 }
             }
         }
@@ -61,7 +91,7 @@ impl Host for ArielOSHost {
     async fn wait_for_reading(
         &mut self,
         label: Option<comp_sensor::Label>,
-    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, ()> {
+    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, SensorError> {
         let mut results = Vec::new();
         for sensor in REGISTRY.sensors() {
             match sensor.wait_for_reading().await {
@@ -74,27 +104,8 @@ impl Host for ArielOSHost {
                     );
                     continue;
                 }
-                Ok(samples) => match label {
-                    Some(label) => {
-                        for (reading_channel, sample) in
-                            samples.samples().filter(|(r, _)| r.label() == label.into())
-                        {
-                            results.push((
-                                comp_sensor::Sample::from(sample),
-                                comp_sensor::Channel::from(reading_channel),
-                            ))
-                        }
-                    }
-                    None => {
-                        for (reading_channel, sample) in samples.samples() {
-                            results.push((
-                                comp_sensor::Sample::from(sample),
-                                comp_sensor::Channel::from(reading_channel),
-                            ))
-                        }
-                    }
-                },
-                Err(_error) => return Err(()),
+                Ok(samples) => collect_samples(samples, label.as_ref(), &mut results),
+                Err(error) => return Err(error.into()),
             }
         }
         Ok(results)
@@ -104,7 +115,7 @@ impl Host for ArielOSHost {
     fn wait_for_reading(
         &mut self,
         label: Option<comp_sensor::Label>,
-    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, ()> {
+    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, SensorError> {
         let mut results = Vec::new();
         for sensor in REGISTRY.sensors() {
             match block_on(sensor.wait_for_reading()) {
@@ -117,31 +128,119 @@ impl Host for ArielOSHost {
                     );
                     continue;
                 }
-                Ok(samples) => match label {
-                    Some(label) => {
-                        for (reading_channel, sample) in
-                            samples.samples().filter(|(r, _)| r.label() == label.into())
-                        {
-                            results.push((
-                                comp_sensor::Sample::from(sample),
-                                comp_sensor::Channel::from(reading_channel),
-                            ))
-                        }
-                    }
-                    None => {
-                        for (reading_channel, sample) in samples.samples() {
-                            results.push((
-                                comp_sensor::Sample::from(sample),
-                                comp_sensor::Channel::from(reading_channel),
-                            ))
-                        }
-                    }
-                },
-                Err(_error) => return Err(()),
+                Ok(samples) => collect_samples(samples, label.as_ref(), &mut results),
+                Err(error) => return Err(error.into()),
             }
         }
         Ok(results)
     }
+
+    fn list_sensors(&mut self) -> Vec<comp_sensor::SensorInfo> {
+        REGISTRY
+            .sensors()
+            .enumerate()
+            .map(|(handle, sensor)| comp_sensor::SensorInfo {
+                handle: handle as u32,
+                display_name: sensor.display_name().map(String::from),
+                label: sensor.label().map(String::from),
+                part_number: sensor.part_number().map(String::from),
+                categories: sensor.categories().iter().map(|&c| c.into()).collect(),
+                reading_channels: sensor.reading_channels().iter().map(Into::into).collect(),
+            })
+            .collect()
+    }
+
+    fn trigger_measurement(&mut self, sensor: u32) -> Result<(), SensorError> {
+        sensor_by_handle(sensor)?.trigger_measurement()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "sensors-async")]
+    async fn read(
+        &mut self,
+        sensor: u32,
+    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, SensorError> {
+        let samples = sensor_by_handle(sensor)?.wait_for_reading().await?;
+        let mut results = Vec::new();
+        collect_samples(samples, None, &mut results);
+        Ok(results)
+    }
+
+    #[cfg(not(feature = "sensors-async"))]
+    fn read(
+        &mut self,
+        sensor: u32,
+    ) -> Result<Vec<(comp_sensor::Sample, comp_sensor::Channel)>, SensorError> {
+        let samples = block_on(sensor_by_handle(sensor)?.wait_for_reading())?;
+        let mut results = Vec::new();
+        collect_samples(samples, None, &mut results);
+        Ok(results)
+    }
+
+    fn set_mode(
+        &mut self,
+        sensor: u32,
+        mode: comp_sensor::Mode,
+    ) -> Result<comp_sensor::State, SensorError> {
+        let state = sensor_by_handle(sensor)?.set_mode(mode.into())?;
+        Ok(state.into())
+    }
+
+    fn get_state(&mut self, sensor: u32) -> Result<comp_sensor::State, SensorError> {
+        Ok(sensor_by_handle(sensor)?.state().into())
+    }
+}
+
+impl From<TriggerMeasurementError> for SensorError {
+    fn from(value: TriggerMeasurementError) -> Self {
+        match value {
+            TriggerMeasurementError::NonEnabled => SensorError::NonEnabled,
+        }
+    }
+}
+
+impl From<ReadingError> for SensorError {
+    fn from(value: ReadingError) -> Self {
+        match value {
+            ReadingError::NonEnabled => SensorError::NonEnabled,
+            ReadingError::SensorAccess => SensorError::SensorAccess,
+            ReadingError::NotMeasuring => SensorError::NotMeasuring,
+        }
+    }
+}
+
+/// The value is `other` on the capsule side, so it has no Ariel OS counterpart.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownVariant;
+
+impl From<SetModeError> for SensorError {
+    fn from(value: SetModeError) -> Self {
+        match value {
+            SetModeError::Uninitialized => SensorError::Uninitialized,
+        }
+    }
+}
+
+impl From<comp_sensor::Mode> for Mode {
+    fn from(value: comp_sensor::Mode) -> Self {
+        match value {
+            comp_sensor::Mode::Disabled => Mode::Disabled,
+            comp_sensor::Mode::Enabled => Mode::Enabled,
+            comp_sensor::Mode::Sleeping => Mode::Sleeping,
+        }
+    }
+}
+
+impl From<State> for comp_sensor::State {
+    fn from(value: State) -> Self {
+        match value {
+            State::Uninitialized => comp_sensor::State::Uninitialized,
+            State::Disabled => comp_sensor::State::Disabled,
+            State::Enabled => comp_sensor::State::Enabled,
+            State::Measuring => comp_sensor::State::Measuring,
+            State::Sleeping => comp_sensor::State::Sleeping,
+        }
+    }
 }
 
 impl From<Category> for comp_sensor::Category {
@@ -173,19 +272,17 @@ impl From<Category> for comp_sensor::Category {
             Category::Temperature => comp_sensor::Category::Temperature,
             Category::Tvoc => comp_sensor::Category::Tvoc,
             Category::Voltage => comp_sensor::Category::Voltage,
-            category => {
-                unimplemented!(
-                    "Sensors of the following category ({:?}) isn't supported yet",
-                    category
-                )
-            }
+            // Added to ariel-os-sensors after these bindings were written
+            _ => comp_sensor::Category::Other(format!("{value:?}")),
         }
     }
 }
 
-impl From<comp_sensor::Category> for Category {
-    fn from(value: comp_sensor::Category) -> Self {
-        match value {
+impl TryFrom<comp_sensor::Category> for Category {
+    type Error = UnknownVariant;
+
+    fn try_from(value: comp_sensor::Category) -> Result<Self, Self::Error> {
+        Ok(match value {
             comp_sensor::Category::Accelerometer => Category::Accelerometer,
             comp_sensor::Category::AccelerometerTemperature => Category::AccelerometerTemperature,
             comp_sensor::Category::AccelerometerGyroscope => Category::AccelerometerGyroscope,
@@ -200,7 +297,7 @@ impl From<comp_sensor::Category> for Category {
             comp_sensor::Category::Color => Category::Color,
             comp_sensor::Category::Gnss => Category::Gnss,
             comp_sensor::Category::Gyroscope => Category::Gyroscope,
-            comp_sensor::Category::RelativeHumidity => Category::Gyroscope,
+            comp_sensor::Category::RelativeHumidity => Category::RelativeHumidity,
             comp_sensor::Category::RelativeHumidityTemperature => {
                 Category::RelativeHumidityTemperature
             }
@@ -212,7 +309,8 @@ impl From<comp_sensor::Category> for Category {
             comp_sensor::Category::Temperature => Category::Temperature,
             comp_sensor::Category::Tvoc => Category::Tvoc,
             comp_sensor::Category::Voltage => Category::Voltage,
-        }
+            comp_sensor::Category::Other(_) => return Err(UnknownVariant),
+        })
     }
 }
 
@@ -237,16 +335,17 @@ impl From<Label> for comp_sensor::Label {
             Label::X => comp_sensor::Label::X,
             Label::Y => comp_sensor::Label::Y,
             Label::Z => comp_sensor::Label::Z,
-            label => {
-                unimplemented!("This label ({}) is not supported yet", label)
-            }
+            // Added to ariel-os-sensors after these bindings were written
+            _ => comp_sensor::Label::Other(format!("{value:?}")),
         }
     }
 }
 
-impl From<comp_sensor::Label> for Label {
-    fn from(value: comp_sensor::Label) -> Self {
-        match value {
+impl TryFrom<comp_sensor::Label> for Label {
+    type Error = UnknownVariant;
+
+    fn try_from(value: comp_sensor::Label) -> Result<Self, Self::Error> {
+        Ok(match value {
             comp_sensor::Label::AccelerationX => Label::AccelerationX,
             comp_sensor::Label::AccelerationY => Label::AccelerationY,
             comp_sensor::Label::AccelerationZ => Label::AccelerationZ,
@@ -265,7 +364,8 @@ impl From<comp_sensor::Label> for Label {
             comp_sensor::Label::X => Label::X,
             comp_sensor::Label::Y => Label::Y,
             comp_sensor::Label::Z => Label::Z,
-        }
+            comp_sensor::Label::Other(_) => return Err(UnknownVariant),
+        })
     }
 }
 
@@ -312,13 +412,62 @@ impl From<MeasurementUnit> for comp_sensor::MeasurementUnit {
             MeasurementUnit::Volt => comp_sensor::MeasurementUnit::Volt,
             MeasurementUnit::Watt => comp_sensor::MeasurementUnit::Watt,
             MeasurementUnit::Weber => comp_sensor::MeasurementUnit::Weber,
-            unit => {
-                unimplemented!("This unit ({}) is not supported yet", unit)
-            }
+            // Added to ariel-os-sensors after these bindings were written
+            _ => comp_sensor::MeasurementUnit::Other(format!("{value:?}")),
         }
     }
 }
 
+impl TryFrom<comp_sensor::MeasurementUnit> for MeasurementUnit {
+    type Error = UnknownVariant;
+
+    fn try_from(value: comp_sensor::MeasurementUnit) -> Result<Self, Self::Error> {
+        Ok(match value {
+            comp_sensor::MeasurementUnit::AccelG => MeasurementUnit::AccelG,
+            comp_sensor::MeasurementUnit::Ampere => MeasurementUnit::Ampere,
+            comp_sensor::MeasurementUnit::Becquerel => MeasurementUnit::Becquerel,
+            comp_sensor::MeasurementUnit::Boolean => MeasurementUnit::Bool,
+            comp_sensor::MeasurementUnit::Candela => MeasurementUnit::Candela,
+            comp_sensor::MeasurementUnit::Celsius => MeasurementUnit::Celsius,
+            comp_sensor::MeasurementUnit::Coulomb => MeasurementUnit::Coulomb,
+            comp_sensor::MeasurementUnit::Decibel => MeasurementUnit::Decibel,
+            comp_sensor::MeasurementUnit::Decimaldegree => MeasurementUnit::DecimalDegree,
+            comp_sensor::MeasurementUnit::Degree => MeasurementUnit::Degree,
+            comp_sensor::MeasurementUnit::DegreePerSecond => MeasurementUnit::DegreePerSecond,
+            comp_sensor::MeasurementUnit::Farad => MeasurementUnit::Farad,
+            comp_sensor::MeasurementUnit::Gram => MeasurementUnit::Gram,
+            comp_sensor::MeasurementUnit::Gray => MeasurementUnit::Gray,
+            comp_sensor::MeasurementUnit::Henry => MeasurementUnit::Henry,
+            comp_sensor::MeasurementUnit::Hertz => MeasurementUnit::Hertz,
+            comp_sensor::MeasurementUnit::Joule => MeasurementUnit::Joule,
+            comp_sensor::MeasurementUnit::Katal => MeasurementUnit::Katal,
+            comp_sensor::MeasurementUnit::Kelvin => MeasurementUnit::Kelvin,
+            comp_sensor::MeasurementUnit::Lumen => MeasurementUnit::Lumen,
+            comp_sensor::MeasurementUnit::Lux => MeasurementUnit::Lux,
+            comp_sensor::MeasurementUnit::Meter => MeasurementUnit::Meter,
+            comp_sensor::MeasurementUnit::MeterPerSecond => MeasurementUnit::MeterPerSecond,
+            comp_sensor::MeasurementUnit::Mole => MeasurementUnit::Mole,
+            comp_sensor::MeasurementUnit::Newton => MeasurementUnit::Newton,
+            comp_sensor::MeasurementUnit::Ohm => MeasurementUnit::Ohm,
+            comp_sensor::MeasurementUnit::Pascal => MeasurementUnit::Pascal,
+            comp_sensor::MeasurementUnit::Percent => MeasurementUnit::Percent,
+            comp_sensor::MeasurementUnit::PercentageRelativeHumidity => {
+                MeasurementUnit::PercentageRelativeHumidity
+            }
+            comp_sensor::MeasurementUnit::Radian => MeasurementUnit::Radian,
+            comp_sensor::MeasurementUnit::Second => MeasurementUnit::Second,
+            comp_sensor::MeasurementUnit::Siemens => MeasurementUnit::Siemens,
+            comp_sensor::MeasurementUnit::Sievert => MeasurementUnit::Sievert,
+            comp_sensor::MeasurementUnit::Steradian => MeasurementUnit::Steradian,
+            comp_sensor::MeasurementUnit::Tesla => MeasurementUnit::Tesla,
+            comp_sensor::MeasurementUnit::Volt => MeasurementUnit::Volt,
+            comp_sensor::MeasurementUnit::Watt => MeasurementUnit::Watt,
+            comp_sensor::MeasurementUnit::Weber => MeasurementUnit::Weber,
+            comp_sensor::MeasurementUnit::Other(_) => return Err(UnknownVariant),
+        })
+    }
+}
+
 impl From<Sample> for comp_sensor::Sample {
     fn from(value: Sample) -> Self {
         let measure = value.value().unwrap_or_default();
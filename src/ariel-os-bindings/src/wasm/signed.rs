@@ -0,0 +1,142 @@
+//! Signed capsule container.
+//!
+//! Deserializing a precompiled capsule is equivalent to running native code, so images received at
+//! runtime must be checked before they reach `Component::deserialize_raw`. A signed image is a
+//! 128 byte header followed by the `.cwasm` payload, as produced by `sign_capsule.rs`:
+//!
+//! | Offset | Size | Content                                        |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | Magic, `b"trvm"`                               |
+//! | 4      | 1    | Container format, currently 1                  |
+//! | 5      | 1    | Id of the key that signed the image            |
+//! | 6      | 2    | Reserved, zero                                 |
+//! | 8      | 4    | Capsule version, little endian                 |
+//! | 12     | 4    | Payload length, little endian                  |
+//! | 16     | 32   | SHA-256 of the payload                         |
+//! | 48     | 16   | Reserved, zero                                 |
+//! | 64     | 64   | Ed25519 signature over the first 64 bytes      |
+//!
+//! The header size keeps the payload as aligned as the image itself.
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: [u8; 4] = *b"trvm";
+pub const FORMAT: u8 = 1;
+pub const HEADER_LEN: usize = 128;
+
+const SIGNED_LEN: usize = 64;
+
+/// A public key capsule images may be signed with.
+#[derive(Debug, Clone, Copy)]
+pub struct TrustedKey {
+    /// Id the signer puts in the header, so the right key can be picked without trying them all.
+    pub id: u8,
+    /// Ed25519 public key.
+    pub public_key: [u8; 32],
+}
+
+/// Reasons for refusing a capsule image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The image is shorter than the header.
+    TooShort,
+    /// The image does not start with [`MAGIC`], e.g. because it is an unsigned `.cwasm`.
+    BadMagic,
+    /// The container format is not [`FORMAT`].
+    UnsupportedFormat,
+    /// No trusted key has the id found in the header.
+    UnknownKey,
+    /// The payload length in the header does not match the image.
+    LengthMismatch,
+    /// The payload does not match the hash in the header.
+    HashMismatch,
+    /// The signature does not match the header.
+    BadSignature,
+}
+
+impl core::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let reason = match self {
+            VerifyError::TooShort => "image is shorter than the header",
+            VerifyError::BadMagic => "image is not a signed capsule",
+            VerifyError::UnsupportedFormat => "unsupported container format",
+            VerifyError::UnknownKey => "image is signed with an unknown key",
+            VerifyError::LengthMismatch => "payload length does not match the header",
+            VerifyError::HashMismatch => "payload does not match its hash",
+            VerifyError::BadSignature => "invalid signature",
+        };
+        f.write_str(reason)
+    }
+}
+
+impl core::error::Error for VerifyError {}
+
+/// A capsule image whose signature checked out.
+#[derive(Debug, Clone, Copy)]
+pub struct VerifiedCapsule<'a> {
+    key_id: u8,
+    version: u32,
+    payload: &'a [u8],
+}
+
+impl<'a> VerifiedCapsule<'a> {
+    pub fn key_id(&self) -> u8 {
+        self.key_id
+    }
+
+    /// Version the signer gave the capsule.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The precompiled component, to be passed to `Component::deserialize{,_raw}`.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+/// Checks that `image` is a capsule signed by one of the `keys`.
+///
+/// Only the payload of a successfully verified image may be deserialized.
+pub fn verify<'a>(
+    image: &'a [u8],
+    keys: &[TrustedKey],
+) -> Result<VerifiedCapsule<'a>, VerifyError> {
+    let Some((header, payload)) = image.split_at_checked(HEADER_LEN) else {
+        return Err(VerifyError::TooShort);
+    };
+    if header[0..4] != MAGIC {
+        return Err(VerifyError::BadMagic);
+    }
+    if header[4] != FORMAT {
+        return Err(VerifyError::UnsupportedFormat);
+    }
+    let key_id = header[5];
+    let key = keys
+        .iter()
+        .find(|key| key.id == key_id)
+        .ok_or(VerifyError::UnknownKey)?;
+
+    // Check the signature first: nothing else in the header can be trusted before
+    let key = VerifyingKey::from_bytes(&key.public_key).map_err(|_| VerifyError::UnknownKey)?;
+    let signature = Signature::from_slice(&header[SIGNED_LEN..HEADER_LEN])
+        .map_err(|_| VerifyError::BadSignature)?;
+    key.verify_strict(&header[..SIGNED_LEN], &signature)
+        .map_err(|_| VerifyError::BadSignature)?;
+
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let len = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    if usize::try_from(len) != Ok(payload.len()) {
+        return Err(VerifyError::LengthMismatch);
+    }
+    if Sha256::digest(payload)[..] != header[16..48] {
+        return Err(VerifyError::HashMismatch);
+    }
+
+    Ok(VerifiedCapsule {
+        key_id,
+        version,
+        payload,
+    })
+}
@@ -0,0 +1,238 @@
+//! A/B slots for capsule updates.
+//!
+//! [`CapsuleSlots`] keeps the running capsule in an active slot while an update is tried out from
+//! a candidate slot. The candidate only replaces the active capsule once it is confirmed, e.g.
+//! after running without trapping for a while; otherwise it is rolled back.
+//!
+//! Images are never moved nor modified while they are in a slot, so a component created with
+//! `Component::deserialize_raw` from [`Slot::image`] stays valid until that slot is dropped: the
+//! previous active slot on [`CapsuleSlots::confirm`], the candidate on
+//! [`CapsuleSlots::rollback`] or when a newer candidate is staged.
+//!
+//! Slots only live in RAM, so the highest confirmed version is kept in a [`VersionStore`]: after a
+//! reboot, images older than that are still refused even though the firmware starts over from its
+//! built-in capsule.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// A precompiled capsule.
+pub enum Image {
+    /// Built into the firmware.
+    Static(&'static [u8]),
+    /// Received at runtime.
+    Owned(Box<[u8]>),
+}
+
+impl Image {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Image::Static(bytes) => bytes,
+            Image::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl From<&'static [u8]> for Image {
+    fn from(bytes: &'static [u8]) -> Self {
+        Image::Static(bytes)
+    }
+}
+
+impl From<Box<[u8]>> for Image {
+    fn from(bytes: Box<[u8]>) -> Self {
+        Image::Owned(bytes)
+    }
+}
+
+impl From<Vec<u8>> for Image {
+    fn from(bytes: Vec<u8>) -> Self {
+        Image::Owned(bytes.into_boxed_slice())
+    }
+}
+
+/// A capsule image and its version.
+pub struct Slot {
+    version: u64,
+    image: Image,
+}
+
+impl Slot {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn image(&self) -> &[u8] {
+        self.image.as_bytes()
+    }
+}
+
+/// Errors of the slot operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotError {
+    /// The offered capsule is older than `minimum`, the oldest version that may be staged.
+    Downgrade { minimum: u64, offered: u64 },
+    /// There is no capsule on trial to confirm or roll back.
+    NoCandidate,
+    /// The [`VersionStore`] could not load or store the confirmed version.
+    Storage,
+}
+
+impl core::fmt::Display for SlotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SlotError::Downgrade { minimum, offered } => write!(
+                f,
+                "capsule version {offered} is below the minimum version {minimum}"
+            ),
+            SlotError::NoCandidate => f.write_str("no capsule is on trial"),
+            SlotError::Storage => {
+                f.write_str("the confirmed capsule version could not be accessed")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SlotError {}
+
+/// Keeps the highest confirmed capsule version across reboots.
+pub trait VersionStore {
+    /// Returns the stored version, or `None` if no capsule was confirmed yet.
+    fn load(&mut self) -> impl Future<Output = Result<Option<u64>, SlotError>>;
+
+    /// Replaces the stored version.
+    fn store(&mut self, version: u64) -> impl Future<Output = Result<(), SlotError>>;
+}
+
+/// Keeps the confirmed version in Ariel OS's flash storage.
+#[cfg(feature = "storage")]
+pub struct FlashVersionStore {
+    key: &'static str,
+}
+
+#[cfg(feature = "storage")]
+impl FlashVersionStore {
+    /// Stores the version under `key`, which must not be used for anything else.
+    pub const fn new(key: &'static str) -> Self {
+        Self { key }
+    }
+}
+
+#[cfg(feature = "storage")]
+impl VersionStore for FlashVersionStore {
+    async fn load(&mut self) -> Result<Option<u64>, SlotError> {
+        ariel_os_storage::get(self.key)
+            .await
+            .map_err(|_| SlotError::Storage)
+    }
+
+    async fn store(&mut self, version: u64) -> Result<(), SlotError> {
+        ariel_os_storage::insert(self.key, version)
+            .await
+            .map_err(|_| SlotError::Storage)
+    }
+}
+
+/// An active capsule and possibly a newer one on trial.
+///
+/// ```ignore
+/// let versions = FlashVersionStore::new("capsule-version");
+/// let mut slots = CapsuleSlots::new(1, include_bytes!("payload.cwasm").as_slice(), versions).await?;
+/// slots.stage(2, new_image)?;
+/// // Run slots.current() for a while, then either
+/// slots.confirm().await?;
+/// // or, if it trapped
+/// slots.rollback()?;
+/// ```
+pub struct CapsuleSlots<S> {
+    active: Slot,
+    candidate: Option<Slot>,
+    /// Highest version confirmed so far, possibly before a reboot.
+    confirmed: u64,
+    versions: S,
+}
+
+impl<S: VersionStore> CapsuleSlots<S> {
+    /// Starts out with a trusted capsule, e.g. the one built into the firmware.
+    ///
+    /// Versions confirmed before are loaded from `versions`, so only updates at least as new as
+    /// the last confirmed one can be staged.
+    pub async fn new(
+        version: u64,
+        image: impl Into<Image>,
+        mut versions: S,
+    ) -> Result<Self, SlotError> {
+        let confirmed = versions
+            .load()
+            .await?
+            .map_or(version, |stored| stored.max(version));
+        Ok(Self {
+            active: Slot {
+                version,
+                image: image.into(),
+            },
+            candidate: None,
+            confirmed,
+            versions,
+        })
+    }
+
+    /// Puts a capsule on trial, replacing any previous candidate.
+    ///
+    /// Versions must increase: capsules that are not newer than both the active capsule and the
+    /// candidate are rejected, and so are capsules older than the last confirmed version. That
+    /// version itself may be staged again, since its image did not survive the reboot.
+    pub fn stage(&mut self, version: u64, image: impl Into<Image>) -> Result<(), SlotError> {
+        let newest = self.current().version;
+        if version <= newest || version < self.confirmed {
+            return Err(SlotError::Downgrade {
+                minimum: newest.saturating_add(1).max(self.confirmed),
+                offered: version,
+            });
+        }
+        self.candidate = Some(Slot {
+            version,
+            image: image.into(),
+        });
+        Ok(())
+    }
+
+    /// The capsule to run: the candidate while there is one, the active capsule otherwise.
+    pub fn current(&self) -> &Slot {
+        self.candidate.as_ref().unwrap_or(&self.active)
+    }
+
+    pub fn active(&self) -> &Slot {
+        &self.active
+    }
+
+    /// Whether [`current`](Self::current) is a candidate that still needs confirming.
+    pub fn in_trial(&self) -> bool {
+        self.candidate.is_some()
+    }
+
+    /// Makes the candidate the active capsule and returns its version.
+    ///
+    /// The version is stored first; if that fails, the candidate stays on trial. The previous
+    /// active capsule is dropped.
+    pub async fn confirm(&mut self) -> Result<u64, SlotError> {
+        let version = self
+            .candidate
+            .as_ref()
+            .map(Slot::version)
+            .ok_or(SlotError::NoCandidate)?;
+        if version > self.confirmed {
+            self.versions.store(version).await?;
+            self.confirmed = version;
+        }
+        self.active = self.candidate.take().ok_or(SlotError::NoCandidate)?;
+        Ok(version)
+    }
+
+    /// Drops the candidate and returns its version, going back to the active capsule.
+    pub fn rollback(&mut self) -> Result<u64, SlotError> {
+        let candidate = self.candidate.take().ok_or(SlotError::NoCandidate)?;
+        Ok(candidate.version)
+    }
+}
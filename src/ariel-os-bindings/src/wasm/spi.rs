@@ -0,0 +1,177 @@
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::ArielOSHost;
+use ariel_os_embassy::spi::main::SpiDevice;
+
+use embedded_hal_async::spi::{Error as _, ErrorKind, SpiDevice as _};
+
+use wasmtime::component::{Resource, ResourceTable, bindgen};
+
+bindgen!({
+    world: "ariel:wasm-bindings/spi",
+    path: "../../wit/",
+    with: {
+        "ariel:wasm-bindings/spi-api.spi-device": SpiDeviceHandle,
+    },
+    imports: { default: async | trappable },
+});
+
+pub use ariel::wasm_bindings::spi_api::{
+    Host, HostSpiDevice, HostWithStore, SpiError, add_to_linker,
+};
+
+/// Longest single transfer a capsule may request.
+pub const MAX_TRANSFER_LEN: usize = 256;
+
+/// Host side of an `spi-device` resource: index of the device in [`ArielSpiHost::devices`].
+pub struct SpiDeviceHandle(usize);
+
+impl From<ErrorKind> for SpiError {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Overrun => SpiError::Overrun,
+            ErrorKind::ModeFault => SpiError::ModeFault,
+            ErrorKind::FrameFormat => SpiError::FrameFormat,
+            ErrorKind::ChipSelectFault => SpiError::ChipSelectFault,
+            _ => SpiError::Other,
+        }
+    }
+}
+
+fn check_len(len: usize) -> Result<(), SpiError> {
+    if len > MAX_TRANSFER_LEN {
+        return Err(SpiError::TransferTooLong);
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub(crate) struct ArielSpiHost {
+    devices: Vec<(String, SpiDevice)>,
+    table: ResourceTable,
+}
+
+impl ArielSpiHost {
+    fn device(&mut self, device: &Resource<SpiDeviceHandle>) -> wasmtime::Result<&mut SpiDevice> {
+        let SpiDeviceHandle(index) = *self.table.get(device)?;
+        Ok(&mut self.devices[index].1)
+    }
+}
+
+impl Host for ArielSpiHost {}
+
+impl HostSpiDevice for ArielSpiHost {
+    async fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<SpiDeviceHandle>, SpiError>> {
+        let Some(index) = self.devices.iter().position(|(n, _)| *n == name) else {
+            return Ok(Err(SpiError::NotFound));
+        };
+        Ok(Ok(self.table.push(SpiDeviceHandle(index))?))
+    }
+
+    async fn read(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        len: u32,
+    ) -> wasmtime::Result<Result<Vec<u8>, SpiError>> {
+        if let Err(e) = check_len(len as usize) {
+            return Ok(Err(e));
+        }
+        let mut buffer = vec![0; len as usize];
+        Ok(self
+            .device(&device)?
+            .read(&mut buffer)
+            .await
+            .map(|()| buffer)
+            .map_err(|e| e.kind().into()))
+    }
+
+    async fn write(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        data: Vec<u8>,
+    ) -> wasmtime::Result<Result<(), SpiError>> {
+        if let Err(e) = check_len(data.len()) {
+            return Ok(Err(e));
+        }
+        Ok(self
+            .device(&device)?
+            .write(&data)
+            .await
+            .map_err(|e| e.kind().into()))
+    }
+
+    async fn transfer(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        mut data: Vec<u8>,
+    ) -> wasmtime::Result<Result<Vec<u8>, SpiError>> {
+        if let Err(e) = check_len(data.len()) {
+            return Ok(Err(e));
+        }
+        Ok(self
+            .device(&device)?
+            .transfer_in_place(&mut data)
+            .await
+            .map(|()| data)
+            .map_err(|e| e.kind().into()))
+    }
+
+    async fn drop(&mut self, device: Resource<SpiDeviceHandle>) -> wasmtime::Result<()> {
+        self.table.delete(device)?;
+        Ok(())
+    }
+}
+
+impl Host for ArielOSHost {}
+
+impl HostSpiDevice for ArielOSHost {
+    async fn open(
+        &mut self,
+        name: String,
+    ) -> wasmtime::Result<Result<Resource<SpiDeviceHandle>, SpiError>> {
+        self.spi_host.open(name).await
+    }
+
+    async fn read(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        len: u32,
+    ) -> wasmtime::Result<Result<Vec<u8>, SpiError>> {
+        self.spi_host.read(device, len).await
+    }
+
+    async fn write(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        data: Vec<u8>,
+    ) -> wasmtime::Result<Result<(), SpiError>> {
+        self.spi_host.write(device, data).await
+    }
+
+    async fn transfer(
+        &mut self,
+        device: Resource<SpiDeviceHandle>,
+        data: Vec<u8>,
+    ) -> wasmtime::Result<Result<Vec<u8>, SpiError>> {
+        self.spi_host.transfer(device, data).await
+    }
+
+    async fn drop(&mut self, device: Resource<SpiDeviceHandle>) -> wasmtime::Result<()> {
+        self.spi_host.drop(device).await
+    }
+}
+
+impl ArielOSHost {
+    /// Makes an SPI device available to capsules under the given name.
+    ///
+    /// The device owns its chip select pin, so capsules cannot talk to other devices on the bus.
+    pub fn bind_spi_device(&mut self, name: &str, device: SpiDevice) {
+        self.spi_host.devices.push((String::from(name), device));
+    }
+}
@@ -0,0 +1,81 @@
+//! Carrying capsule state across updates.
+//!
+//! Capsules opt in by exporting the `state-transfer-api` interface. When one is replaced, the host
+//! calls `export-state` on it before dropping its store, and passes the bytes to `import-state` on
+//! its successor right after instantiating it. The bytes are opaque to the host; only the capsules
+//! need to agree on their format.
+//!
+//! The interface is looked up on the instance rather than being part of the world, so capsules
+//! that do not export it can still be run and updated; they just start afresh.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use wasmtime::AsContextMut;
+use wasmtime::component::{Instance, TypedFunc};
+
+/// Name under which capsules export the `state-transfer-api` interface.
+pub const INTERFACE: &str = "ariel:wasm-bindings/state-transfer-api@0.0.1";
+
+/// The `state-transfer-api` functions of a capsule instance.
+pub struct StateTransfer {
+    export_state: TypedFunc<(), (Vec<u8>,)>,
+    import_state: TypedFunc<(Vec<u8>,), ()>,
+}
+
+impl StateTransfer {
+    /// Looks up the interface on `instance`, returning `None` if the capsule does not export it.
+    ///
+    /// Fails if the capsule exports an interface by that name with different functions.
+    pub fn lookup(
+        mut store: impl AsContextMut,
+        instance: &Instance,
+    ) -> wasmtime::Result<Option<Self>> {
+        let Some(interface) = instance.get_export_index(&mut store, None, INTERFACE) else {
+            return Ok(None);
+        };
+        let (Some(export_state), Some(import_state)) = (
+            instance.get_export_index(&mut store, Some(&interface), "export-state"),
+            instance.get_export_index(&mut store, Some(&interface), "import-state"),
+        ) else {
+            return Err(wasmtime::Error::msg(
+                "capsule exports an incomplete state-transfer-api interface",
+            ));
+        };
+        Ok(Some(Self {
+            export_state: instance.get_typed_func(&mut store, export_state)?,
+            import_state: instance.get_typed_func(&mut store, import_state)?,
+        }))
+    }
+
+    /// Asks the capsule for its state, before it gets replaced.
+    pub fn export_state(&self, store: impl AsContextMut) -> wasmtime::Result<Vec<u8>> {
+        let (state,) = self.export_state.call(store, ())?;
+        Ok(state)
+    }
+
+    /// Hands the state of its predecessor to a freshly instantiated capsule.
+    pub fn import_state(&self, store: impl AsContextMut, state: Vec<u8>) -> wasmtime::Result<()> {
+        self.import_state.call(store, (state,))
+    }
+
+    /// Like [`Self::export_state`], for stores with async host functions.
+    #[cfg(feature = "async")]
+    pub async fn export_state_async(
+        &self,
+        store: impl AsContextMut<Data: Send>,
+    ) -> wasmtime::Result<Vec<u8>> {
+        let (state,) = self.export_state.call_async(store, ()).await?;
+        Ok(state)
+    }
+
+    /// Like [`Self::import_state`], for stores with async host functions.
+    #[cfg(feature = "async")]
+    pub async fn import_state_async(
+        &self,
+        store: impl AsContextMut<Data: Send>,
+        state: Vec<u8>,
+    ) -> wasmtime::Result<()> {
+        self.import_state.call_async(store, (state,)).await
+    }
+}
@@ -0,0 +1,200 @@
+extern crate alloc;
+use alloc::format;
+use alloc::string::{String, ToString as _};
+use alloc::vec::Vec;
+
+use super::ArielOSHost;
+
+use wasmtime::component::bindgen;
+
+bindgen!({
+    world: "ariel:wasm-bindings/storage",
+    path: "../../wit/",
+    imports: { default: async },
+});
+
+pub use ariel::wasm_bindings::storage_api::{Host, HostWithStore, StorageError, add_to_linker};
+
+/// Longest key a capsule may use.
+pub const MAX_KEY_LEN: usize = 32;
+/// Largest value a capsule may store under a single key.
+pub const MAX_VALUE_LEN: usize = 256;
+/// Most keys a single namespace can hold.
+pub const MAX_KEYS: usize = 16;
+
+type Key = heapless::String<MAX_KEY_LEN>;
+type Value = heapless::Vec<u8, MAX_VALUE_LEN>;
+/// Keys of a namespace along with the length of their values.
+///
+/// Ariel OS's storage cannot enumerate keys, so each namespace keeps its own index. It also
+/// allows enforcing the quota without reading every value.
+type Index = heapless::Vec<(Key, u32), MAX_KEYS>;
+
+fn flash_error<E>(_: E) -> StorageError {
+    StorageError::Flash
+}
+
+/// Keys and namespace names are joined with a `/` to form flash keys, so neither may contain
+/// one: `a/b` in namespace `c` would otherwise be the same as `b` in namespace `c/a`.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/')
+}
+
+fn check_key(key: &str) -> Result<Key, StorageError> {
+    if !is_valid_name(key) {
+        return Err(StorageError::InvalidKey);
+    }
+    Key::try_from(key).map_err(|_| StorageError::InvalidKey)
+}
+
+/// The namespace name passed to [`ArielOSHost::bind_storage`] is empty or contains a `/`.
+#[derive(Debug)]
+pub struct InvalidNamespace;
+
+impl core::fmt::Display for InvalidNamespace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("storage namespaces must be non-empty and must not contain `/`")
+    }
+}
+
+impl core::error::Error for InvalidNamespace {}
+
+struct Namespace {
+    name: String,
+    /// Bytes of keys and values the namespace may hold.
+    quota: usize,
+}
+
+impl Namespace {
+    fn flash_key(&self, key: &str) -> String {
+        format!("{}/{}", self.name, key)
+    }
+
+    /// Capsule keys are never empty, so this cannot collide with them.
+    fn index_key(&self) -> String {
+        self.flash_key("")
+    }
+
+    async fn index(&self) -> Result<Index, StorageError> {
+        Ok(ariel_os_storage::get(&self.index_key())
+            .await
+            .map_err(flash_error)?
+            .unwrap_or_default())
+    }
+
+    async fn store_index(&self, index: &Index) -> Result<(), StorageError> {
+        ariel_os_storage::insert(&self.index_key(), index)
+            .await
+            .map_err(flash_error)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ArielStorageHost {
+    namespace: Option<Namespace>,
+}
+
+impl ArielStorageHost {
+    fn namespace(&self) -> Result<&Namespace, StorageError> {
+        self.namespace.as_ref().ok_or(StorageError::NotBound)
+    }
+}
+
+impl Host for ArielStorageHost {
+    async fn get(&mut self, key: String) -> Result<Option<Vec<u8>>, StorageError> {
+        let namespace = self.namespace()?;
+        let key = check_key(&key)?;
+        let value: Option<Value> = ariel_os_storage::get(&namespace.flash_key(&key))
+            .await
+            .map_err(flash_error)?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    async fn set(&mut self, key: String, value: Vec<u8>) -> Result<(), StorageError> {
+        let namespace = self.namespace()?;
+        let key = check_key(&key)?;
+        let value = Value::from_slice(&value).map_err(|()| StorageError::ValueTooLarge)?;
+
+        let mut index = namespace.index().await?;
+        let used: usize = index
+            .iter()
+            .filter(|(k, _)| *k != key)
+            .map(|(k, len)| k.len() + *len as usize)
+            .sum();
+        if used + key.len() + value.len() > namespace.quota {
+            return Err(StorageError::QuotaExceeded);
+        }
+        let len = value.len() as u32;
+        match index.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = len,
+            None => index
+                .push((key.clone(), len))
+                .map_err(|_| StorageError::TooManyKeys)?,
+        }
+
+        // Write the value first so the index never lists a key that is missing from flash
+        ariel_os_storage::insert(&namespace.flash_key(&key), value)
+            .await
+            .map_err(flash_error)?;
+        namespace.store_index(&index).await
+    }
+
+    async fn delete(&mut self, key: String) -> Result<(), StorageError> {
+        let namespace = self.namespace()?;
+        let key = check_key(&key)?;
+
+        let mut index = namespace.index().await?;
+        let Some(position) = index.iter().position(|(k, _)| *k == key) else {
+            return Ok(());
+        };
+        index.swap_remove(position);
+
+        namespace.store_index(&index).await?;
+        ariel_os_storage::remove(&namespace.flash_key(&key))
+            .await
+            .map_err(flash_error)
+    }
+
+    async fn list_keys(&mut self) -> Result<Vec<String>, StorageError> {
+        let index = self.namespace()?.index().await?;
+        Ok(index.iter().map(|(key, _)| key.to_string()).collect())
+    }
+}
+
+impl Host for ArielOSHost {
+    async fn get(&mut self, key: String) -> Result<Option<Vec<u8>>, StorageError> {
+        self.storage_host.get(key).await
+    }
+
+    async fn set(&mut self, key: String, value: Vec<u8>) -> Result<(), StorageError> {
+        self.storage_host.set(key, value).await
+    }
+
+    async fn delete(&mut self, key: String) -> Result<(), StorageError> {
+        self.storage_host.delete(key).await
+    }
+
+    async fn list_keys(&mut self) -> Result<Vec<String>, StorageError> {
+        self.storage_host.list_keys().await
+    }
+}
+
+impl ArielOSHost {
+    /// Gives capsules a private storage namespace in flash, limited to `quota` bytes of keys and
+    /// values.
+    ///
+    /// Data is only tied to the namespace, so binding the same namespace after a capsule update
+    /// or a reboot gives the new instance access to what the previous one stored.
+    ///
+    /// Fails if `namespace` is empty or contains a `/`.
+    pub fn bind_storage(&mut self, namespace: &str, quota: usize) -> Result<(), InvalidNamespace> {
+        if !is_valid_name(namespace) {
+            return Err(InvalidNamespace);
+        }
+        self.storage_host.namespace = Some(Namespace {
+            name: String::from(namespace),
+            quota,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+//! SUIT manifests for capsule updates.
+//!
+//! Processes the subset of SUIT (RFC 9019, draft-ietf-suit-manifest) needed to install a single
+//! capsule carried as an integrated payload:
+//!
+//! ```text
+//! SUIT_Envelope = #6.107({
+//!     2: bstr .cbor [ bstr .cbor SUIT_Digest, bstr .cbor COSE_Sign1 ],
+//!     3: bstr .cbor SUIT_Manifest,
+//!     "#capsule": bstr,
+//! })
+//! ```
+//!
+//! The manifest's command sequences (common, validate and install) are scanned for the vendor and
+//! class identifiers, the image digest and size, and the URI of the integrated payload. Whatever
+//! conditions the manifest lists, the identifiers are always checked against the device and the
+//! payload against its digest and size. Digests must use SHA-256, signatures EdDSA (Ed25519) with
+//! a detached payload.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use minicbor::Decoder;
+use minicbor::data::Type;
+use sha2::{Digest, Sha256};
+
+use super::signed::{self, TrustedKey, VerifyError};
+
+/// COSE algorithm identifier of SHA-256.
+const SHA_256: i64 = -16;
+/// COSE algorithm identifier of EdDSA.
+const EDDSA: i64 = -8;
+
+/// Identifies the devices a capsule is meant for, as 16 byte UUIDs (RFC 9124).
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceIdentity {
+    pub vendor_id: [u8; 16],
+    pub class_id: [u8; 16],
+}
+
+/// Reasons for refusing an update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuitError {
+    /// The envelope or manifest is not valid CBOR or lacks mandatory parts.
+    Malformed,
+    /// The manifest uses a version, algorithm or form that is not supported.
+    Unsupported,
+    /// No trusted key signed the manifest.
+    BadSignature,
+    /// The manifest does not match the digest that was signed.
+    ManifestDigestMismatch,
+    /// The capsule is for another vendor.
+    WrongVendor,
+    /// The capsule is for another class of devices.
+    WrongClass,
+    /// The envelope does not contain the payload the manifest refers to.
+    MissingPayload,
+    /// The payload does not have the size given in the manifest.
+    SizeMismatch,
+    /// The payload does not match the digest given in the manifest.
+    DigestMismatch,
+    /// The image is a signed container that failed verification.
+    Container(VerifyError),
+}
+
+impl core::fmt::Display for SuitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let reason = match self {
+            SuitError::Malformed => "malformed SUIT envelope",
+            SuitError::Unsupported => "unsupported SUIT manifest",
+            SuitError::BadSignature => "manifest is not signed by a trusted key",
+            SuitError::ManifestDigestMismatch => "manifest does not match its digest",
+            SuitError::WrongVendor => "capsule is for another vendor",
+            SuitError::WrongClass => "capsule is for another class of devices",
+            SuitError::MissingPayload => "envelope does not contain the capsule",
+            SuitError::SizeMismatch => "capsule size does not match the manifest",
+            SuitError::DigestMismatch => "capsule does not match its digest",
+            SuitError::Container(e) => return e.fmt(f),
+        };
+        f.write_str(reason)
+    }
+}
+
+impl core::error::Error for SuitError {}
+
+impl From<minicbor::decode::Error> for SuitError {
+    fn from(_: minicbor::decode::Error) -> Self {
+        SuitError::Malformed
+    }
+}
+
+impl From<VerifyError> for SuitError {
+    fn from(error: VerifyError) -> Self {
+        SuitError::Container(error)
+    }
+}
+
+/// A capsule whose manifest checked out.
+#[derive(Debug, Clone, Copy)]
+pub struct VerifiedUpdate<'a> {
+    version: u64,
+    payload: &'a [u8],
+}
+
+impl<'a> VerifiedUpdate<'a> {
+    /// Sequence number of the manifest, or version of a signed container.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The precompiled component, to be passed to `Component::deserialize{,_raw}`.
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+}
+
+/// Checks an update that is either a SUIT envelope or a signed container (see
+/// [`signed::verify`]).
+///
+/// Signed containers start with [`signed::MAGIC`], which is not valid at the start of an envelope.
+pub fn verify_update<'a>(
+    image: &'a [u8],
+    keys: &[TrustedKey],
+    device: &DeviceIdentity,
+) -> Result<VerifiedUpdate<'a>, SuitError> {
+    if image.starts_with(&signed::MAGIC) {
+        let capsule = signed::verify(image, keys)?;
+        Ok(VerifiedUpdate {
+            version: capsule.version().into(),
+            payload: capsule.payload(),
+        })
+    } else {
+        process(image, keys, device)
+    }
+}
+
+/// Checks a SUIT envelope and returns the capsule it carries.
+///
+/// Only the payload of a successfully processed envelope may be deserialized.
+pub fn process<'a>(
+    envelope: &'a [u8],
+    keys: &[TrustedKey],
+    device: &DeviceIdentity,
+) -> Result<VerifiedUpdate<'a>, SuitError> {
+    let mut authentication = None;
+    let mut manifest = None;
+    let mut integrated = Vec::new();
+
+    let mut d = Decoder::new(envelope);
+    skip_tag(&mut d)?;
+    for _ in 0..definite(d.map()?)? {
+        match d.datatype()? {
+            Type::U8 | Type::U16 | Type::U32 | Type::U64 => match d.u64()? {
+                2 => authentication = Some(d.bytes()?),
+                3 => manifest = Some(d.bytes()?),
+                _ => d.skip()?,
+            },
+            Type::String => {
+                let key = d.str()?;
+                integrated.push((key, d.bytes()?));
+            }
+            _ => return Err(SuitError::Malformed),
+        }
+    }
+    let authentication = authentication.ok_or(SuitError::Malformed)?;
+    let manifest = manifest.ok_or(SuitError::Malformed)?;
+
+    // Check the signature first: nothing in the manifest can be trusted before
+    let digest = authenticate(authentication, keys)?;
+    if Sha256::digest(manifest)[..] != *digest {
+        return Err(SuitError::ManifestDigestMismatch);
+    }
+
+    let (version, parameters) = parse_manifest(manifest)?;
+
+    if parameters.vendor_id != Some(&device.vendor_id[..]) {
+        return Err(SuitError::WrongVendor);
+    }
+    if parameters.class_id != Some(&device.class_id[..]) {
+        return Err(SuitError::WrongClass);
+    }
+
+    let uri = parameters.uri.ok_or(SuitError::MissingPayload)?;
+    let payload = integrated
+        .iter()
+        .find(|(key, _)| *key == uri)
+        .map(|(_, payload)| *payload)
+        .ok_or(SuitError::MissingPayload)?;
+
+    let size = parameters.image_size.ok_or(SuitError::Malformed)?;
+    if u64::try_from(payload.len()) != Ok(size) {
+        return Err(SuitError::SizeMismatch);
+    }
+    let image_digest = parse_digest(parameters.image_digest.ok_or(SuitError::Malformed)?)?;
+    if Sha256::digest(payload)[..] != *image_digest {
+        return Err(SuitError::DigestMismatch);
+    }
+
+    Ok(VerifiedUpdate { version, payload })
+}
+
+/// Checks the authentication wrapper and returns the signed SHA-256 digest of the manifest.
+fn authenticate<'a>(wrapper: &'a [u8], keys: &[TrustedKey]) -> Result<&'a [u8], SuitError> {
+    let mut d = Decoder::new(wrapper);
+    let len = definite(d.array()?)?;
+    if len < 2 {
+        return Err(SuitError::BadSignature);
+    }
+    let digest_bstr = d.bytes()?;
+    let digest = parse_digest(digest_bstr)?;
+    for _ in 1..len {
+        if verify_cose_sign1(d.bytes()?, digest_bstr, keys)? {
+            return Ok(digest);
+        }
+    }
+    Err(SuitError::BadSignature)
+}
+
+/// Checks whether a COSE_Sign1 with detached payload signs `payload` with one of the `keys`.
+fn verify_cose_sign1(cose: &[u8], payload: &[u8], keys: &[TrustedKey]) -> Result<bool, SuitError> {
+    let mut d = Decoder::new(cose);
+    skip_tag(&mut d)?;
+    if definite(d.array()?)? != 4 {
+        return Err(SuitError::Malformed);
+    }
+    let protected = d.bytes()?;
+    // Unprotected header; the key id is not needed with the few keys a device trusts
+    d.skip()?;
+    match d.datatype()? {
+        Type::Null => d.null()?,
+        _ => {
+            if d.bytes()? != payload {
+                return Ok(false);
+            }
+        }
+    }
+    let Ok(signature) = Signature::from_slice(d.bytes()?) else {
+        return Ok(false);
+    };
+
+    let mut p = Decoder::new(protected);
+    let mut algorithm = None;
+    for _ in 0..definite(p.map()?)? {
+        match p.i64()? {
+            1 => algorithm = Some(p.i64()?),
+            _ => p.skip()?,
+        }
+    }
+    // Other signers of the envelope may use algorithms this device cannot check
+    if algorithm != Some(EDDSA) {
+        return Ok(false);
+    }
+
+    // Sig_structure = ["Signature1", protected, external_aad, payload]
+    let mut to_be_signed = Vec::with_capacity(protected.len() + payload.len() + 24);
+    to_be_signed.push(0x84);
+    push_text(&mut to_be_signed, "Signature1");
+    push_bytes(&mut to_be_signed, protected);
+    push_bytes(&mut to_be_signed, &[]);
+    push_bytes(&mut to_be_signed, payload);
+
+    Ok(keys.iter().any(|key| {
+        VerifyingKey::from_bytes(&key.public_key)
+            .is_ok_and(|key| key.verify_strict(&to_be_signed, &signature).is_ok())
+    }))
+}
+
+/// Parameters gathered from the command sequences.
+#[derive(Default)]
+struct Parameters<'a> {
+    vendor_id: Option<&'a [u8]>,
+    class_id: Option<&'a [u8]>,
+    image_digest: Option<&'a [u8]>,
+    image_size: Option<u64>,
+    uri: Option<&'a str>,
+}
+
+/// Returns the sequence number and the parameters of a manifest.
+fn parse_manifest(manifest: &[u8]) -> Result<(u64, Parameters<'_>), SuitError> {
+    let mut version = None;
+    let mut sequence_number = None;
+    let mut parameters = Parameters::default();
+
+    let mut d = Decoder::new(manifest);
+    for _ in 0..definite(d.map()?)? {
+        match d.i64()? {
+            1 => version = Some(d.u64()?),
+            2 => sequence_number = Some(d.u64()?),
+            // suit-common
+            3 => {
+                let mut c = Decoder::new(d.bytes()?);
+                for _ in 0..definite(c.map()?)? {
+                    match c.i64()? {
+                        // suit-shared-sequence
+                        4 => scan_commands(c.bytes()?, &mut parameters)?,
+                        _ => c.skip()?,
+                    }
+                }
+            }
+            // suit-validate and suit-install; severed members are not supported
+            7 | 17 => match d.datatype()? {
+                Type::Bytes => scan_commands(d.bytes()?, &mut parameters)?,
+                _ => return Err(SuitError::Unsupported),
+            },
+            _ => d.skip()?,
+        }
+    }
+    if version != Some(1) {
+        return Err(SuitError::Unsupported);
+    }
+    Ok((sequence_number.ok_or(SuitError::Malformed)?, parameters))
+}
+
+/// Gathers the parameters set in a command sequence.
+fn scan_commands<'a>(sequence: &'a [u8], parameters: &mut Parameters<'a>) -> Result<(), SuitError> {
+    let mut d = Decoder::new(sequence);
+    let len = definite(d.array()?)?;
+    if len % 2 != 0 {
+        return Err(SuitError::Malformed);
+    }
+    for _ in 0..len / 2 {
+        match d.i64()? {
+            // suit-directive-override-parameters
+            20 => {
+                for _ in 0..definite(d.map()?)? {
+                    match d.i64()? {
+                        1 => parameters.vendor_id = Some(d.bytes()?),
+                        2 => parameters.class_id = Some(d.bytes()?),
+                        3 => parameters.image_digest = Some(d.bytes()?),
+                        14 => parameters.image_size = Some(d.u64()?),
+                        21 => parameters.uri = Some(d.str()?),
+                        _ => d.skip()?,
+                    }
+                }
+            }
+            // Conditions are checked after scanning, other directives do not apply
+            _ => d.skip()?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the bytes of a SUIT_Digest, which must use SHA-256.
+fn parse_digest(digest: &[u8]) -> Result<&[u8], SuitError> {
+    let mut d = Decoder::new(digest);
+    if definite(d.array()?)? != 2 {
+        return Err(SuitError::Malformed);
+    }
+    if d.i64()? != SHA_256 {
+        return Err(SuitError::Unsupported);
+    }
+    let bytes = d.bytes()?;
+    if bytes.len() != 32 {
+        return Err(SuitError::Malformed);
+    }
+    Ok(bytes)
+}
+
+/// Skips the (optional) tag of a tagged item.
+fn skip_tag(d: &mut Decoder<'_>) -> Result<(), SuitError> {
+    if d.datatype()? == Type::Tag {
+        d.tag()?;
+    }
+    Ok(())
+}
+
+/// Indefinite length items are not used by SUIT.
+fn definite(len: Option<u64>) -> Result<u64, SuitError> {
+    len.ok_or(SuitError::Malformed)
+}
+
+fn push_header(out: &mut Vec<u8>, major: u8, len: usize) {
+    let major = major << 5;
+    match len {
+        0..24 => out.push(major | len as u8),
+        24..0x100 => out.extend_from_slice(&[major | 24, len as u8]),
+        0x100..0x10000 => {
+            out.push(major | 25);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 26);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn push_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    push_header(out, 2, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn push_text(out: &mut Vec<u8>, text: &str) {
+    push_header(out, 3, text.len());
+    out.extend_from_slice(text.as_bytes());
+}
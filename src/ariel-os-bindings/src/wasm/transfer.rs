@@ -0,0 +1,356 @@
+//! Reliable capsule transfer over datagrams.
+//!
+//! A small stop-and-wait protocol to send a capsule image over UDP, as done by the
+//! `insecure-updates` example and its `send_files.rs`. The sender announces the image, then sends
+//! it in chunks at explicit offsets; the receiver acknowledges every frame with the number of
+//! contiguous bytes it holds, so lost or reordered datagrams are simply sent again. Once complete,
+//! the image is checked against the SHA-256 digest from the announcement.
+//!
+//! All integers are big endian. Sender to receiver:
+//!
+//! | Frame | Layout                                                            |
+//! |-------|-------------------------------------------------------------------|
+//! | Start | `0x01`, transfer id (2), image length (4), SHA-256 of the image (32) |
+//! | Data  | `0x02`, transfer id (2), offset (4), data                          |
+//!
+//! Receiver to sender, always [`REPLY_LEN`] bytes:
+//!
+//! | Reply | Layout                                                     |
+//! |-------|------------------------------------------------------------|
+//! | Ack   | `0x81`, transfer id (2), next expected offset (4), `0`      |
+//! | Nack  | `0x82`, transfer id (2), next expected offset (4), reason (1) |
+//! | Done  | `0x83`, transfer id (2), image length (4), `0`              |
+//!
+//! Announcing the same transfer (id, length and digest) again resumes it: the receiver answers with
+//! the offset it got to, or with Done if it already has the whole image.
+
+use sha2::{Digest, Sha256};
+
+const START: u8 = 0x01;
+const DATA: u8 = 0x02;
+const ACK: u8 = 0x81;
+const NACK: u8 = 0x82;
+const DONE: u8 = 0x83;
+
+pub const START_LEN: usize = 39;
+pub const DATA_HEADER_LEN: usize = 7;
+pub const REPLY_LEN: usize = 8;
+
+/// A frame from the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame<'a> {
+    Start { id: u16, len: u32, sha256: [u8; 32] },
+    Data { id: u16, offset: u32, data: &'a [u8] },
+}
+
+impl<'a> Frame<'a> {
+    pub fn parse(datagram: &'a [u8]) -> Option<Self> {
+        let (&kind, rest) = datagram.split_first()?;
+        let id = u16::from_be_bytes(rest.get(0..2)?.try_into().ok()?);
+        let value = u32::from_be_bytes(rest.get(2..6)?.try_into().ok()?);
+        match kind {
+            START => Some(Frame::Start {
+                id,
+                len: value,
+                sha256: rest.get(6..38)?.try_into().ok()?,
+            }),
+            DATA => Some(Frame::Data {
+                id,
+                offset: value,
+                data: &rest[6..],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Writes the frame into `out`, returning its length, or `None` if `out` is too short.
+    pub fn encode(&self, out: &mut [u8]) -> Option<usize> {
+        let len = match self {
+            Frame::Start { .. } => START_LEN,
+            Frame::Data { data, .. } => DATA_HEADER_LEN + data.len(),
+        };
+        let out = out.get_mut(..len)?;
+        let (kind, id, value) = match *self {
+            Frame::Start { id, len, sha256 } => {
+                out[7..].copy_from_slice(&sha256);
+                (START, id, len)
+            }
+            Frame::Data { id, offset, data } => {
+                out[7..].copy_from_slice(data);
+                (DATA, id, offset)
+            }
+        };
+        out[0] = kind;
+        out[1..3].copy_from_slice(&id.to_be_bytes());
+        out[3..7].copy_from_slice(&value.to_be_bytes());
+        Some(len)
+    }
+}
+
+/// Why the receiver refused a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum NackReason {
+    /// The image is empty, larger than the receive buffer, or data goes past its end.
+    BadLength = 1,
+    /// The data is not at the next expected offset.
+    OutOfOrder = 2,
+    /// The complete image does not match the announced digest; the transfer must start over.
+    HashMismatch = 3,
+    /// No such transfer was announced; it must start over.
+    UnknownTransfer = 4,
+}
+
+impl NackReason {
+    fn from_u8(reason: u8) -> Option<Self> {
+        match reason {
+            1 => Some(NackReason::BadLength),
+            2 => Some(NackReason::OutOfOrder),
+            3 => Some(NackReason::HashMismatch),
+            4 => Some(NackReason::UnknownTransfer),
+            _ => None,
+        }
+    }
+}
+
+/// A reply from the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    Ack {
+        id: u16,
+        next: u32,
+    },
+    Nack {
+        id: u16,
+        next: u32,
+        reason: NackReason,
+    },
+    Done {
+        id: u16,
+        len: u32,
+    },
+}
+
+impl Reply {
+    pub fn parse(datagram: &[u8]) -> Option<Self> {
+        let datagram: &[u8; REPLY_LEN] = datagram.try_into().ok()?;
+        let id = u16::from_be_bytes([datagram[1], datagram[2]]);
+        let value = u32::from_be_bytes(datagram[3..7].try_into().unwrap());
+        match datagram[0] {
+            ACK => Some(Reply::Ack { id, next: value }),
+            NACK => Some(Reply::Nack {
+                id,
+                next: value,
+                reason: NackReason::from_u8(datagram[7])?,
+            }),
+            DONE => Some(Reply::Done { id, len: value }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; REPLY_LEN] {
+        let (kind, id, value, reason) = match *self {
+            Reply::Ack { id, next } => (ACK, id, next, 0),
+            Reply::Nack { id, next, reason } => (NACK, id, next, reason as u8),
+            Reply::Done { id, len } => (DONE, id, len, 0),
+        };
+        let mut out = [0; REPLY_LEN];
+        out[0] = kind;
+        out[1..3].copy_from_slice(&id.to_be_bytes());
+        out[3..7].copy_from_slice(&value.to_be_bytes());
+        out[7] = reason;
+        out
+    }
+}
+
+/// What [`Receiver::handle`] made of a datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handled {
+    /// To be sent back to the sender of the datagram.
+    pub reply: Option<Reply>,
+    /// Whether this datagram completed the image, see [`Receiver::image`].
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    Receiving {
+        id: u16,
+        len: u32,
+        sha256: [u8; 32],
+        received: u32,
+    },
+    Complete {
+        id: u16,
+        len: u32,
+        sha256: [u8; 32],
+    },
+}
+
+/// Receives a capsule image into a caller-provided buffer.
+///
+/// This does not touch the network: feed it every datagram and send back the replies.
+///
+/// ```ignore
+/// let (n, peer) = socket.recv_from(&mut buf).await?;
+/// let handled = receiver.handle(&buf[..n]);
+/// if let Some(reply) = handled.reply {
+///     socket.send_to(&reply.encode(), peer).await?;
+/// }
+/// if handled.complete {
+///     let image = receiver.image().unwrap();
+/// }
+/// ```
+pub struct Receiver<'a> {
+    buffer: &'a mut [u8],
+    state: State,
+}
+
+impl<'a> Receiver<'a> {
+    /// Images up to the size of `buffer` can be received.
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            state: State::Idle,
+        }
+    }
+
+    /// Handles a datagram from the sender.
+    ///
+    /// Start frames never write into the buffer, so they can be handled while a capsule still
+    /// runs from the previous image. Data frames can.
+    pub fn handle(&mut self, datagram: &[u8]) -> Handled {
+        let reply = match Frame::parse(datagram) {
+            Some(Frame::Start { id, len, sha256 }) => self.start(id, len, sha256),
+            Some(Frame::Data { id, offset, data }) => return self.data(id, offset, data),
+            None => None,
+        };
+        Handled {
+            reply,
+            complete: false,
+        }
+    }
+
+    /// Whether a transfer was announced and has not completed yet.
+    pub fn in_progress(&self) -> bool {
+        matches!(self.state, State::Receiving { .. })
+    }
+
+    /// The last complete image.
+    pub fn image(&self) -> Option<&[u8]> {
+        match self.state {
+            State::Complete { len, .. } => Some(&self.buffer[..len as usize]),
+            _ => None,
+        }
+    }
+
+    /// Forgets about any transfer.
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+    }
+
+    fn start(&mut self, id: u16, len: u32, sha256: [u8; 32]) -> Option<Reply> {
+        match self.state {
+            State::Receiving {
+                id: current,
+                len: current_len,
+                sha256: current_sha256,
+                received,
+            } if (current, current_len, current_sha256) == (id, len, sha256) => {
+                return Some(Reply::Ack { id, next: received });
+            }
+            State::Complete {
+                id: current,
+                len: current_len,
+                sha256: current_sha256,
+            } if (current, current_len, current_sha256) == (id, len, sha256) => {
+                return Some(Reply::Done { id, len });
+            }
+            _ => {}
+        }
+        if len == 0 || usize::try_from(len).map_or(true, |len| len > self.buffer.len()) {
+            self.state = State::Idle;
+            return Some(Reply::Nack {
+                id,
+                next: 0,
+                reason: NackReason::BadLength,
+            });
+        }
+        self.state = State::Receiving {
+            id,
+            len,
+            sha256,
+            received: 0,
+        };
+        Some(Reply::Ack { id, next: 0 })
+    }
+
+    fn data(&mut self, id: u16, offset: u32, data: &[u8]) -> Handled {
+        let reply = |reply| Handled {
+            reply: Some(reply),
+            complete: false,
+        };
+        let (len, sha256, received) = match self.state {
+            State::Receiving {
+                id: current,
+                len,
+                sha256,
+                received,
+            } if current == id => (len, sha256, received),
+            // The Done reply got lost
+            State::Complete { id: current, len, .. } if current == id => {
+                return reply(Reply::Done { id, len });
+            }
+            _ => {
+                return reply(Reply::Nack {
+                    id,
+                    next: 0,
+                    reason: NackReason::UnknownTransfer,
+                });
+            }
+        };
+        if offset != received {
+            return reply(Reply::Nack {
+                id,
+                next: received,
+                reason: NackReason::OutOfOrder,
+            });
+        }
+        let end = u32::try_from(data.len())
+            .ok()
+            .and_then(|n| received.checked_add(n))
+            .filter(|end| *end <= len);
+        let Some(end) = end else {
+            return reply(Reply::Nack {
+                id,
+                next: received,
+                reason: NackReason::BadLength,
+            });
+        };
+        self.buffer[received as usize..end as usize].copy_from_slice(data);
+
+        if end < len {
+            self.state = State::Receiving {
+                id,
+                len,
+                sha256,
+                received: end,
+            };
+            return reply(Reply::Ack { id, next: end });
+        }
+        if Sha256::digest(&self.buffer[..len as usize])[..] != sha256 {
+            self.state = State::Idle;
+            return reply(Reply::Nack {
+                id,
+                next: 0,
+                reason: NackReason::HashMismatch,
+            });
+        }
+        self.state = State::Complete { id, len, sha256 };
+        Handled {
+            reply: Some(Reply::Done { id, len }),
+            complete: true,
+        }
+    }
+}
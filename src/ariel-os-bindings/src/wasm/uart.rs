@@ -0,0 +1,101 @@
+use ariel_os_embassy::api::time::Timer;
+use ariel_os_hal::uart::Uart as HalUart;
+
+extern crate alloc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use embassy_futures::select::{Either, select};
+use embedded_io_async::{Error as _, ErrorKind, Read as _, Write as _};
+
+use wasmtime::component::bindgen;
+
+use super::ArielOSHost;
+
+bindgen!({
+    world: "ariel:wasm-bindings/uart",
+    path: "../../wit/",
+    imports: { default: async },
+});
+
+pub use ariel::wasm_bindings::uart_api::{Host, HostWithStore, UartError, add_to_linker};
+
+/// Most bytes a single read hands back to the capsule.
+pub const MAX_READ_LEN: usize = 256;
+
+impl From<ErrorKind> for UartError {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::InvalidData => UartError::Framing,
+            _ => UartError::Other,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ArielUartHost {
+    uart: Option<HalUart<'static>>,
+}
+
+impl ArielUartHost {
+    fn uart(&mut self) -> Result<&mut HalUart<'static>, UartError> {
+        self.uart.as_mut().ok_or(UartError::NotBound)
+    }
+}
+
+impl Host for ArielUartHost {
+    async fn read(
+        &mut self,
+        max_len: u32,
+        timeout_millis: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, UartError> {
+        let uart = self.uart()?;
+        let mut buf = vec![0; (max_len as usize).min(MAX_READ_LEN)];
+        let read = match timeout_millis {
+            Some(millis) => match select(uart.read(&mut buf), Timer::after_millis(millis)).await {
+                Either::First(read) => read,
+                Either::Second(()) => return Ok(None),
+            },
+            None => uart.read(&mut buf).await,
+        };
+        let n = read.map_err(|e| UartError::from(e.kind()))?;
+        buf.truncate(n);
+        Ok(Some(buf))
+    }
+
+    async fn write(&mut self, data: Vec<u8>) -> Result<(), UartError> {
+        self.uart()?
+            .write_all(&data)
+            .await
+            .map_err(|e| e.kind().into())
+    }
+
+    async fn flush(&mut self) -> Result<(), UartError> {
+        self.uart()?.flush().await.map_err(|e| e.kind().into())
+    }
+}
+
+impl Host for ArielOSHost {
+    async fn read(
+        &mut self,
+        max_len: u32,
+        timeout_millis: Option<u64>,
+    ) -> Result<Option<Vec<u8>>, UartError> {
+        self.uart_host.read(max_len, timeout_millis).await
+    }
+
+    async fn write(&mut self, data: Vec<u8>) -> Result<(), UartError> {
+        self.uart_host.write(data).await
+    }
+
+    async fn flush(&mut self) -> Result<(), UartError> {
+        self.uart_host.flush().await
+    }
+}
+
+impl ArielOSHost {
+    /// Gives capsules access to a UART, e.g. to parse Modbus-RTU or NMEA from a capsule.
+    pub fn bind_uart(&mut self, uart: HalUart<'static>) {
+        self.uart_host.uart = Some(uart);
+    }
+}
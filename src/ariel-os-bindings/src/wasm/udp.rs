@@ -1,12 +1,18 @@
 use ariel_os_debug::log::info;
+use ariel_os_embassy::api::time::Timer;
 use ariel_os_embassy::reexports::embassy_net;
 
 use embassy_net::IpAddress;
-use embassy_net::udp::{PacketMetadata, UdpMetadata, UdpSocket};
+use embassy_net::udp::{BindError, PacketMetadata, RecvError, SendError, UdpMetadata, UdpSocket};
 
-use wasmtime::component::Resource;
+use wasmtime::component::{Resource, ResourceTable};
 
-use core::mem;
+use critical_section::Mutex;
+
+use core::cell::Cell;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+use core::task::Poll;
 
 extern crate alloc;
 use alloc::vec::Vec;
@@ -18,38 +24,220 @@ use super::ArielOSHost;
 bindgen!({
     world: "ariel:wasm-bindings/udp",
     path: "../../wit/",
+    with: {
+        "ariel:wasm-bindings/udp-api.udp-socket": UdpSocketHandle,
+    },
+    imports: {
+        "ariel:wasm-bindings/udp-api.[method]udp-socket.recv": async,
+        "ariel:wasm-bindings/udp-api.[method]udp-socket.send": async,
+    }
 });
 
 pub use ariel::wasm_bindings::udp_api::add_to_linker;
-pub use ariel::wasm_bindings::udp_api::{self as gen_udp, Host, HostUdpSocket, HostWithStore};
+pub use ariel::wasm_bindings::udp_api::{
+    self as gen_udp, Host, HostUdpSocket, HostWithStore, UdpError,
+};
+
+use embassy_futures::poll_once;
+use embassy_futures::select::{Either, select};
+
+/// Buffers for one socket of a [`UdpSocketPool`], holding up to `META` datagrams of up to `SIZE`
+/// bytes in each direction.
+///
+/// Meant to be placed in a `StaticCell` and handed over to [`UdpSocketPool::with_buffers`].
+pub struct UdpSocketBuffers<const META: usize, const SIZE: usize> {
+    rx_meta: [PacketMetadata; META],
+    rx_buffer: [u8; SIZE],
+    tx_meta: [PacketMetadata; META],
+    tx_buffer: [u8; SIZE],
+}
+
+impl<const META: usize, const SIZE: usize> UdpSocketBuffers<META, SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            rx_meta: [PacketMetadata::EMPTY; META],
+            rx_buffer: [0; SIZE],
+            tx_meta: [PacketMetadata::EMPTY; META],
+            tx_buffer: [0; SIZE],
+        }
+    }
+}
 
-use embassy_futures::block_on;
+impl<const META: usize, const SIZE: usize> Default for UdpSocketBuffers<META, SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pool entry pointing into a leased [`UdpSocketBuffers`].
+///
+/// # Safety invariants
+///
+/// The pointers come from a `&'static mut` and are only turned into references by the single
+/// socket that claimed the slot, until it releases it.
+struct SocketBuffers {
+    rx_meta: NonNull<[PacketMetadata]>,
+    rx_buffer: NonNull<[u8]>,
+    tx_meta: NonNull<[PacketMetadata]>,
+    tx_buffer: NonNull<[u8]>,
+    in_use: Mutex<Cell<bool>>,
+}
+
+// SAFETY: The buffers are only accessed through the pointers by the socket that claimed them, and
+// claims are made in a critical section, so no two threads can hold the same slot.
+unsafe impl Send for SocketBuffers {}
+unsafe impl Sync for SocketBuffers {}
+
+impl SocketBuffers {
+    /// Marks the buffers as used, returning whether they were free.
+    fn claim(&self) -> bool {
+        critical_section::with(|cs| !self.in_use.borrow(cs).replace(true))
+    }
+
+    fn release(&self) {
+        critical_section::with(|cs| self.in_use.borrow(cs).set(false));
+    }
+}
+
+/// Network stack of a [`UdpSocketPool`].
+struct PoolStack(ariel_os_embassy::NetworkStack);
+
+// SAFETY: embassy-net requires the stack and its sockets to only be used from the executor running
+// the stack, so stores with UDP sockets must run there too. The bounds are only required because
+// hosts, which refer to the pool, must be `Send`.
+unsafe impl Send for PoolStack {}
+unsafe impl Sync for PoolStack {}
+
+/// The network stack and the socket buffers capsules open their sockets from.
+///
+/// The pool lives outside of the hosts, so replacing a capsule together with its store does not
+/// lose any buffers: sockets hand theirs back when they are dropped, including when the store
+/// holding them is.
+///
+/// ```ignore
+/// static BUFFERS: StaticCell<[UdpSocketBuffers<1, 128>; 2]> = StaticCell::new();
+/// static POOL: StaticCell<UdpSocketPool> = StaticCell::new();
+/// let pool = BUFFERS
+///     .init([const { UdpSocketBuffers::new() }; 2])
+///     .iter_mut()
+///     .fold(UdpSocketPool::new(stack), UdpSocketPool::with_buffers);
+/// let pool = POOL.init(pool);
+/// // For every host, including those of updated capsules
+/// host.set_udp_pool(pool);
+/// ```
+pub struct UdpSocketPool {
+    stack: PoolStack,
+    slots: Vec<SocketBuffers>,
+}
+
+impl UdpSocketPool {
+    /// Creates a pool without any buffers, i.e. on which every `open` fails with `pool-exhausted`.
+    pub fn new(stack: ariel_os_embassy::NetworkStack) -> Self {
+        Self {
+            stack: PoolStack(stack),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Adds one socket's worth of buffers to the pool.
+    ///
+    /// Can be called several times to let capsules open several sockets at once.
+    pub fn with_buffers<const META: usize, const SIZE: usize>(
+        mut self,
+        buffers: &'static mut UdpSocketBuffers<META, SIZE>,
+    ) -> Self {
+        self.slots.push(SocketBuffers {
+            rx_meta: NonNull::from(buffers.rx_meta.as_mut_slice()),
+            rx_buffer: NonNull::from(buffers.rx_buffer.as_mut_slice()),
+            tx_meta: NonNull::from(buffers.tx_meta.as_mut_slice()),
+            tx_buffer: NonNull::from(buffers.tx_buffer.as_mut_slice()),
+            in_use: Mutex::new(Cell::new(false)),
+        });
+        self
+    }
+
+    /// Creates a socket on the first free buffers of the pool.
+    fn open_socket(&'static self) -> Result<UdpSocketHandle, UdpError> {
+        let (slot, buffers) = self
+            .slots
+            .iter()
+            .enumerate()
+            .find(|(_, buffers)| buffers.claim())
+            .ok_or(UdpError::PoolExhausted)?;
+
+        let buffer_size = buffers.rx_buffer.len();
+        // SAFETY:
+        // * The pointers are valid forever since they come from a `&'static mut`.
+        // * Exclusivity is ensured by the claim, which is only released after the socket is dropped.
+        let socket = unsafe {
+            UdpSocket::new(
+                self.stack.0,
+                &mut *buffers.rx_meta.as_ptr(),
+                &mut *buffers.rx_buffer.as_ptr(),
+                &mut *buffers.tx_meta.as_ptr(),
+                &mut *buffers.tx_buffer.as_ptr(),
+            )
+        };
+
+        Ok(UdpSocketHandle {
+            socket: ManuallyDrop::new(socket),
+            pool: self,
+            slot,
+            buffer_size,
+        })
+    }
+}
+
+/// Host side of a `udp-socket` resource owned by a capsule.
+///
+/// Gives its buffers back to the pool when dropped.
+pub struct UdpSocketHandle {
+    socket: ManuallyDrop<UdpSocket<'static>>,
+    pool: &'static UdpSocketPool,
+    slot: usize,
+    buffer_size: usize,
+}
+
+// SAFETY: See `PoolStack`; the socket is only used from the executor running the stack. The bound
+// is only required by `ResourceTable`.
+unsafe impl Send for UdpSocketHandle {}
+
+impl Drop for UdpSocketHandle {
+    fn drop(&mut self) {
+        // SAFETY: The socket is not used after this.
+        // The socket has to be gone before its buffers can be handed out again.
+        unsafe { ManuallyDrop::drop(&mut self.socket) };
+        self.pool.slots[self.slot].release();
+    }
+}
+
+impl UdpSocketHandle {
+    // The socket is polled by hand (and through `move` closures) so that the futures only hold
+    // `&mut Self`, which is `Send`, instead of a `&UdpSocket`, which isn't.
+
+    async fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, UdpMetadata), RecvError> {
+        core::future::poll_fn(move |cx| self.socket.poll_recv_from(buf, cx)).await
+    }
+
+    async fn send_to(&mut self, buf: &[u8], endpoint: UdpMetadata) -> Result<(), SendError> {
+        core::future::poll_fn(move |cx| self.socket.poll_send_to(buf, endpoint, cx)).await
+    }
+
+    fn new_buffer(&self) -> Vec<u8> {
+        core::iter::repeat_n(0, self.buffer_size).collect()
+    }
+}
 
 #[derive(Default)]
 pub struct ArielUDPHost {
-    socket: Option<UdpSocket<'static>>,
-    buffer_size: usize,
+    pool: Option<&'static UdpSocketPool>,
+    table: ResourceTable,
 }
 
 impl ArielUDPHost {
-    pub unsafe fn initialize_socket(
-        &mut self,
-        stack: ariel_os_embassy::NetworkStack,
-        rx_meta: &mut [PacketMetadata],
-        rx_buffer: &mut [u8],
-        tx_meta: &mut [PacketMetadata],
-        tx_buffer: &mut [u8],
-    ) {
-        let buff_size = tx_buffer.len();
-        let rx_meta: &'static mut [PacketMetadata] = unsafe { mem::transmute(rx_meta) };
-        let rx_buffer: &'static mut [u8] = unsafe { mem::transmute(rx_buffer) };
-        let tx_meta: &'static mut [PacketMetadata] = unsafe { mem::transmute(tx_meta) };
-        let tx_buffer: &'static mut [u8] = unsafe { mem::transmute(tx_buffer) };
-
-        let socket = UdpSocket::new(stack, rx_meta, rx_buffer, tx_meta, tx_buffer);
-
-        self.socket = Some(socket);
-        self.buffer_size = buff_size;
+    /// Lets capsules open sockets from `pool`.
+    pub fn set_pool(&mut self, pool: &'static UdpSocketPool) {
+        self.pool = Some(pool);
     }
 }
 
@@ -64,144 +252,232 @@ impl gen_udp::Ipv4Addr {
     }
 }
 
-// #[cfg(feature = "ipv6")]
-// impl udp::Ipv6Addr {
-//     fn from_segments(segments: [u16; 8]) -> Self {
-//         Self {
-//             a: segments[0],
-//             b: segments[1],
-//             c: segments[2],
-//             d: segments[3],
-//             e: segments[4],
-//             f: segments[5],
-//             g: segments[6],
-//             h: segments[7],
-//         }
-//     }
-// }
-
-impl From<IpAddress> for gen_udp::IpAddr {
-    fn from(t: IpAddress) -> Self {
+#[cfg(feature = "ipv6")]
+impl gen_udp::Ipv6Addr {
+    fn from_segments(segments: [u16; 8]) -> Self {
+        Self {
+            a: segments[0],
+            b: segments[1],
+            c: segments[2],
+            d: segments[3],
+            e: segments[4],
+            f: segments[5],
+            g: segments[6],
+            h: segments[7],
+        }
+    }
+}
+
+/// Error returned when an address cannot be passed between a capsule and the network stack,
+/// e.g. an IPv6 address without the `ipv6` feature.
+#[derive(Debug)]
+pub struct UnsupportedAddress;
+
+impl TryFrom<IpAddress> for gen_udp::IpAddr {
+    type Error = UnsupportedAddress;
+
+    fn try_from(t: IpAddress) -> Result<Self, Self::Error> {
         match t {
             IpAddress::Ipv4(ipaddr) => {
                 let octs = ipaddr.octets();
-                gen_udp::IpAddr::V4(gen_udp::Ipv4Addr::from_octets(octs))
+                Ok(gen_udp::IpAddr::V4(gen_udp::Ipv4Addr::from_octets(octs)))
             }
-            // #[cfg(feature = "ipv6")]
-            // IpAddress::Ipv6(ipaddr) => {
-            //     let segments = ipaddr.segments();
-            //     udp::IpAddr::V6(
-            //         udp::Ipv6Addr::from_segments(segments)
-            //     )
-            // },
+            #[cfg(feature = "ipv6")]
+            IpAddress::Ipv6(ipaddr) => {
+                let segments = ipaddr.segments();
+                Ok(gen_udp::IpAddr::V6(gen_udp::Ipv6Addr::from_segments(
+                    segments,
+                )))
+            }
+            // Another crate may have enabled IPv6 in the network stack without this feature
+            #[cfg(not(feature = "ipv6"))]
             #[allow(unreachable_patterns, reason = "Conditional compilation")]
-            _ => unreachable!(),
+            _ => Err(UnsupportedAddress),
         }
     }
 }
 
-impl From<gen_udp::IpAddr> for IpAddress {
-    fn from(t: gen_udp::IpAddr) -> Self {
+impl TryFrom<gen_udp::IpAddr> for IpAddress {
+    type Error = UnsupportedAddress;
+
+    fn try_from(t: gen_udp::IpAddr) -> Result<Self, Self::Error> {
         match t {
             gen_udp::IpAddr::V4(ipaddr) => {
                 let gen_udp::Ipv4Addr { a, b, c, d } = ipaddr;
-                Self::v4(a, b, c, d)
+                Ok(Self::v4(a, b, c, d))
             }
-            // #[cfg(feature = "ipv6")]
-            // udp::IpAddr::V6(ipaddr) => {
-            //     let udp::Ipv6Addr { a, b, c, d, e, f, g, h } = ipaddr;
-            //     Self::v6(a, b, c, d, e, f, g, h)
-            // },
-            #[allow(unreachable_patterns, reason = "Conditional compilation")]
-            _ => unreachable!(),
+            #[cfg(feature = "ipv6")]
+            gen_udp::IpAddr::V6(ipaddr) => {
+                let gen_udp::Ipv6Addr {
+                    a,
+                    b,
+                    c,
+                    d,
+                    e,
+                    f,
+                    g,
+                    h,
+                } = ipaddr;
+                Ok(Self::v6(a, b, c, d, e, f, g, h))
+            }
+            #[cfg(not(feature = "ipv6"))]
+            gen_udp::IpAddr::V6(_) => Err(UnsupportedAddress),
         }
     }
 }
 
-impl From<UdpMetadata> for gen_udp::UdpMetadata {
-    fn from(t: UdpMetadata) -> Self {
+impl TryFrom<UdpMetadata> for gen_udp::UdpMetadata {
+    type Error = UnsupportedAddress;
+
+    fn try_from(t: UdpMetadata) -> Result<Self, Self::Error> {
         let UdpMetadata {
             endpoint,
             local_address,
             meta: _,
         } = t;
-        let e_addr = endpoint.addr.into();
+        let e_addr = endpoint.addr.try_into()?;
         let e_port = endpoint.port;
 
-        Self {
+        Ok(Self {
             endpoint: gen_udp::Endpoint {
                 addr: e_addr,
                 port: e_port,
             },
-            local_addr: local_address.map(gen_udp::IpAddr::from),
-        }
+            local_addr: local_address.map(gen_udp::IpAddr::try_from).transpose()?,
+        })
     }
 }
 
-impl From<gen_udp::UdpMetadata> for UdpMetadata {
-    fn from(t: gen_udp::UdpMetadata) -> Self {
+impl TryFrom<gen_udp::UdpMetadata> for UdpMetadata {
+    type Error = UnsupportedAddress;
+
+    fn try_from(t: gen_udp::UdpMetadata) -> Result<Self, Self::Error> {
         let gen_udp::UdpMetadata {
             endpoint,
-            local_addr: _,
+            local_addr,
         } = t;
-        let e_addr: IpAddress = endpoint.addr.into();
+        let e_addr = IpAddress::try_from(endpoint.addr)?;
         let e_port = endpoint.port;
 
-        (e_addr, e_port).into()
+        // Keeping the local address lets a capsule answer from the address a datagram arrived on,
+        // which matters when a node holds both a link-local and a global IPv6 address.
+        let local_address = local_addr.map(IpAddress::try_from).transpose()?;
+
+        Ok(Self {
+            endpoint: (e_addr, e_port).into(),
+            local_address,
+            meta: Default::default(),
+        })
     }
 }
 
-impl HostUdpSocket for ArielUDPHost {
-    fn bind(&mut self, port: u16) -> Result<(), ()> {
-        match self.socket.as_mut() {
-            Some(socket) => socket.bind(port).map_err(|_| ()),
-            None => {
-                info!("Unintialized Socket");
-                Err(())
-            }
+impl From<UnsupportedAddress> for UdpError {
+    fn from(_: UnsupportedAddress) -> Self {
+        UdpError::UnsupportedAddress
+    }
+}
+
+impl From<wasmtime::component::ResourceTableError> for UdpError {
+    fn from(_: wasmtime::component::ResourceTableError) -> Self {
+        UdpError::InvalidSocket
+    }
+}
+
+impl From<BindError> for UdpError {
+    fn from(value: BindError) -> Self {
+        match value {
+            BindError::InvalidState => UdpError::InvalidState,
+            BindError::NoRoute => UdpError::NoRoute,
         }
     }
+}
 
-    fn send(&mut self, data: Vec<u8>, endpoint: gen_udp::UdpMetadata) -> Result<(), ()> {
-        match self.socket.as_ref() {
-            Some(socket) => {
-                let endpoint = UdpMetadata::from(endpoint);
-                info!("Sending some data to {:?}", endpoint);
-                block_on(socket.send_to(&data, endpoint)).map_err(|_| ())
-            }
-            None => {
-                info!("Unintialized Socket");
-                Err(())
+impl From<SendError> for UdpError {
+    fn from(value: SendError) -> Self {
+        match value {
+            SendError::NoRoute => UdpError::NoRoute,
+            SendError::SocketNotBound => UdpError::SocketNotBound,
+            SendError::PacketTooLarge => UdpError::PacketTooLarge,
+        }
+    }
+}
+
+impl From<RecvError> for UdpError {
+    fn from(value: RecvError) -> Self {
+        match value {
+            RecvError::Truncated => UdpError::Truncated,
+        }
+    }
+}
+
+impl HostUdpSocket for ArielUDPHost {
+    fn open(&mut self) -> Result<Resource<UdpSocketHandle>, UdpError> {
+        let pool = self.pool.ok_or(UdpError::NotInitialized)?;
+        let handle = pool.open_socket()?;
+        // On failure, the handle is dropped and gives its buffers back
+        self.table.push(handle).map_err(|_| UdpError::PoolExhausted)
+    }
+
+    fn bind(&mut self, socket: Resource<UdpSocketHandle>, port: u16) -> Result<(), UdpError> {
+        let handle = self.table.get_mut(&socket)?;
+        Ok(handle.socket.bind(port)?)
+    }
+
+    async fn send(
+        &mut self,
+        socket: Resource<UdpSocketHandle>,
+        data: Vec<u8>,
+        endpoint: gen_udp::UdpMetadata,
+    ) -> Result<(), UdpError> {
+        let handle = self.table.get_mut(&socket)?;
+        let endpoint = UdpMetadata::try_from(endpoint)?;
+        info!("Sending some data to {:?}", endpoint);
+        Ok(handle.send_to(&data, endpoint).await?)
+    }
+
+    fn try_recv(
+        &mut self,
+        socket: Resource<UdpSocketHandle>,
+    ) -> Result<Option<(Vec<u8>, gen_udp::UdpMetadata)>, UdpError> {
+        let handle = self.table.get_mut(&socket)?;
+        let mut buf = handle.new_buffer();
+        match poll_once(handle.recv_from(&mut buf)) {
+            Poll::Pending => Ok(None),
+            Poll::Ready(received) => {
+                let (n, endpoint) = received?;
+                info!("Received some data from {:?}", endpoint);
+                buf.truncate(n);
+                Ok(Some((buf, endpoint.try_into()?)))
             }
         }
     }
 
-    fn try_recv(&mut self) -> Result<Option<(Vec<u8>, gen_udp::UdpMetadata)>, ()> {
-        match self.socket.as_ref() {
-            Some(socket) => {
-                if !socket.may_recv() {
-                    return Ok(None);
-                }
-                let mut buf: Vec<u8> = core::iter::repeat_n(0, self.buffer_size).collect();
-                match block_on(socket.recv_from(&mut buf)) {
-                    Err(_) => Err(()),
-                    Ok((0, _)) => Ok(None),
-                    Ok((n, endpoint)) => {
-                        info!("Received some data from {:?}", endpoint);
-                        buf.truncate(n);
-                        Ok(Some((buf, endpoint.into())))
-                    }
+    async fn recv(
+        &mut self,
+        socket: Resource<UdpSocketHandle>,
+        timeout_millis: Option<u64>,
+    ) -> Result<Option<(Vec<u8>, gen_udp::UdpMetadata)>, UdpError> {
+        let handle = self.table.get_mut(&socket)?;
+        let mut buf = handle.new_buffer();
+        let received = match timeout_millis {
+            Some(millis) => {
+                match select(handle.recv_from(&mut buf), Timer::after_millis(millis)).await {
+                    Either::First(received) => received,
+                    Either::Second(()) => return Ok(None),
                 }
             }
-            None => {
-                info!("Unintialized Socket");
-                Err(())
-            }
-        }
+            None => handle.recv_from(&mut buf).await,
+        };
+        let (n, endpoint) = received?;
+        info!("Received some data from {:?}", endpoint);
+        buf.truncate(n);
+        Ok(Some((buf, endpoint.try_into()?)))
     }
 
-    fn drop(&mut self, _: Resource<gen_udp::UdpSocket>) -> wasmtime::Result<()> {
-        unreachable!()
+    fn drop(&mut self, socket: Resource<UdpSocketHandle>) -> wasmtime::Result<()> {
+        // Dropping the handle gives its buffers back to the pool
+        self.table.delete(socket)?;
+        Ok(())
     }
 }
 
@@ -210,50 +486,49 @@ impl Host for ArielUDPHost {}
 impl Host for ArielOSHost {}
 
 impl HostUdpSocket for ArielOSHost {
-    fn bind(&mut self, port: u16) -> Result<(), ()> {
-        self.udp_host.bind(port)
+    fn open(&mut self) -> Result<Resource<UdpSocketHandle>, UdpError> {
+        self.udp_host.open()
     }
 
-    fn send(
+    fn bind(&mut self, socket: Resource<UdpSocketHandle>, port: u16) -> Result<(), UdpError> {
+        self.udp_host.bind(socket, port)
+    }
+
+    async fn send(
         &mut self,
-        data: wasmtime::component::__internal::Vec<u8>,
+        socket: Resource<UdpSocketHandle>,
+        data: Vec<u8>,
         endpoint: gen_udp::UdpMetadata,
-    ) -> Result<(), ()> {
-        self.udp_host.send(data, endpoint)
+    ) -> Result<(), UdpError> {
+        self.udp_host.send(socket, data, endpoint).await
     }
 
     fn try_recv(
         &mut self,
-    ) -> Result<
-        Option<(
-            wasmtime::component::__internal::Vec<u8>,
-            gen_udp::UdpMetadata,
-        )>,
-        (),
-    > {
-        self.udp_host.try_recv()
+        socket: Resource<UdpSocketHandle>,
+    ) -> Result<Option<(Vec<u8>, gen_udp::UdpMetadata)>, UdpError> {
+        self.udp_host.try_recv(socket)
     }
 
-    fn drop(
+    async fn recv(
         &mut self,
-        rep: wasmtime::component::Resource<gen_udp::UdpSocket>,
-    ) -> wasmtime::Result<()> {
-        self.udp_host.drop(rep)
+        socket: Resource<UdpSocketHandle>,
+        timeout_millis: Option<u64>,
+    ) -> Result<Option<(Vec<u8>, gen_udp::UdpMetadata)>, UdpError> {
+        self.udp_host.recv(socket, timeout_millis).await
+    }
+
+    fn drop(&mut self, socket: Resource<UdpSocketHandle>) -> wasmtime::Result<()> {
+        self.udp_host.drop(socket)
     }
 }
 
 impl ArielOSHost {
-    pub unsafe fn initialize_socket(
-        &mut self,
-        stack: ariel_os_embassy::NetworkStack,
-        rx_meta: &mut [ariel_os_embassy::reexports::embassy_net::udp::PacketMetadata],
-        rx_buffer: &mut [u8],
-        tx_meta: &mut [ariel_os_embassy::reexports::embassy_net::udp::PacketMetadata],
-        tx_buffer: &mut [u8],
-    ) {
-        unsafe {
-            self.udp_host
-                .initialize_socket(stack, rx_meta, rx_buffer, tx_meta, tx_buffer)
-        };
+    /// Lets capsules running on this host open sockets from `pool`.
+    ///
+    /// Every host needs this, including the fresh one built for an updated capsule; the buffers
+    /// stay in the pool when a host is dropped.
+    pub fn set_udp_pool(&mut self, pool: &'static UdpSocketPool) {
+        self.udp_host.set_pool(pool);
     }
 }
@@ -0,0 +1,56 @@
+#![cfg(all(feature = "log", feature = "sensors"))]
+//! Checks that [`CapsuleLinker`] rejects components importing interfaces they were not granted.
+
+mod common;
+
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+
+const IMPORTS_SENSORS: &str = r#"
+(component
+  (import "ariel:wasm-bindings/sensors-api@0.0.1" (instance))
+)
+"#;
+
+const IMPORTS_OTHER_PACKAGE: &str = r#"
+(component
+  (import "example:example/other-api@0.0.1" (instance))
+)
+"#;
+
+#[test]
+fn ungranted_import_is_rejected() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Log)
+        .build(&engine)
+        .unwrap();
+    let error = linker
+        .check(&common::component(&engine, IMPORTS_SENSORS))
+        .unwrap_err();
+    assert_eq!(error.import, "ariel:wasm-bindings/sensors-api@0.0.1");
+}
+
+#[test]
+fn granted_import_is_accepted() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Sensors)
+        .build(&engine)
+        .unwrap();
+    assert!(
+        linker
+            .check(&common::component(&engine, IMPORTS_SENSORS))
+            .is_ok()
+    );
+}
+
+#[test]
+fn other_packages_are_left_to_the_linker() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder().build(&engine).unwrap();
+    assert!(
+        linker
+            .check(&common::component(&engine, IMPORTS_OTHER_PACKAGE))
+            .is_ok()
+    );
+}
@@ -0,0 +1,197 @@
+#![cfg(feature = "coap")]
+//! Uploads capsules to a `Sandbox` and runs them through CoAP requests.
+
+mod common;
+
+use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::coap::sanbdox::Sandbox;
+use ariel_os_bindings::wasm::coap::{CanInstantiate, EphemeralCapsule};
+use coap_handler::Handler;
+use coap_message::error::RenderableOnMinimal;
+use coap_message::{MinimalWritableMessage, ReadableMessage};
+use coap_message_implementations::heap::HeapMessage;
+use coap_numbers::code::{
+    BAD_REQUEST, CONTENT, CREATED, DELETE, DELETED, GET, INTERNAL_SERVER_ERROR, METHOD_NOT_ALLOWED,
+    NOT_FOUND, POST, PUT,
+};
+use wasmtime::component::{Instance, Linker, TypedFunc};
+use wasmtime::{Engine, Store};
+
+/// Answers 42.
+const ANSWER: &str = r#"
+(component
+  (core module $guest
+    (func (export "run") (result i32) (i32.const 42)))
+  (core instance $guest-instance (instantiate $guest))
+  (func (export "run") (result u32) (canon lift (core func $guest-instance "run")))
+)
+"#;
+
+/// Traps when run.
+const TRAP: &str = r#"
+(component
+  (core module $guest
+    (func (export "run") (result i32) unreachable))
+  (core instance $guest-instance (instantiate $guest))
+  (func (export "run") (result u32) (canon lift (core func $guest-instance "run")))
+)
+"#;
+
+/// Starts with 128 KiB of memory, twice what `CapsuleLimits::DEFAULT` allows.
+const GREEDY: &str = r#"
+(component
+  (core module $guest
+    (memory (export "memory") 2)
+    (func (export "run") (result i32) (i32.const 42)))
+  (core instance $guest-instance (instantiate $guest))
+  (func (export "run") (result u32) (canon lift (core func $guest-instance "run")))
+)
+"#;
+
+/// Capsule whose `run` export returns a number.
+struct Runner(TypedFunc<(), (u32,)>);
+
+impl CanInstantiate<ArielOSHost> for Runner {
+    fn link(_: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        Ok(())
+    }
+
+    fn from_instance(
+        store: &mut Store<ArielOSHost>,
+        instance: &Instance,
+    ) -> wasmtime::Result<Self> {
+        Ok(Self(instance.get_typed_func(store, "run")?))
+    }
+}
+
+impl EphemeralCapsule<ArielOSHost, u32> for Runner {
+    fn run(&mut self, store: &mut Store<ArielOSHost>) -> wasmtime::Result<u32> {
+        let (result,) = self.0.call(store, ())?;
+        Ok(result)
+    }
+}
+
+/// Sends a request for `/path` and returns the response code and payload.
+fn request(
+    sandbox: &mut Sandbox<'_, ArielOSHost, u32, Runner>,
+    code: u8,
+    path: &str,
+    payload: &[u8],
+) -> (u8, Vec<u8>) {
+    let mut request = HeapMessage::new();
+    request.set_code(code);
+    request
+        .add_option(coap_numbers::option::URI_PATH, path.as_bytes())
+        .unwrap();
+    request.set_payload(payload).unwrap();
+
+    let mut response = HeapMessage::new();
+    let result = sandbox
+        .extract_request_data(&request)
+        .and_then(|data| sandbox.build_response(&mut response, data));
+    if let Err(error) = result {
+        response = HeapMessage::new();
+        error.render(&mut response).unwrap();
+    }
+    (response.code(), response.payload().to_vec())
+}
+
+/// Returns the component compiled for `engine`, as it would be uploaded.
+fn precompiled(engine: &Engine, wat: &str) -> Vec<u8> {
+    engine.precompile_component(wat.as_bytes()).unwrap()
+}
+
+fn unverified_sandbox(engine: &Engine) -> Sandbox<'_, ArielOSHost, u32, Runner> {
+    // SAFETY: The tests only upload components they compiled themselves.
+    unsafe { Sandbox::new_unverified(engine) }
+}
+
+#[test]
+fn runs_uploaded_capsules() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+
+    let (code, _) = request(&mut sandbox, PUT, "answer", &precompiled(&engine, ANSWER));
+    assert_eq!(code, CREATED);
+    assert_eq!(
+        request(&mut sandbox, GET, "answer", &[]),
+        (CONTENT, b"42".to_vec())
+    );
+    // Capsules can be run again
+    assert_eq!(
+        request(&mut sandbox, GET, "answer", &[]),
+        (CONTENT, b"42".to_vec())
+    );
+}
+
+#[test]
+fn deleted_capsules_are_gone() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+
+    request(&mut sandbox, PUT, "answer", &precompiled(&engine, ANSWER));
+    assert_eq!(request(&mut sandbox, DELETE, "answer", &[]).0, DELETED);
+    assert_eq!(request(&mut sandbox, GET, "answer", &[]).0, NOT_FOUND);
+}
+
+#[test]
+fn refuses_uploads_that_are_not_components() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+
+    assert_eq!(
+        request(&mut sandbox, PUT, "garbage", b"not a component").0,
+        BAD_REQUEST
+    );
+    assert_eq!(request(&mut sandbox, GET, "garbage", &[]).0, NOT_FOUND);
+}
+
+#[test]
+fn reports_traps_as_server_errors() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+
+    request(&mut sandbox, PUT, "trap", &precompiled(&engine, TRAP));
+    assert_eq!(
+        request(&mut sandbox, GET, "trap", &[]).0,
+        INTERNAL_SERVER_ERROR
+    );
+}
+
+#[test]
+fn limits_capsules_only_with_a_limiter() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+    request(&mut sandbox, PUT, "greedy", &precompiled(&engine, GREEDY));
+    assert_eq!(
+        request(&mut sandbox, GET, "greedy", &[]),
+        (CONTENT, b"42".to_vec())
+    );
+
+    let mut sandbox = unverified_sandbox(&engine).with_limiter();
+    request(&mut sandbox, PUT, "greedy", &precompiled(&engine, GREEDY));
+    assert_eq!(request(&mut sandbox, GET, "greedy", &[]).0, NOT_FOUND);
+}
+
+#[test]
+fn refuses_other_methods() {
+    let engine = common::engine();
+    let mut sandbox = unverified_sandbox(&engine);
+
+    assert_eq!(
+        request(&mut sandbox, POST, "answer", &[]).0,
+        METHOD_NOT_ALLOWED
+    );
+}
+
+#[test]
+#[cfg(feature = "signed")]
+fn verified_sandbox_refuses_unsigned_capsules() {
+    let engine = common::engine();
+    let mut sandbox = Sandbox::<'_, ArielOSHost, u32, Runner>::new(&engine);
+
+    assert_eq!(
+        request(&mut sandbox, PUT, "answer", &precompiled(&engine, ANSWER)).0,
+        coap_numbers::code::FORBIDDEN
+    );
+}
@@ -0,0 +1,31 @@
+//! Harness running small components against the host interfaces on a plain `std` target.
+//!
+//! Components are written in the WebAssembly text format so no wasm toolchain is needed. Run with
+//! the features of the interfaces under test, e.g.
+//! `cargo test -p ariel-os-bindings --features log,sensors`.
+#![allow(dead_code, reason = "Not every test uses every helper")]
+
+use ariel_os_bindings::wasm::ArielOSHost;
+use wasmtime::component::{Component, Instance, Linker};
+use wasmtime::{Engine, Store};
+
+/// Engine compiling for the machine running the tests, rather than for pulley.
+pub fn engine() -> Engine {
+    Engine::default()
+}
+
+pub fn component(engine: &Engine, wat: &str) -> Component {
+    Component::new(engine, wat).expect("invalid test component")
+}
+
+/// Instantiates the component with a fresh host.
+///
+/// Uses the async API so this works regardless of which host functions are async.
+pub fn instantiate(linker: &Linker<ArielOSHost>, wat: &str) -> (Store<ArielOSHost>, Instance) {
+    let engine = linker.engine();
+    let component = component(engine, wat);
+    let mut store = Store::new(engine, ArielOSHost::default());
+    let instance = embassy_futures::block_on(linker.instantiate_async(&mut store, &component))
+        .expect("instantiation failed");
+    (store, instance)
+}
@@ -0,0 +1,318 @@
+#![cfg(feature = "gpio")]
+//! Drives virtual pins from a component through the `gpio-api` resources.
+
+mod common;
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+use std::time::Duration;
+
+use ariel_os_bindings::wasm::gpio::GpioInput;
+use ariel_os_bindings::wasm::{ArielOSHost, Capability, CapsuleLinker};
+use embedded_hal::digital::{ErrorKind, ErrorType, OutputPin, StatefulOutputPin};
+use wasmtime::component::{ComponentType, Lift};
+
+/// Output pin whose level can be checked from the test.
+struct VirtualOutput(Arc<AtomicBool>);
+
+impl ErrorType for VirtualOutput {
+    type Error = Infallible;
+}
+
+impl OutputPin for VirtualOutput {
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for VirtualOutput {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.load(Ordering::SeqCst))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.0.load(Ordering::SeqCst))
+    }
+}
+
+/// Output pin whose driver fails on every access.
+struct BrokenOutput;
+
+impl ErrorType for BrokenOutput {
+    type Error = ErrorKind;
+}
+
+impl OutputPin for BrokenOutput {
+    fn set_high(&mut self) -> Result<(), ErrorKind> {
+        Err(ErrorKind::Other)
+    }
+
+    fn set_low(&mut self) -> Result<(), ErrorKind> {
+        Err(ErrorKind::Other)
+    }
+}
+
+impl StatefulOutputPin for BrokenOutput {
+    fn is_set_high(&mut self) -> Result<bool, ErrorKind> {
+        Err(ErrorKind::Other)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, ErrorKind> {
+        Err(ErrorKind::Other)
+    }
+}
+
+/// Input pin driven from the test, possibly from another thread.
+struct VirtualInput(Arc<AtomicBool>);
+
+impl ErrorType for VirtualInput {
+    type Error = Infallible;
+}
+
+/// Resolves once `level` is `high`.
+fn wait_for(
+    level: Arc<AtomicBool>,
+    high: bool,
+) -> impl Future<Output = Result<(), Infallible>> + Send {
+    std::future::poll_fn(move |cx| {
+        if level.load(Ordering::SeqCst) == high {
+            Poll::Ready(Ok(()))
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+}
+
+impl GpioInput for VirtualInput {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.load(Ordering::SeqCst))
+    }
+
+    fn wait_for_high(&mut self) -> impl Future<Output = Result<(), Infallible>> + Send {
+        wait_for(self.0.clone(), true)
+    }
+
+    fn wait_for_low(&mut self) -> impl Future<Output = Result<(), Infallible>> + Send {
+        wait_for(self.0.clone(), false)
+    }
+
+    fn wait_for_rising_edge(&mut self) -> impl Future<Output = Result<(), Infallible>> + Send {
+        let low = wait_for(self.0.clone(), false);
+        let high = wait_for(self.0.clone(), true);
+        async move {
+            low.await?;
+            high.await
+        }
+    }
+
+    fn wait_for_falling_edge(&mut self) -> impl Future<Output = Result<(), Infallible>> + Send {
+        let high = wait_for(self.0.clone(), true);
+        let low = wait_for(self.0.clone(), false);
+        async move {
+            high.await?;
+            low.await
+        }
+    }
+
+    fn wait_for_any_edge(&mut self) -> impl Future<Output = Result<(), Infallible>> + Send {
+        let other = !self.0.load(Ordering::SeqCst);
+        wait_for(self.0.clone(), other)
+    }
+}
+
+/// Toggles the output named `led`, waits for the input named `button` to go high, and checks
+/// that an output named `missing` is reported as not found.
+///
+/// Functions returning a `result` write it to address 40: the discriminant, then the error or
+/// the `bool`. Unexpected errors trap.
+const BLINKY: &str = r#"
+(component
+  (type $gpio-error (enum "not-found" "hardware"))
+  (import "ariel:wasm-bindings/gpio-api@0.0.1" (instance $gpio
+    (export "gpio-error" (type $error (eq $gpio-error)))
+    (export "output" (type $output (sub resource)))
+    (export "input" (type $input (sub resource)))
+    (export "[static]output.open"
+      (func (param "name" string) (result (result (own $output) (error $error)))))
+    (export "[method]output.toggle"
+      (func (param "self" (borrow $output)) (result (result (error $error)))))
+    (export "[method]output.is-high"
+      (func (param "self" (borrow $output)) (result (result bool (error $error)))))
+    (export "[static]input.open"
+      (func (param "name" string) (result (result (own $input) (error $error)))))
+    (export "[method]input.is-high"
+      (func (param "self" (borrow $input)) (result (result bool (error $error)))))
+    (export "[method]input.wait-for-high"
+      (func (param "self" (borrow $input)) (result (result (error $error)))))
+  ))
+
+  (core module $memory-module
+    (memory (export "memory") 1)
+    (data (i32.const 16) "led")
+    (data (i32.const 20) "button")
+    (data (i32.const 48) "missing")
+  )
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+
+  (core func $open-output (canon lower (func $gpio "[static]output.open") (memory $memory)))
+  (core func $toggle (canon lower (func $gpio "[method]output.toggle") (memory $memory)))
+  (core func $output-is-high
+    (canon lower (func $gpio "[method]output.is-high") (memory $memory)))
+  (core func $open-input (canon lower (func $gpio "[static]input.open") (memory $memory)))
+  (core func $input-is-high (canon lower (func $gpio "[method]input.is-high") (memory $memory)))
+  (core func $wait-for-high
+    (canon lower (func $gpio "[method]input.wait-for-high") (memory $memory)))
+
+  (core module $guest
+    (import "host" "open-output" (func $open-output (param i32 i32 i32)))
+    (import "host" "toggle" (func $toggle (param i32 i32)))
+    (import "host" "output-is-high" (func $output-is-high (param i32 i32)))
+    (import "host" "open-input" (func $open-input (param i32 i32 i32)))
+    (import "host" "input-is-high" (func $input-is-high (param i32 i32)))
+    (import "host" "wait-for-high" (func $wait-for-high (param i32 i32)))
+    (import "host" "memory" (memory 1))
+
+    ;; Results of open are written to address 32, with the handle at 36
+    (func $handle (result i32)
+      (if (i32.load8_u (i32.const 32)) (then unreachable))
+      (i32.load (i32.const 36)))
+
+    (func $ok-value (result i32)
+      (if (i32.load8_u (i32.const 40)) (then unreachable))
+      (i32.load8_u (i32.const 41)))
+
+    (func (export "toggle-led") (result i32)
+      (local $led i32)
+      (call $open-output (i32.const 16) (i32.const 3) (i32.const 32))
+      (local.set $led (call $handle))
+      (call $toggle (local.get $led) (i32.const 40))
+      (drop (call $ok-value))
+      (call $output-is-high (local.get $led) (i32.const 40))
+      (call $ok-value))
+
+    ;; 0 if toggling worked, 1 + the error otherwise
+    (func (export "toggle-error") (result i32)
+      (call $open-output (i32.const 16) (i32.const 3) (i32.const 32))
+      (call $toggle (call $handle) (i32.const 40))
+      (if (result i32) (i32.load8_u (i32.const 40))
+        (then (i32.add (i32.const 1) (i32.load8_u (i32.const 41))))
+        (else (i32.const 0))))
+
+    (func (export "wait-for-button") (result i32)
+      (local $button i32)
+      (call $open-input (i32.const 20) (i32.const 6) (i32.const 32))
+      (local.set $button (call $handle))
+      (call $wait-for-high (local.get $button) (i32.const 40))
+      (drop (call $ok-value))
+      (call $input-is-high (local.get $button) (i32.const 40))
+      (call $ok-value))
+
+    (func (export "open-missing") (result i32)
+      (call $open-output (i32.const 48) (i32.const 7) (i32.const 32))
+      (i32.load8_u (i32.const 32)))
+  )
+  (core instance $host
+    (export "open-output" (func $open-output))
+    (export "toggle" (func $toggle))
+    (export "output-is-high" (func $output-is-high))
+    (export "open-input" (func $open-input))
+    (export "input-is-high" (func $input-is-high))
+    (export "wait-for-high" (func $wait-for-high))
+    (export "memory" (memory $memory)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "toggle-led") (result bool)
+    (canon lift (core func $guest-instance "toggle-led")))
+  (func (export "toggle-error") (result u32)
+    (canon lift (core func $guest-instance "toggle-error")))
+  (func (export "wait-for-button") (result bool)
+    (canon lift (core func $guest-instance "wait-for-button")))
+  ;; Whether opening failed
+  (func (export "open-missing") (result bool)
+    (canon lift (core func $guest-instance "open-missing")))
+)
+"#;
+
+fn call<R: ComponentType + Lift + Send + Sync + 'static>(
+    linker: &CapsuleLinker,
+    bind: impl FnOnce(&mut ArielOSHost),
+    name: &str,
+) -> R {
+    let (mut store, instance) = common::instantiate(linker.linker(), BLINKY);
+    bind(store.data_mut());
+    let func = instance
+        .get_typed_func::<(), (R,)>(&mut store, name)
+        .unwrap();
+    let (result,) = embassy_futures::block_on(func.call_async(&mut store, ())).unwrap();
+    result
+}
+
+fn linker() -> CapsuleLinker {
+    CapsuleLinker::builder()
+        .grant(Capability::Gpio)
+        .build(&common::engine())
+        .unwrap()
+}
+
+#[test]
+fn toggles_a_bound_output() {
+    let led = Arc::new(AtomicBool::new(false));
+    let is_high: bool = call(
+        &linker(),
+        |host| host.bind_output("led", VirtualOutput(led.clone())),
+        "toggle-led",
+    );
+    assert!(is_high);
+    assert!(led.load(Ordering::SeqCst));
+}
+
+#[test]
+fn waits_for_a_bound_input() {
+    let button = Arc::new(AtomicBool::new(false));
+    let presser = {
+        let button = button.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            button.store(true, Ordering::SeqCst);
+        })
+    };
+    let is_high: bool = call(
+        &linker(),
+        |host| host.bind_input("button", VirtualInput(button.clone())),
+        "wait-for-button",
+    );
+    presser.join().unwrap();
+    assert!(is_high);
+}
+
+#[test]
+fn reports_unbound_pins_as_not_found() {
+    let failed: bool = call(
+        &linker(),
+        |host| host.bind_output("led", VirtualOutput(Arc::default())),
+        "open-missing",
+    );
+    assert!(failed);
+}
+
+#[test]
+fn reports_pin_failures_as_hardware_errors() {
+    let error: u32 = call(
+        &linker(),
+        |host| host.bind_output("led", BrokenOutput),
+        "toggle-error",
+    );
+    // 1 + the discriminant of `hardware`
+    assert_eq!(error, 2);
+}
@@ -0,0 +1,66 @@
+//! Checks that hosts are bounded by default once their limiter is installed.
+
+mod common;
+
+use ariel_os_bindings::wasm::{ArielOSHost, CapsuleLimits};
+use wasmtime::Store;
+use wasmtime::component::{Instance, Linker};
+
+/// Grows its memory, which starts at the default cap of 64 KiB, by the given number of pages.
+const GROWER: &str = r#"
+(component
+  (core module $guest
+    (memory 1)
+    (func (export "grow") (param i32) (result i32)
+      (memory.grow (local.get 0)))
+  )
+  (core instance $guest-instance (instantiate $guest))
+  (func (export "grow") (param "pages" u32) (result s32)
+    (canon lift (core func $guest-instance "grow")))
+)
+"#;
+
+/// Starts with more memory than the default cap.
+const OVERSIZED: &str = r#"
+(component
+  (core module $guest
+    (memory 2)
+  )
+  (core instance $guest-instance (instantiate $guest))
+)
+"#;
+
+/// Instantiates the component with the limiter of `host` installed.
+fn instantiate(wat: &str, host: ArielOSHost) -> wasmtime::Result<(Store<ArielOSHost>, Instance)> {
+    let engine = common::engine();
+    let component = common::component(&engine, wat);
+    let mut store = Store::new(&engine, host);
+    store.limiter(|host| host);
+    let instance = Linker::new(&engine).instantiate(&mut store, &component)?;
+    Ok((store, instance))
+}
+
+/// Grows the memory by a page, returning the previous number of pages or -1 on failure.
+fn grow(store: &mut Store<ArielOSHost>, instance: &Instance) -> i32 {
+    let grow = instance
+        .get_typed_func::<(u32,), (i32,)>(&mut *store, "grow")
+        .unwrap();
+    grow.call(store, (1,)).unwrap().0
+}
+
+#[test]
+fn default_is_bounded() {
+    assert_eq!(CapsuleLimits::default(), CapsuleLimits::DEFAULT);
+
+    let (mut store, instance) = instantiate(GROWER, ArielOSHost::default()).unwrap();
+    assert_eq!(grow(&mut store, &instance), -1);
+
+    assert!(instantiate(OVERSIZED, ArielOSHost::default()).is_err());
+}
+
+#[test]
+fn unlimited_host_may_grow() {
+    let host = ArielOSHost::with_limits(CapsuleLimits::UNLIMITED);
+    let (mut store, instance) = instantiate(GROWER, host).unwrap();
+    assert_eq!(grow(&mut store, &instance), 1);
+}
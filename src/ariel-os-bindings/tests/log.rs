@@ -0,0 +1,48 @@
+#![cfg(feature = "log")]
+
+mod common;
+
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+
+/// Logs a string stored in its data segment.
+const LOGGER: &str = r#"
+(component
+  (import "ariel:wasm-bindings/log-api@0.0.1" (instance $log
+    (export "info" (func (param "input" string)))
+  ))
+
+  (core module $memory-module
+    (memory (export "memory") 1)
+    (data (i32.const 16) "hello from wasm")
+  )
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+
+  (core func $info (canon lower (func $log "info") (memory $memory)))
+
+  (core module $guest
+    (import "host" "info" (func $info (param i32 i32)))
+    (func (export "run")
+      (call $info (i32.const 16) (i32.const 15)))
+  )
+  (core instance $host (export "info" (func $info)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "run") (canon lift (core func $guest-instance "run")))
+)
+"#;
+
+#[test]
+fn info_accepts_guest_strings() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Log)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), LOGGER);
+
+    let run = instance
+        .get_typed_func::<(), ()>(&mut store, "run")
+        .unwrap();
+    embassy_futures::block_on(run.call_async(&mut store, ())).unwrap();
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "pwm")]
+//! Drives a fake embedded-hal PWM channel from a component.
+
+mod common;
+
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use ariel_os_bindings::wasm::pwm::PwmError;
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+
+/// Channel recording the last duty cycle it was set to.
+struct FakeChannel(Arc<Mutex<u16>>);
+
+impl ErrorType for FakeChannel {
+    type Error = Infallible;
+}
+
+impl SetDutyCycle for FakeChannel {
+    fn max_duty_cycle(&self) -> u16 {
+        1000
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+        *self.0.lock().unwrap() = duty;
+        Ok(())
+    }
+}
+
+/// Opens the channel named `led`, then sets its duty cycle and frequency to the given values.
+const DIMMER: &str = r#"
+(component
+  (type $pwm-error (enum "not-found" "invalid-duty-cycle" "unsupported-frequency" "other"))
+  (import "ariel:wasm-bindings/pwm-api@0.0.1" (instance $pwm
+    (export "pwm-error" (type $error (eq $pwm-error)))
+    (export "pwm-channel" (type $channel (sub resource)))
+    (export "[static]pwm-channel.open"
+      (func (param "name" string) (result (result (own $channel) (error $error)))))
+    (export "[method]pwm-channel.set-duty-cycle"
+      (func (param "self" (borrow $channel)) (param "numerator" u16) (param "denominator" u16)
+        (result (result (error $error)))))
+    (export "[method]pwm-channel.set-frequency"
+      (func (param "self" (borrow $channel)) (param "hertz" u32)
+        (result (result (error $error)))))
+  ))
+  (alias export $pwm "pwm-error" (type $error))
+
+  (core module $memory-module
+    (memory (export "memory") 1)
+    (data (i32.const 16) "led")
+  )
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+
+  (core func $open (canon lower (func $pwm "[static]pwm-channel.open") (memory $memory)))
+  (core func $set-duty-cycle
+    (canon lower (func $pwm "[method]pwm-channel.set-duty-cycle") (memory $memory)))
+  (core func $set-frequency
+    (canon lower (func $pwm "[method]pwm-channel.set-frequency") (memory $memory)))
+
+  (core module $guest
+    (import "host" "memory" (memory 1))
+    (import "host" "open" (func $open (param i32 i32 i32)))
+    (import "host" "set-duty-cycle" (func $set-duty-cycle (param i32 i32 i32 i32)))
+    (import "host" "set-frequency" (func $set-frequency (param i32 i32 i32)))
+
+    ;; Results are written to and returned through address 32; the channel handle is at 36
+    (func (export "set-duty-cycle") (param i32 i32) (result i32)
+      (call $open (i32.const 16) (i32.const 3) (i32.const 32))
+      (call $set-duty-cycle (i32.load (i32.const 36)) (local.get 0) (local.get 1) (i32.const 32))
+      (i32.const 32))
+
+    (func (export "set-frequency") (param i32) (result i32)
+      (call $open (i32.const 16) (i32.const 3) (i32.const 32))
+      (call $set-frequency (i32.load (i32.const 36)) (local.get 0) (i32.const 32))
+      (i32.const 32))
+  )
+  (core instance $host
+    (export "memory" (memory $memory))
+    (export "open" (func $open))
+    (export "set-duty-cycle" (func $set-duty-cycle))
+    (export "set-frequency" (func $set-frequency)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "set-duty-cycle")
+    (param "numerator" u16) (param "denominator" u16) (result (result (error $error)))
+    (canon lift (core func $guest-instance "set-duty-cycle") (memory $memory)))
+  (func (export "set-frequency") (param "hertz" u32) (result (result (error $error)))
+    (canon lift (core func $guest-instance "set-frequency") (memory $memory)))
+)
+"#;
+
+#[test]
+fn scales_the_duty_cycle_fraction() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Pwm)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), DIMMER);
+    let duty = Arc::new(Mutex::new(0));
+    store
+        .data_mut()
+        .bind_pwm_channel("led", FakeChannel(duty.clone()));
+
+    let set_duty_cycle = instance
+        .get_typed_func::<(u16, u16), (Result<(), PwmError>,)>(&mut store, "set-duty-cycle")
+        .unwrap();
+    let (result,) =
+        embassy_futures::block_on(set_duty_cycle.call_async(&mut store, (1, 4))).unwrap();
+    assert_eq!(result, Ok(()));
+    assert_eq!(*duty.lock().unwrap(), 250);
+}
+
+#[test]
+fn refuses_frequency_changes_without_a_setter() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Pwm)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), DIMMER);
+    store
+        .data_mut()
+        .bind_pwm_channel("led", FakeChannel(Arc::default()));
+
+    let set_frequency = instance
+        .get_typed_func::<(u32,), (Result<(), PwmError>,)>(&mut store, "set-frequency")
+        .unwrap();
+    let (result,) =
+        embassy_futures::block_on(set_frequency.call_async(&mut store, (1000,))).unwrap();
+    assert_eq!(result, Err(PwmError::UnsupportedFrequency));
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "rng")]
+//! Draws random numbers from the host RNG through a component.
+
+mod common;
+
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+
+/// Passes the `rng-api` functions through.
+const DICE: &str = r#"
+(component
+  (import "ariel:wasm-bindings/rng-api@0.0.1" (instance $rng
+    (export "r-n-g" (type (sub resource)))
+    (export "[static]r-n-g.next-u64" (func (result u64)))
+    (export "[static]r-n-g.random-bytes" (func (param "len" u32) (result (list u8))))
+  ))
+
+  (core module $memory-module
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+
+    ;; Bump allocator, the instance is only used for a few calls
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ptr i32)
+      (local.set $ptr (global.get $heap))
+      (global.set $heap (i32.add (global.get $heap) (local.get 3)))
+      (local.get $ptr))
+  )
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+  (alias core export $memory-instance "realloc" (core func $realloc))
+
+  (core func $next-u64 (canon lower (func $rng "[static]r-n-g.next-u64")))
+  (core func $random-bytes
+    (canon lower (func $rng "[static]r-n-g.random-bytes")
+      (memory $memory) (realloc (core func $realloc))))
+
+  (core module $guest
+    (import "host" "next-u64" (func $next-u64 (result i64)))
+    (import "host" "random-bytes" (func $random-bytes (param i32 i32)))
+
+    (func (export "next-u64") (result i64)
+      (call $next-u64))
+
+    ;; The list is returned through address 16
+    (func (export "random-bytes") (param i32) (result i32)
+      (call $random-bytes (local.get 0) (i32.const 16))
+      (i32.const 16))
+  )
+  (core instance $host
+    (export "next-u64" (func $next-u64))
+    (export "random-bytes" (func $random-bytes)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "next-u64") (result u64)
+    (canon lift (core func $guest-instance "next-u64")))
+  (func (export "random-bytes") (param "len" u32) (result (list u8))
+    (canon lift (core func $guest-instance "random-bytes") (memory $memory)))
+)
+"#;
+
+#[test]
+fn draws_different_numbers() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Rng)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), DICE);
+
+    let next_u64 = instance
+        .get_typed_func::<(), (u64,)>(&mut store, "next-u64")
+        .unwrap();
+    let (first,) = embassy_futures::block_on(next_u64.call_async(&mut store, ())).unwrap();
+    let (second,) = embassy_futures::block_on(next_u64.call_async(&mut store, ())).unwrap();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn fills_the_requested_number_of_bytes() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Rng)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), DICE);
+
+    let random_bytes = instance
+        .get_typed_func::<(u32,), (Vec<u8>,)>(&mut store, "random-bytes")
+        .unwrap();
+    let (bytes,) = embassy_futures::block_on(random_bytes.call_async(&mut store, (32,))).unwrap();
+    assert_eq!(bytes.len(), 32);
+    assert!(bytes.iter().any(|byte| *byte != 0));
+}
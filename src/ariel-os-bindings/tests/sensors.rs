@@ -0,0 +1,148 @@
+#![cfg(feature = "sensors")]
+//! Checks that the conversions between the WIT sensor types and `ariel-os-sensors` round-trip.
+
+use ariel_os_bindings::wasm::sensors::{UnknownVariant, comp_sensor};
+use ariel_os_sensors::{Category, Label, MeasurementUnit};
+
+const CATEGORIES: &[comp_sensor::Category] = &[
+    comp_sensor::Category::Accelerometer,
+    comp_sensor::Category::AccelerometerTemperature,
+    comp_sensor::Category::AccelerometerGyroscope,
+    comp_sensor::Category::AccelerometerGyroscopeTemperature,
+    comp_sensor::Category::AccelerometerMagnetometerTemperature,
+    comp_sensor::Category::Ammeter,
+    comp_sensor::Category::Co2Gas,
+    comp_sensor::Category::Color,
+    comp_sensor::Category::Gnss,
+    comp_sensor::Category::Gyroscope,
+    comp_sensor::Category::RelativeHumidity,
+    comp_sensor::Category::RelativeHumidityTemperature,
+    comp_sensor::Category::Light,
+    comp_sensor::Category::Magnetometer,
+    comp_sensor::Category::Ph,
+    comp_sensor::Category::Pressure,
+    comp_sensor::Category::PushButton,
+    comp_sensor::Category::Temperature,
+    comp_sensor::Category::Tvoc,
+    comp_sensor::Category::Voltage,
+];
+
+const LABELS: &[comp_sensor::Label] = &[
+    comp_sensor::Label::AccelerationX,
+    comp_sensor::Label::AccelerationY,
+    comp_sensor::Label::AccelerationZ,
+    comp_sensor::Label::Altitude,
+    comp_sensor::Label::AngularVelocityX,
+    comp_sensor::Label::AngularVelocityY,
+    comp_sensor::Label::AngularVelocityZ,
+    comp_sensor::Label::GroundSpeed,
+    comp_sensor::Label::Latitude,
+    comp_sensor::Label::Longitude,
+    comp_sensor::Label::Opaque,
+    comp_sensor::Label::RelativeHumidity,
+    comp_sensor::Label::Heading,
+    comp_sensor::Label::Temperature,
+    comp_sensor::Label::VerticalSpeed,
+    comp_sensor::Label::X,
+    comp_sensor::Label::Y,
+    comp_sensor::Label::Z,
+];
+
+const UNITS: &[comp_sensor::MeasurementUnit] = &[
+    comp_sensor::MeasurementUnit::AccelG,
+    comp_sensor::MeasurementUnit::Ampere,
+    comp_sensor::MeasurementUnit::Becquerel,
+    comp_sensor::MeasurementUnit::Boolean,
+    comp_sensor::MeasurementUnit::Candela,
+    comp_sensor::MeasurementUnit::Celsius,
+    comp_sensor::MeasurementUnit::Coulomb,
+    comp_sensor::MeasurementUnit::Decibel,
+    comp_sensor::MeasurementUnit::Decimaldegree,
+    comp_sensor::MeasurementUnit::Degree,
+    comp_sensor::MeasurementUnit::DegreePerSecond,
+    comp_sensor::MeasurementUnit::Farad,
+    comp_sensor::MeasurementUnit::Gram,
+    comp_sensor::MeasurementUnit::Gray,
+    comp_sensor::MeasurementUnit::Henry,
+    comp_sensor::MeasurementUnit::Hertz,
+    comp_sensor::MeasurementUnit::Joule,
+    comp_sensor::MeasurementUnit::Katal,
+    comp_sensor::MeasurementUnit::Kelvin,
+    comp_sensor::MeasurementUnit::Lumen,
+    comp_sensor::MeasurementUnit::Lux,
+    comp_sensor::MeasurementUnit::Meter,
+    comp_sensor::MeasurementUnit::MeterPerSecond,
+    comp_sensor::MeasurementUnit::Mole,
+    comp_sensor::MeasurementUnit::Newton,
+    comp_sensor::MeasurementUnit::Ohm,
+    comp_sensor::MeasurementUnit::Pascal,
+    comp_sensor::MeasurementUnit::Percent,
+    comp_sensor::MeasurementUnit::PercentageRelativeHumidity,
+    comp_sensor::MeasurementUnit::Radian,
+    comp_sensor::MeasurementUnit::Second,
+    comp_sensor::MeasurementUnit::Siemens,
+    comp_sensor::MeasurementUnit::Sievert,
+    comp_sensor::MeasurementUnit::Steradian,
+    comp_sensor::MeasurementUnit::Tesla,
+    comp_sensor::MeasurementUnit::Volt,
+    comp_sensor::MeasurementUnit::Watt,
+    comp_sensor::MeasurementUnit::Weber,
+];
+
+#[test]
+fn category_round_trips() {
+    for value in CATEGORIES {
+        let host = Category::try_from(value.clone()).unwrap();
+        assert_eq!(comp_sensor::Category::from(host), *value);
+    }
+}
+
+#[test]
+fn other_category_has_no_host_counterpart() {
+    assert_eq!(
+        Category::try_from(comp_sensor::Category::Other(String::from("Pm25"))),
+        Err(UnknownVariant)
+    );
+}
+
+#[test]
+fn label_round_trips() {
+    for value in LABELS {
+        let host = Label::try_from(value.clone()).unwrap();
+        assert_eq!(comp_sensor::Label::from(host), *value);
+    }
+}
+
+#[test]
+fn other_label_has_no_host_counterpart() {
+    assert_eq!(
+        Label::try_from(comp_sensor::Label::Other(String::from("Pm25"))),
+        Err(UnknownVariant)
+    );
+}
+
+#[test]
+fn measurement_unit_round_trips() {
+    for value in UNITS {
+        let host = MeasurementUnit::try_from(value.clone()).unwrap();
+        assert_eq!(comp_sensor::MeasurementUnit::from(host), *value);
+    }
+}
+
+#[test]
+fn other_measurement_unit_has_no_host_counterpart() {
+    assert_eq!(
+        MeasurementUnit::try_from(comp_sensor::MeasurementUnit::Other(String::from(
+            "PartsPerMillion"
+        ))),
+        Err(UnknownVariant)
+    );
+}
+
+#[test]
+fn relative_humidity_is_not_gyroscope() {
+    assert_eq!(
+        Category::try_from(comp_sensor::Category::RelativeHumidity),
+        Ok(Category::RelativeHumidity)
+    );
+}
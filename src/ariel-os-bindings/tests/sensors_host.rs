@@ -0,0 +1,181 @@
+#![cfg(feature = "sensors")]
+//! Runs components against a registry holding a single fake sensor.
+
+mod common;
+
+use std::sync::Mutex;
+
+use ariel_os_bindings::wasm::sensors::{SensorError, comp_sensor};
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+use ariel_os_sensors::{
+    Category, Label, MeasurementUnit, Sensor,
+    sensor::{
+        Mode, ReadingChannel, ReadingChannels, ReadingError, ReadingWaiter, Samples, SetModeError,
+        State, TriggerMeasurementError,
+    },
+    signal::Signal as ReadingSignal,
+};
+
+/// Sensor whose mode can be changed but that never measures anything.
+struct FakeSensor {
+    state: Mutex<State>,
+    reading: ReadingSignal<Result<Samples, ReadingError>>,
+}
+
+impl Sensor for FakeSensor {
+    fn trigger_measurement(&self) -> Result<(), TriggerMeasurementError> {
+        match *self.state.lock().unwrap() {
+            State::Enabled => Ok(()),
+            _ => Err(TriggerMeasurementError::NonEnabled),
+        }
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Temperature]
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("Fake sensor")
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn reading_channels(&self) -> ReadingChannels {
+        ReadingChannels::from([ReadingChannel::new(
+            Label::Temperature,
+            0,
+            MeasurementUnit::Celsius,
+        )])
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, SetModeError> {
+        let state = match mode {
+            Mode::Disabled => State::Disabled,
+            Mode::Enabled => State::Enabled,
+            Mode::Sleeping => State::Sleeping,
+        };
+        *self.state.lock().unwrap() = state;
+        Ok(state)
+    }
+
+    fn state(&self) -> State {
+        *self.state.lock().unwrap()
+    }
+
+    fn version(&self) -> u8 {
+        0
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.reading.signal(Err(ReadingError::NotMeasuring));
+        ReadingWaiter::new(self.reading.wait())
+    }
+}
+
+static FAKE_SENSOR: FakeSensor = FakeSensor {
+    state: Mutex::new(State::Enabled),
+    reading: ReadingSignal::new(),
+};
+
+#[linkme::distributed_slice(ariel_os_sensors_registry::SENSOR_REFS)]
+static FAKE_SENSOR_REF: &'static dyn Sensor = &FAKE_SENSOR;
+
+/// Forwards `set-mode` and `get-state` to the host.
+const MODE_SWITCHER: &str = r#"
+(component
+  (import "ariel:wasm-bindings/sensors-api@0.0.1" (instance $sensors
+    (type $mode (enum "disabled" "enabled" "sleeping"))
+    (export "mode" (type $mode-t (eq $mode)))
+    (type $state (enum "uninitialized" "disabled" "enabled" "measuring" "sleeping"))
+    (export "state" (type $state-t (eq $state)))
+    (type $error (enum "non-enabled" "sensor-access" "not-measuring" "invalid-handle" "uninitialized"))
+    (export "sensor-error" (type $error-t (eq $error)))
+    (type $result (result $state-t (error $error-t)))
+    (export "set-mode" (func (param "sensor" u32) (param "mode" $mode-t) (result $result)))
+    (export "get-state" (func (param "sensor" u32) (result $result)))
+  ))
+  (alias export $sensors "mode" (type $mode))
+  (alias export $sensors "state" (type $state))
+  (alias export $sensors "sensor-error" (type $error))
+
+  (core module $memory-module (memory (export "memory") 1))
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+
+  (core func $set-mode (canon lower (func $sensors "set-mode") (memory $memory)))
+  (core func $get-state (canon lower (func $sensors "get-state") (memory $memory)))
+
+  ;; Results are written to and lifted from the start of memory
+  (core module $guest
+    (import "host" "set-mode" (func $set-mode (param i32 i32 i32)))
+    (import "host" "get-state" (func $get-state (param i32 i32)))
+    (func (export "set-mode") (param i32 i32) (result i32)
+      (call $set-mode (local.get 0) (local.get 1) (i32.const 0))
+      (i32.const 0))
+    (func (export "get-state") (param i32) (result i32)
+      (call $get-state (local.get 0) (i32.const 0))
+      (i32.const 0))
+  )
+  (core instance $host
+    (export "set-mode" (func $set-mode))
+    (export "get-state" (func $get-state))
+  )
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (type $result (result $state (error $error)))
+  (func (export "set-mode") (param "sensor" u32) (param "mode" $mode) (result $result)
+    (canon lift (core func $guest-instance "set-mode") (memory $memory)))
+  (func (export "get-state") (param "sensor" u32) (result $result)
+    (canon lift (core func $guest-instance "get-state") (memory $memory)))
+)
+"#;
+
+type StateResult = (Result<comp_sensor::State, SensorError>,);
+
+#[test]
+fn mode_changes_are_visible_through_the_state() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Sensors)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), MODE_SWITCHER);
+
+    let set_mode = instance
+        .get_typed_func::<(u32, comp_sensor::Mode), StateResult>(&mut store, "set-mode")
+        .unwrap();
+    let get_state = instance
+        .get_typed_func::<(u32,), StateResult>(&mut store, "get-state")
+        .unwrap();
+
+    let (state,) = embassy_futures::block_on(
+        set_mode.call_async(&mut store, (0, comp_sensor::Mode::Sleeping)),
+    )
+    .unwrap();
+    assert_eq!(state, Ok(comp_sensor::State::Sleeping));
+
+    let (state,) = embassy_futures::block_on(get_state.call_async(&mut store, (0,))).unwrap();
+    assert_eq!(state, Ok(comp_sensor::State::Sleeping));
+}
+
+#[test]
+fn unknown_handle_is_an_error() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Sensors)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), MODE_SWITCHER);
+
+    let get_state = instance
+        .get_typed_func::<(u32,), StateResult>(&mut store, "get-state")
+        .unwrap();
+    let (state,) = embassy_futures::block_on(get_state.call_async(&mut store, (1,))).unwrap();
+    assert_eq!(state, Err(SensorError::InvalidHandle));
+}
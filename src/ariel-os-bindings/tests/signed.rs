@@ -0,0 +1,97 @@
+#![cfg(feature = "signed")]
+//! Checks that only untampered images signed with a trusted key get through.
+
+use ariel_os_bindings::wasm::signed::{FORMAT, HEADER_LEN, MAGIC, TrustedKey, VerifyError, verify};
+use ed25519_dalek::{Signer, SigningKey};
+use sha2::{Digest, Sha256};
+
+const PAYLOAD: &[u8] = b"not really a cwasm, but the verifier does not care";
+
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32])
+}
+
+fn trusted(id: u8, seed: u8) -> TrustedKey {
+    TrustedKey {
+        id,
+        public_key: signing_key(seed).verifying_key().to_bytes(),
+    }
+}
+
+/// Builds an image the way `sign_capsule.rs` does.
+fn sign(key: &SigningKey, key_id: u8, version: u32, payload: &[u8]) -> Vec<u8> {
+    let mut image = vec![0; HEADER_LEN];
+    image[0..4].copy_from_slice(&MAGIC);
+    image[4] = FORMAT;
+    image[5] = key_id;
+    image[8..12].copy_from_slice(&version.to_le_bytes());
+    image[12..16].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    image[16..48].copy_from_slice(&Sha256::digest(payload));
+    let signature = key.sign(&image[..64]);
+    image[64..128].copy_from_slice(&signature.to_bytes());
+    image.extend_from_slice(payload);
+    image
+}
+
+#[test]
+fn accepts_signed_image() {
+    let image = sign(&signing_key(1), 7, 42, PAYLOAD);
+    let capsule = verify(&image, &[trusted(3, 3), trusted(7, 1)]).unwrap();
+    assert_eq!(capsule.key_id(), 7);
+    assert_eq!(capsule.version(), 42);
+    assert_eq!(capsule.payload(), PAYLOAD);
+}
+
+#[test]
+fn rejects_unsigned_image() {
+    assert_eq!(
+        verify(PAYLOAD, &[trusted(7, 1)]).unwrap_err(),
+        VerifyError::TooShort
+    );
+    let mut unsigned = vec![0; HEADER_LEN];
+    unsigned.extend_from_slice(PAYLOAD);
+    assert_eq!(
+        verify(&unsigned, &[trusted(7, 1)]).unwrap_err(),
+        VerifyError::BadMagic
+    );
+}
+
+#[test]
+fn rejects_unknown_key() {
+    let image = sign(&signing_key(1), 7, 42, PAYLOAD);
+    assert_eq!(
+        verify(&image, &[trusted(3, 1)]).unwrap_err(),
+        VerifyError::UnknownKey
+    );
+    // Right id, wrong key
+    assert_eq!(
+        verify(&image, &[trusted(7, 2)]).unwrap_err(),
+        VerifyError::BadSignature
+    );
+}
+
+#[test]
+fn rejects_tampered_header() {
+    let mut image = sign(&signing_key(1), 7, 42, PAYLOAD);
+    // Pretend to be a newer version
+    image[8] = 43;
+    assert_eq!(
+        verify(&image, &[trusted(7, 1)]).unwrap_err(),
+        VerifyError::BadSignature
+    );
+}
+
+#[test]
+fn rejects_tampered_payload() {
+    let mut image = sign(&signing_key(1), 7, 42, PAYLOAD);
+    *image.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        verify(&image, &[trusted(7, 1)]).unwrap_err(),
+        VerifyError::HashMismatch
+    );
+    image.push(0);
+    assert_eq!(
+        verify(&image, &[trusted(7, 1)]).unwrap_err(),
+        VerifyError::LengthMismatch
+    );
+}
@@ -0,0 +1,159 @@
+//! Checks the trial, confirm and rollback steps of the capsule slots.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use ariel_os_bindings::wasm::slots::{CapsuleSlots, SlotError, VersionStore};
+use embassy_futures::block_on;
+
+const BUILT_IN: &[u8] = b"built-in";
+
+/// Version store that outlives the slots, as flash would across a reboot.
+#[derive(Clone, Default)]
+struct Versions {
+    stored: Rc<Cell<Option<u64>>>,
+    broken: bool,
+}
+
+impl VersionStore for Versions {
+    async fn load(&mut self) -> Result<Option<u64>, SlotError> {
+        Ok(self.stored.get())
+    }
+
+    async fn store(&mut self, version: u64) -> Result<(), SlotError> {
+        if self.broken {
+            return Err(SlotError::Storage);
+        }
+        self.stored.set(Some(version));
+        Ok(())
+    }
+}
+
+/// Slots starting out with the built-in capsule, as after a reboot.
+fn boot(versions: &Versions) -> CapsuleSlots<Versions> {
+    block_on(CapsuleSlots::new(1, BUILT_IN, versions.clone())).unwrap()
+}
+
+fn slots() -> CapsuleSlots<Versions> {
+    boot(&Versions::default())
+}
+
+#[test]
+fn runs_active_without_candidate() {
+    let slots = slots();
+    assert!(!slots.in_trial());
+    assert_eq!(slots.current().version(), 1);
+    assert_eq!(slots.current().image(), BUILT_IN);
+}
+
+#[test]
+fn confirmed_candidate_becomes_active() {
+    let mut slots = slots();
+    slots.stage(2, b"update".to_vec()).unwrap();
+    assert!(slots.in_trial());
+    assert_eq!(slots.current().version(), 2);
+    assert_eq!(slots.active().version(), 1);
+
+    assert_eq!(block_on(slots.confirm()), Ok(2));
+    assert!(!slots.in_trial());
+    assert_eq!(slots.current().image(), b"update");
+    assert_eq!(block_on(slots.confirm()), Err(SlotError::NoCandidate));
+}
+
+#[test]
+fn rollback_goes_back_to_active() {
+    let mut slots = slots();
+    slots.stage(2, b"broken".to_vec()).unwrap();
+    assert_eq!(slots.rollback(), Ok(2));
+    assert!(!slots.in_trial());
+    assert_eq!(slots.current().version(), 1);
+    assert_eq!(slots.current().image(), BUILT_IN);
+    assert_eq!(slots.rollback(), Err(SlotError::NoCandidate));
+}
+
+#[test]
+fn rejects_downgrades() {
+    let mut slots = slots();
+    slots.stage(3, b"update".to_vec()).unwrap();
+    block_on(slots.confirm()).unwrap();
+    for version in [1, 3] {
+        assert_eq!(
+            slots.stage(version, b"old".to_vec()),
+            Err(SlotError::Downgrade {
+                minimum: 4,
+                offered: version
+            })
+        );
+    }
+    assert!(!slots.in_trial());
+}
+
+#[test]
+fn rejects_versions_not_newer_than_the_candidate() {
+    let mut slots = slots();
+    slots.stage(3, b"update".to_vec()).unwrap();
+    for version in [2, 3] {
+        assert_eq!(
+            slots.stage(version, b"old".to_vec()),
+            Err(SlotError::Downgrade {
+                minimum: 4,
+                offered: version
+            })
+        );
+    }
+    assert_eq!(slots.current().image(), b"update");
+}
+
+#[test]
+fn newer_candidate_replaces_previous_one() {
+    let mut slots = slots();
+    slots.stage(2, b"first".to_vec()).unwrap();
+    slots.stage(3, b"second".to_vec()).unwrap();
+    assert_eq!(slots.current().version(), 3);
+    assert_eq!(slots.rollback(), Ok(3));
+    assert_eq!(slots.current().version(), 1);
+}
+
+#[test]
+fn confirmed_version_survives_reboots() {
+    let versions = Versions::default();
+    let mut slots = boot(&versions);
+    slots.stage(3, b"update".to_vec()).unwrap();
+    block_on(slots.confirm()).unwrap();
+    assert_eq!(versions.stored.get(), Some(3));
+
+    let mut slots = boot(&versions);
+    assert_eq!(slots.current().version(), 1);
+    assert_eq!(
+        slots.stage(2, b"old".to_vec()),
+        Err(SlotError::Downgrade {
+            minimum: 3,
+            offered: 2
+        })
+    );
+    // The confirmed image itself is gone after the reboot and may be sent again
+    slots.stage(3, b"update".to_vec()).unwrap();
+    block_on(slots.confirm()).unwrap();
+    assert_eq!(versions.stored.get(), Some(3));
+}
+
+#[test]
+fn candidate_stays_on_trial_if_its_version_cannot_be_stored() {
+    let mut slots = boot(&Versions {
+        broken: true,
+        ..Versions::default()
+    });
+    slots.stage(2, b"update".to_vec()).unwrap();
+    assert_eq!(block_on(slots.confirm()), Err(SlotError::Storage));
+    assert!(slots.in_trial());
+    assert_eq!(slots.active().version(), 1);
+}
+
+#[test]
+fn images_stay_in_place_on_confirm() {
+    let mut slots = slots();
+    slots.stage(2, b"update".to_vec()).unwrap();
+    let before = slots.current().image().as_ptr();
+    block_on(slots.confirm()).unwrap();
+    assert_eq!(slots.current().image().as_ptr(), before);
+}
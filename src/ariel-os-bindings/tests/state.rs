@@ -0,0 +1,256 @@
+//! Checks that state exported by one capsule reaches the next, both through `StateTransfer` and
+//! across a `WasmHandler` restart, and that the interface stays optional.
+
+mod common;
+
+use ariel_os_bindings::wasm::state::StateTransfer;
+use wasmtime::component::Linker;
+#[cfg(feature = "coap")]
+use {
+    ariel_os_bindings::wasm::ArielOSHost,
+    ariel_os_bindings::wasm::coap::{CanInstantiate, CoAPError, PersistentCapsule, WasmHandler},
+    std::cell::RefCell,
+    wasmtime::component::Instance,
+    wasmtime::{Config, Engine, Store},
+};
+
+/// Keeps whatever state it is given, and starts out with "fresh".
+const STATEFUL: &str = r#"
+(component
+  (core module $guest
+    (memory (export "memory") 1)
+    (data (i32.const 16) "fresh")
+    (global $heap (mut i32) (i32.const 1024))
+    (global $state (mut i32) (i32.const 16))
+    (global $state-len (mut i32) (i32.const 5))
+
+    ;; Bump allocator, enough for a single call
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ptr i32)
+      (local.set $ptr (global.get $heap))
+      (global.set $heap (i32.add (global.get $heap) (local.get 3)))
+      (local.get $ptr))
+
+    ;; The list is returned through the start of memory
+    (func (export "export-state") (result i32)
+      (i32.store (i32.const 0) (global.get $state))
+      (i32.store (i32.const 4) (global.get $state-len))
+      (i32.const 0))
+
+    (func (export "import-state") (param i32 i32)
+      (global.set $state (local.get 0))
+      (global.set $state-len (local.get 1)))
+  )
+  (core instance $guest-instance (instantiate $guest))
+  (alias core export $guest-instance "memory" (core memory $memory))
+  (alias core export $guest-instance "realloc" (core func $realloc))
+
+  (func $export-state (result (list u8))
+    (canon lift (core func $guest-instance "export-state") (memory $memory)))
+  (func $import-state (param "state" (list u8))
+    (canon lift (core func $guest-instance "import-state") (memory $memory)
+      (realloc (core func $realloc))))
+
+  (instance $state-transfer
+    (export "export-state" (func $export-state))
+    (export "import-state" (func $import-state))
+  )
+  (export "ariel:wasm-bindings/state-transfer-api@0.0.1" (instance $state-transfer))
+)
+"#;
+
+/// Does not export the interface.
+const STATELESS: &str = r#"
+(component
+  (core module $guest
+    (func (export "run"))
+  )
+  (core instance $guest-instance (instantiate $guest))
+  (func (export "run") (canon lift (core func $guest-instance "run")))
+)
+"#;
+
+/// Exports the interface without `import-state`.
+const INCOMPLETE: &str = r#"
+(component
+  (core module $guest
+    (memory (export "memory") 1)
+    (func (export "export-state") (result i32)
+      (i32.const 0))
+  )
+  (core instance $guest-instance (instantiate $guest))
+  (alias core export $guest-instance "memory" (core memory $memory))
+
+  (func $export-state (result (list u8))
+    (canon lift (core func $guest-instance "export-state") (memory $memory)))
+
+  (instance $state-transfer
+    (export "export-state" (func $export-state))
+  )
+  (export "ariel:wasm-bindings/state-transfer-api@0.0.1" (instance $state-transfer))
+)
+"#;
+
+/// Counts the updates it went through in its single byte of state.
+#[cfg(feature = "coap")]
+const COUNTER: &str = r#"
+(component
+  (core module $guest
+    (memory (export "memory") 1)
+
+    ;; Imported lists are only read before the next call, so they can all go to the same place
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (i32.const 1024))
+
+    ;; The list is returned through the start of memory, the counter lives at address 16
+    (func (export "export-state") (result i32)
+      (i32.store (i32.const 0) (i32.const 16))
+      (i32.store (i32.const 4) (i32.const 1))
+      (i32.const 0))
+
+    (func (export "import-state") (param i32 i32)
+      (i32.store8 (i32.const 16) (i32.add (i32.load8_u (local.get 0)) (i32.const 1))))
+  )
+  (core instance $guest-instance (instantiate $guest))
+  (alias core export $guest-instance "memory" (core memory $memory))
+  (alias core export $guest-instance "realloc" (core func $realloc))
+
+  (func $export-state (result (list u8))
+    (canon lift (core func $guest-instance "export-state") (memory $memory)))
+  (func $import-state (param "state" (list u8))
+    (canon lift (core func $guest-instance "import-state") (memory $memory)
+      (realloc (core func $realloc))))
+
+  (instance $state-transfer
+    (export "export-state" (func $export-state))
+    (export "import-state" (func $import-state))
+  )
+  (export "ariel:wasm-bindings/state-transfer-api@0.0.1" (instance $state-transfer))
+)
+"#;
+
+#[test]
+fn hands_state_to_the_next_capsule() {
+    let linker = Linker::new(&common::engine());
+
+    let (mut old_store, old) = common::instantiate(&linker, STATEFUL);
+    let old = StateTransfer::lookup(&mut old_store, &old)
+        .unwrap()
+        .unwrap();
+    assert_eq!(old.export_state(&mut old_store).unwrap(), b"fresh");
+    old.import_state(&mut old_store, b"seen 3 devices".to_vec())
+        .unwrap();
+    let state = old.export_state(&mut old_store).unwrap();
+    drop(old_store);
+
+    let (mut new_store, new) = common::instantiate(&linker, STATEFUL);
+    let new = StateTransfer::lookup(&mut new_store, &new)
+        .unwrap()
+        .unwrap();
+    new.import_state(&mut new_store, state).unwrap();
+    assert_eq!(new.export_state(&mut new_store).unwrap(), b"seen 3 devices");
+}
+
+#[test]
+fn interface_is_optional() {
+    let linker = Linker::new(&common::engine());
+    let (mut store, instance) = common::instantiate(&linker, STATELESS);
+    assert!(
+        StateTransfer::lookup(&mut store, &instance)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn rejects_incomplete_interface() {
+    let linker = Linker::new(&common::engine());
+    let (mut store, instance) = common::instantiate(&linker, INCOMPLETE);
+    assert!(StateTransfer::lookup(&mut store, &instance).is_err());
+}
+
+/// Capsule for [`WasmHandler`] that records the state it starts with in [`STARTED_WITH`].
+#[cfg(feature = "coap")]
+struct Recorder(Instance);
+
+#[cfg(feature = "coap")]
+std::thread_local! {
+    static STARTED_WITH: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(feature = "coap")]
+impl CanInstantiate<ArielOSHost> for Recorder {
+    fn link(_: &mut Linker<ArielOSHost>) -> wasmtime::Result<()> {
+        Ok(())
+    }
+
+    fn from_instance(_: &mut Store<ArielOSHost>, instance: &Instance) -> wasmtime::Result<Self> {
+        Ok(Self(*instance))
+    }
+}
+
+#[cfg(feature = "coap")]
+impl PersistentCapsule<ArielOSHost> for Recorder {
+    type E = CoAPError;
+
+    fn coap_run(
+        &mut self,
+        _: &mut Store<ArielOSHost>,
+        _: u8,
+        _: u32,
+        _: Vec<u8>,
+    ) -> Result<(u8, Vec<u8>), CoAPError> {
+        Err(CoAPError::internal_server_error())
+    }
+
+    fn initialize_handler(&mut self, store: &mut Store<ArielOSHost>) -> wasmtime::Result<()> {
+        let state_transfer = StateTransfer::lookup(&mut *store, &self.0)?.unwrap();
+        let state = state_transfer.export_state(store)?;
+        STARTED_WITH.with_borrow_mut(|started_with| started_with.push(state));
+        Ok(())
+    }
+
+    fn report_resources(&mut self, _: &mut Store<ArielOSHost>) -> Result<Vec<String>, CoAPError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Replaces the program of a stopped handler by the given component and starts it.
+#[cfg(feature = "coap")]
+fn restart(
+    handler: &mut WasmHandler<ArielOSHost, Recorder>,
+    engine: &Engine,
+    wat: &str,
+) -> wasmtime::Result<()> {
+    let program = engine.precompile_component(wat.as_bytes()).unwrap();
+    *handler.mutate_program().unwrap() = program;
+    // SAFETY: The program was just compiled by this engine.
+    unsafe { handler.start_from_dynamic(engine) }
+}
+
+#[test]
+#[cfg(feature = "coap")]
+fn handler_carries_state_across_restarts() {
+    // Raw deserialization needs interpreted code, as on the devices
+    let mut config = Config::new();
+    config.target("pulley64").unwrap();
+    let engine = Engine::new(&config).unwrap();
+    let mut handler = WasmHandler::<_, Recorder>::new(ArielOSHost::default());
+
+    restart(&mut handler, &engine, COUNTER).unwrap();
+    handler.stop();
+    restart(&mut handler, &engine, COUNTER).unwrap();
+    handler.stop();
+
+    // A failed start neither wedges the handler nor loses the state
+    assert!(restart(&mut handler, &engine, INCOMPLETE).is_err());
+    restart(&mut handler, &engine, COUNTER).unwrap();
+    handler.stop();
+
+    handler.discard_state();
+    restart(&mut handler, &engine, COUNTER).unwrap();
+
+    STARTED_WITH.with_borrow(|started_with| {
+        assert_eq!(*started_with, [vec![0], vec![1], vec![2], vec![0]]);
+    });
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "storage")]
+//! Checks the validation of keys and namespaces, which happens before flash is touched.
+
+mod common;
+
+use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::storage::StorageError;
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+
+/// Deletes the key `a/b` and returns the result.
+const DELETER: &str = r#"
+(component
+  (type $storage-error (enum "not-bound" "invalid-key" "value-too-large" "quota-exceeded"
+    "too-many-keys" "flash"))
+  (import "ariel:wasm-bindings/storage-api@0.0.1" (instance $storage
+    (export "storage-error" (type $error (eq $storage-error)))
+    (export "delete" (func (param "key" string) (result (result (error $error)))))
+  ))
+  (alias export $storage "storage-error" (type $error))
+
+  (core module $memory-module
+    (memory (export "memory") 1)
+    (data (i32.const 16) "a/b")
+  )
+  (core instance $memory-instance (instantiate $memory-module))
+  (alias core export $memory-instance "memory" (core memory $memory))
+
+  (core func $delete (canon lower (func $storage "delete") (memory $memory)))
+
+  (core module $guest
+    (import "host" "delete" (func $delete (param i32 i32 i32)))
+    ;; The result is written to and returned through address 32
+    (func (export "run") (result i32)
+      (call $delete (i32.const 16) (i32.const 3) (i32.const 32))
+      (i32.const 32))
+  )
+  (core instance $host (export "delete" (func $delete)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "run") (result (result (error $error)))
+    (canon lift (core func $guest-instance "run") (memory $memory)))
+)
+"#;
+
+#[test]
+fn refuses_keys_containing_a_slash() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Storage)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), DELETER);
+    store.data_mut().bind_storage("capsule", 64).unwrap();
+
+    let run = instance
+        .get_typed_func::<(), (Result<(), StorageError>,)>(&mut store, "run")
+        .unwrap();
+    let (result,) = embassy_futures::block_on(run.call_async(&mut store, ())).unwrap();
+    assert_eq!(result, Err(StorageError::InvalidKey));
+}
+
+#[test]
+fn refuses_invalid_namespaces() {
+    let mut host = ArielOSHost::default();
+    assert!(host.bind_storage("capsule/other", 64).is_err());
+    assert!(host.bind_storage("", 64).is_err());
+    assert!(host.bind_storage("capsule", 64).is_ok());
+}
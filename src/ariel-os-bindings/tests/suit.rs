@@ -0,0 +1,312 @@
+#![cfg(feature = "suit")]
+//! Checks that SUIT envelopes are only accepted when signed, for this device and untampered.
+
+use ariel_os_bindings::wasm::signed::TrustedKey;
+use ariel_os_bindings::wasm::suit::{DeviceIdentity, SuitError, process, verify_update};
+use ed25519_dalek::{Signer, SigningKey};
+use minicbor::Encoder;
+use sha2::{Digest, Sha256};
+
+const PAYLOAD: &[u8] = b"not really a cwasm, but the processor does not care";
+
+const DEVICE: DeviceIdentity = DeviceIdentity {
+    vendor_id: [0xfa; 16],
+    class_id: [0x1c; 16],
+};
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[1; 32])
+}
+
+fn trusted() -> [TrustedKey; 1] {
+    [TrustedKey {
+        id: 0,
+        public_key: signing_key().verifying_key().to_bytes(),
+    }]
+}
+
+const EDDSA: i64 = -8;
+const ES256: i64 = -7;
+
+/// COSE_Sign1 over the manifest digest, claiming to use `algorithm`.
+fn sign(manifest_digest: &[u8], key: &SigningKey, algorithm: i64) -> Vec<u8> {
+    let mut protected = encoder();
+    protected
+        .map(1)
+        .unwrap()
+        .u8(1)
+        .unwrap()
+        .i64(algorithm)
+        .unwrap();
+    let protected = protected.into_writer();
+
+    let mut to_be_signed = encoder();
+    to_be_signed.array(4).unwrap().str("Signature1").unwrap();
+    to_be_signed.bytes(&protected).unwrap();
+    to_be_signed.bytes(&[]).unwrap();
+    to_be_signed.bytes(manifest_digest).unwrap();
+    let signature = key.sign(&to_be_signed.into_writer());
+
+    let mut cose = encoder();
+    cose.array(4).unwrap().bytes(&protected).unwrap();
+    cose.map(0).unwrap().null().unwrap();
+    cose.bytes(&signature.to_bytes()).unwrap();
+    cose.into_writer()
+}
+
+fn encoder() -> Encoder<Vec<u8>> {
+    Encoder::new(Vec::new())
+}
+
+fn digest(bytes: &[u8]) -> Vec<u8> {
+    let mut e = encoder();
+    e.array(2).unwrap().i64(-16).unwrap();
+    e.bytes(&Sha256::digest(bytes)).unwrap();
+    e.into_writer()
+}
+
+/// What goes into a test envelope.
+struct Envelope {
+    sequence_number: u64,
+    vendor_id: [u8; 16],
+    class_id: [u8; 16],
+    image_size: usize,
+    payload_key: &'static str,
+    payload: Vec<u8>,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            sequence_number: 3,
+            vendor_id: DEVICE.vendor_id,
+            class_id: DEVICE.class_id,
+            image_size: PAYLOAD.len(),
+            payload_key: "#capsule",
+            payload: PAYLOAD.to_vec(),
+        }
+    }
+}
+
+impl Envelope {
+    fn manifest(&self) -> Vec<u8> {
+        let mut shared = encoder();
+        shared.array(6).unwrap();
+        // suit-directive-override-parameters
+        shared.u8(20).unwrap().map(5).unwrap();
+        shared.u8(1).unwrap().bytes(&self.vendor_id).unwrap();
+        shared.u8(2).unwrap().bytes(&self.class_id).unwrap();
+        shared.u8(3).unwrap().bytes(&digest(PAYLOAD)).unwrap();
+        shared.u8(14).unwrap().u64(self.image_size as u64).unwrap();
+        shared.u8(21).unwrap().str("#capsule").unwrap();
+        // Vendor and class conditions
+        shared.u8(1).unwrap().u8(15).unwrap();
+        shared.u8(2).unwrap().u8(15).unwrap();
+
+        let mut common = encoder();
+        common.map(2).unwrap();
+        common.u8(2).unwrap().array(1).unwrap().array(1).unwrap();
+        common.bytes(b"capsule").unwrap();
+        common.u8(4).unwrap().bytes(&shared.into_writer()).unwrap();
+
+        let mut install = encoder();
+        // suit-directive-fetch, then suit-condition-image-match
+        install.array(4).unwrap();
+        install.u8(21).unwrap().u8(2).unwrap();
+        install.u8(3).unwrap().u8(15).unwrap();
+
+        let mut manifest = encoder();
+        manifest.map(4).unwrap();
+        manifest.u8(1).unwrap().u8(1).unwrap();
+        manifest.u8(2).unwrap().u64(self.sequence_number).unwrap();
+        manifest
+            .u8(3)
+            .unwrap()
+            .bytes(&common.into_writer())
+            .unwrap();
+        manifest
+            .u8(17)
+            .unwrap()
+            .bytes(&install.into_writer())
+            .unwrap();
+        manifest.into_writer()
+    }
+
+    fn build(&self, key: &SigningKey) -> Vec<u8> {
+        let manifest = self.manifest();
+        let manifest_digest = digest(&manifest);
+        Self::wrap(
+            &manifest,
+            &manifest_digest,
+            &[sign(&manifest_digest, key, EDDSA)],
+            self.payload_key,
+            &self.payload,
+        )
+    }
+
+    fn wrap(
+        manifest: &[u8],
+        manifest_digest: &[u8],
+        signatures: &[Vec<u8>],
+        payload_key: &str,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut authentication = encoder();
+        authentication
+            .array(1 + signatures.len() as u64)
+            .unwrap()
+            .bytes(manifest_digest)
+            .unwrap();
+        for signature in signatures {
+            authentication.bytes(signature).unwrap();
+        }
+
+        // Tagged as SUIT_Envelope
+        let mut envelope = encoder();
+        envelope.map(3).unwrap();
+        envelope
+            .u8(2)
+            .unwrap()
+            .bytes(&authentication.into_writer())
+            .unwrap();
+        envelope.u8(3).unwrap().bytes(manifest).unwrap();
+        envelope.str(payload_key).unwrap().bytes(payload).unwrap();
+        let mut tagged = vec![0xd8, 107];
+        tagged.extend_from_slice(&envelope.into_writer());
+        tagged
+    }
+}
+
+#[test]
+fn accepts_envelope() {
+    let envelope = Envelope::default().build(&signing_key());
+    let update = process(&envelope, &trusted(), &DEVICE).unwrap();
+    assert_eq!(update.version(), 3);
+    assert_eq!(update.payload(), PAYLOAD);
+
+    let update = verify_update(&envelope, &trusted(), &DEVICE).unwrap();
+    assert_eq!(update.payload(), PAYLOAD);
+}
+
+#[test]
+fn rejects_untrusted_signer() {
+    let envelope = Envelope::default().build(&SigningKey::from_bytes(&[2; 32]));
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::BadSignature
+    );
+}
+
+#[test]
+fn skips_signatures_with_unsupported_algorithms() {
+    let manifest = Envelope::default().manifest();
+    let manifest_digest = digest(&manifest);
+    let es256 = sign(&manifest_digest, &signing_key(), ES256);
+
+    let envelope = Envelope::wrap(
+        &manifest,
+        &manifest_digest,
+        &[es256.clone(), sign(&manifest_digest, &signing_key(), EDDSA)],
+        "#capsule",
+        PAYLOAD,
+    );
+    assert!(process(&envelope, &trusted(), &DEVICE).is_ok());
+
+    let envelope = Envelope::wrap(&manifest, &manifest_digest, &[es256], "#capsule", PAYLOAD);
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::BadSignature
+    );
+}
+
+#[test]
+fn rejects_tampered_manifest() {
+    let original = Envelope::default().manifest();
+    let tampered = Envelope {
+        sequence_number: 4,
+        ..Default::default()
+    }
+    .manifest();
+    // Signature over the digest of the original manifest, sent with another one
+    let envelope = Envelope::wrap(
+        &tampered,
+        &digest(&original),
+        &[sign(&digest(&original), &signing_key(), EDDSA)],
+        "#capsule",
+        PAYLOAD,
+    );
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::ManifestDigestMismatch
+    );
+}
+
+#[test]
+fn rejects_other_devices() {
+    let envelope = Envelope {
+        vendor_id: [0; 16],
+        ..Default::default()
+    }
+    .build(&signing_key());
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::WrongVendor
+    );
+
+    let envelope = Envelope {
+        class_id: [0; 16],
+        ..Default::default()
+    }
+    .build(&signing_key());
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::WrongClass
+    );
+}
+
+#[test]
+fn rejects_bad_payload() {
+    let envelope = Envelope {
+        payload_key: "#other",
+        ..Default::default()
+    }
+    .build(&signing_key());
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::MissingPayload
+    );
+
+    let envelope = Envelope {
+        image_size: PAYLOAD.len() + 1,
+        ..Default::default()
+    }
+    .build(&signing_key());
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::SizeMismatch
+    );
+
+    let mut payload = PAYLOAD.to_vec();
+    payload[0] ^= 1;
+    let envelope = Envelope {
+        payload,
+        ..Default::default()
+    }
+    .build(&signing_key());
+    assert_eq!(
+        process(&envelope, &trusted(), &DEVICE).unwrap_err(),
+        SuitError::DigestMismatch
+    );
+}
+
+#[test]
+fn rejects_garbage() {
+    assert_eq!(
+        process(&[0xa1, 0x02], &trusted(), &DEVICE).unwrap_err(),
+        SuitError::Malformed
+    );
+    assert!(matches!(
+        verify_update(b"trvm", &trusted(), &DEVICE).unwrap_err(),
+        SuitError::Container(_)
+    ));
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "time")]
+//! Sleeps from a component and checks the clock it sees.
+
+mod common;
+
+use ariel_os_bindings::wasm::{Capability, CapsuleLinker};
+
+/// Sleeps for the given time and returns how long that took according to `now-as-millis`.
+const NAP: &str = r#"
+(component
+  (import "ariel:wasm-bindings/time-api@0.0.1" (instance $time
+    (export "sleep" (func (param "millis" u64)))
+    (export "now-as-millis" (func (result u64)))
+  ))
+
+  (core func $sleep (canon lower (func $time "sleep")))
+  (core func $now-as-millis (canon lower (func $time "now-as-millis")))
+
+  (core module $guest
+    (import "host" "sleep" (func $sleep (param i64)))
+    (import "host" "now-as-millis" (func $now-as-millis (result i64)))
+
+    (func (export "nap") (param i64) (result i64)
+      (local $start i64)
+      (local.set $start (call $now-as-millis))
+      (call $sleep (local.get 0))
+      (i64.sub (call $now-as-millis) (local.get $start)))
+  )
+  (core instance $host
+    (export "sleep" (func $sleep))
+    (export "now-as-millis" (func $now-as-millis)))
+  (core instance $guest-instance (instantiate $guest (with "host" (instance $host))))
+
+  (func (export "nap") (param "millis" u64) (result u64)
+    (canon lift (core func $guest-instance "nap")))
+)
+"#;
+
+#[test]
+fn sleeps_for_at_least_the_requested_time() {
+    let engine = common::engine();
+    let linker = CapsuleLinker::builder()
+        .grant(Capability::Time)
+        .build(&engine)
+        .unwrap();
+    let (mut store, instance) = common::instantiate(linker.linker(), NAP);
+
+    let nap = instance
+        .get_typed_func::<(u64,), (u64,)>(&mut store, "nap")
+        .unwrap();
+    let (elapsed,) = embassy_futures::block_on(nap.call_async(&mut store, (20,))).unwrap();
+    assert!(elapsed >= 20, "slept for {elapsed}ms");
+}
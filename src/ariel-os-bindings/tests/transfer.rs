@@ -0,0 +1,197 @@
+#![cfg(feature = "transfer")]
+//! Checks that images get through lost, duplicated and reordered datagrams, and nothing else does.
+
+use ariel_os_bindings::wasm::transfer::{Frame, NackReason, Receiver, Reply};
+use sha2::{Digest, Sha256};
+
+const IMAGE: &[u8] = b"not really a cwasm, but long enough to be sent in a few chunks";
+const CHUNK: usize = 16;
+
+fn start(image: &[u8]) -> Vec<u8> {
+    encode(Frame::Start {
+        id: 7,
+        len: image.len() as u32,
+        sha256: Sha256::digest(image).into(),
+    })
+}
+
+fn data(image: &[u8], offset: usize) -> Vec<u8> {
+    let end = image.len().min(offset + CHUNK);
+    encode(Frame::Data {
+        id: 7,
+        offset: offset as u32,
+        data: &image[offset..end],
+    })
+}
+
+fn encode(frame: Frame) -> Vec<u8> {
+    let mut out = [0; 64];
+    let len = frame.encode(&mut out).unwrap();
+    assert_eq!(Frame::parse(&out[..len]), Some(frame));
+    out[..len].to_vec()
+}
+
+/// Sends `image` the way `send_files.rs` does, dropping the datagrams `lose` picks.
+fn send(receiver: &mut Receiver, image: &[u8], mut lose: impl FnMut(usize) -> bool) -> u32 {
+    let mut next = None;
+    for sent in 0.. {
+        assert!(sent < 100, "transfer does not make progress");
+        let frame = match next {
+            None => start(image),
+            Some(offset) => data(image, offset as usize),
+        };
+        if lose(sent) {
+            continue;
+        }
+        let handled = receiver.handle(&frame);
+        let reply = Reply::parse(&handled.reply.unwrap().encode()).unwrap();
+        match reply {
+            Reply::Ack { next: offset, .. } => next = Some(offset),
+            Reply::Nack {
+                next: offset,
+                reason: NackReason::OutOfOrder,
+                ..
+            } => next = Some(offset),
+            Reply::Nack { reason, .. } => panic!("unexpected {reason:?}"),
+            Reply::Done { len, .. } => {
+                assert!(handled.complete);
+                return len;
+            }
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+fn receives_image() {
+    let mut buffer = [0; 128];
+    let mut receiver = Receiver::new(&mut buffer);
+    assert_eq!(send(&mut receiver, IMAGE, |_| false), IMAGE.len() as u32);
+    assert_eq!(receiver.image(), Some(IMAGE));
+}
+
+#[test]
+fn survives_lost_datagrams() {
+    let mut buffer = [0; 128];
+    let mut receiver = Receiver::new(&mut buffer);
+    // Every third datagram is lost, so the sender times out and sends it again
+    send(&mut receiver, IMAGE, |sent| sent % 3 == 2);
+    assert_eq!(receiver.image(), Some(IMAGE));
+}
+
+#[test]
+fn asks_for_missing_data() {
+    let mut buffer = [0; 128];
+    let mut receiver = Receiver::new(&mut buffer);
+    receiver.handle(&start(IMAGE));
+    receiver.handle(&data(IMAGE, 0));
+
+    // Skipped a chunk
+    let handled = receiver.handle(&data(IMAGE, 2 * CHUNK));
+    assert_eq!(
+        handled.reply,
+        Some(Reply::Nack {
+            id: 7,
+            next: CHUNK as u32,
+            reason: NackReason::OutOfOrder
+        })
+    );
+    // A duplicate of a chunk it already has
+    let handled = receiver.handle(&data(IMAGE, 0));
+    assert!(matches!(
+        handled.reply,
+        Some(Reply::Nack {
+            reason: NackReason::OutOfOrder,
+            ..
+        })
+    ));
+    assert!(receiver.in_progress());
+}
+
+#[test]
+fn resumes_transfer() {
+    let mut buffer = [0; 128];
+    let mut receiver = Receiver::new(&mut buffer);
+    receiver.handle(&start(IMAGE));
+    receiver.handle(&data(IMAGE, 0));
+    receiver.handle(&data(IMAGE, CHUNK));
+
+    // The sender restarts and announces the same image again
+    let handled = receiver.handle(&start(IMAGE));
+    assert_eq!(
+        handled.reply,
+        Some(Reply::Ack {
+            id: 7,
+            next: 2 * CHUNK as u32
+        })
+    );
+    send(&mut receiver, IMAGE, |_| false);
+
+    // Once complete, announcing it again just reports it done
+    let handled = receiver.handle(&start(IMAGE));
+    assert_eq!(
+        handled.reply,
+        Some(Reply::Done {
+            id: 7,
+            len: IMAGE.len() as u32
+        })
+    );
+    assert!(!handled.complete);
+    assert_eq!(receiver.image(), Some(IMAGE));
+}
+
+#[test]
+fn rejects_corrupted_image() {
+    let mut buffer = [0; 128];
+    let mut receiver = Receiver::new(&mut buffer);
+    let mut corrupted = IMAGE.to_vec();
+    corrupted[3] ^= 1;
+
+    receiver.handle(&start(IMAGE));
+    let mut handled = None;
+    for offset in (0..IMAGE.len()).step_by(CHUNK) {
+        handled = Some(receiver.handle(&data(&corrupted, offset)));
+    }
+    let handled = handled.unwrap();
+    assert!(!handled.complete);
+    assert_eq!(
+        handled.reply,
+        Some(Reply::Nack {
+            id: 7,
+            next: 0,
+            reason: NackReason::HashMismatch
+        })
+    );
+    assert_eq!(receiver.image(), None);
+    assert!(!receiver.in_progress());
+}
+
+#[test]
+fn rejects_bad_lengths() {
+    let mut buffer = [0; 32];
+    let mut receiver = Receiver::new(&mut buffer);
+    assert!(matches!(
+        receiver.handle(&start(IMAGE)).reply,
+        Some(Reply::Nack {
+            reason: NackReason::BadLength,
+            ..
+        })
+    ));
+    assert!(matches!(
+        receiver.handle(&start(&[])).reply,
+        Some(Reply::Nack {
+            reason: NackReason::BadLength,
+            ..
+        })
+    ));
+    // Data without an announcement
+    assert!(matches!(
+        receiver.handle(&data(IMAGE, 0)).reply,
+        Some(Reply::Nack {
+            reason: NackReason::UnknownTransfer,
+            ..
+        })
+    ));
+    // Not a frame at all
+    assert_eq!(receiver.handle(&[0xff; 4]).reply, None);
+}
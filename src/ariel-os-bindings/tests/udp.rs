@@ -0,0 +1,177 @@
+#![cfg(feature = "udp")]
+//! Sends datagrams between sockets of the host pool over a loopback network stack.
+
+use std::collections::VecDeque;
+use std::task::Context;
+
+use ariel_os_bindings::wasm::ArielOSHost;
+use ariel_os_bindings::wasm::udp::{
+    HostUdpSocket, UdpError, UdpSocketBuffers, UdpSocketHandle, UdpSocketPool, gen_udp,
+};
+use ariel_os_embassy::reexports::embassy_net;
+use embassy_futures::select::{Either, select};
+use embassy_net::driver::{Capabilities, Driver, HardwareAddress, LinkState, RxToken, TxToken};
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, Runner, StackResources, StaticConfigV4};
+use wasmtime::component::Resource;
+
+/// Network device handing every IP packet it sends straight back to the stack.
+#[derive(Default)]
+struct Loopback(VecDeque<Vec<u8>>);
+
+struct Received(Vec<u8>);
+
+struct Transmit<'a>(&'a mut VecDeque<Vec<u8>>);
+
+impl RxToken for Received {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(mut self, f: F) -> R {
+        f(&mut self.0)
+    }
+}
+
+impl TxToken for Transmit<'_> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let mut packet = vec![0; len];
+        let result = f(&mut packet);
+        self.0.push_back(packet);
+        result
+    }
+}
+
+impl Driver for Loopback {
+    type RxToken<'a> = Received;
+    type TxToken<'a> = Transmit<'a>;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Received, Transmit<'_>)> {
+        match self.0.pop_front() {
+            Some(packet) => Some((Received(packet), Transmit(&mut self.0))),
+            None => {
+                // The tests busy-poll anyway
+                cx.waker().wake_by_ref();
+                None
+            }
+        }
+    }
+
+    fn transmit(&mut self, _cx: &mut Context) -> Option<Transmit<'_>> {
+        Some(Transmit(&mut self.0))
+    }
+
+    fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+        LinkState::Up
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::default();
+        capabilities.max_transmission_unit = 1280;
+        capabilities
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        HardwareAddress::Ip
+    }
+}
+
+const ADDRESS: [u8; 4] = [10, 0, 0, 1];
+
+/// Returns a host whose pool has room for `sockets` sockets, and the runner of its stack.
+fn host(sockets: usize) -> (ArielOSHost, Runner<'static, Loopback>) {
+    let [a, b, c, d] = ADDRESS;
+    let config = Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(Ipv4Address::new(a, b, c, d), 24),
+        gateway: None,
+        dns_servers: Default::default(),
+    });
+    let resources = Box::leak(Box::new(StackResources::<4>::new()));
+    let (stack, runner) = embassy_net::new(Loopback::default(), config, resources, 0);
+
+    let pool = (0..sockets).fold(UdpSocketPool::new(stack), |pool, _| {
+        pool.with_buffers(Box::leak(Box::new(UdpSocketBuffers::<2, 256>::new())))
+    });
+    let mut host = ArielOSHost::default();
+    host.set_udp_pool(Box::leak(Box::new(pool)));
+    (host, runner)
+}
+
+/// Another handle to the same socket, since methods take the resource by value.
+fn handle(socket: &Resource<UdpSocketHandle>) -> Resource<UdpSocketHandle> {
+    Resource::new_borrow(socket.rep())
+}
+
+fn endpoint(port: u16) -> gen_udp::UdpMetadata {
+    let [a, b, c, d] = ADDRESS;
+    gen_udp::UdpMetadata {
+        endpoint: gen_udp::Endpoint {
+            addr: gen_udp::IpAddr::V4(gen_udp::Ipv4Addr { a, b, c, d }),
+            port,
+        },
+        local_addr: None,
+    }
+}
+
+#[test]
+fn opening_fails_without_a_pool() {
+    let mut host = ArielOSHost::default();
+    assert!(matches!(host.open(), Err(UdpError::NotInitialized)));
+}
+
+#[test]
+fn dropped_sockets_give_their_buffers_back() {
+    let (mut host, _runner) = host(1);
+    let socket = host.open().unwrap();
+    assert!(matches!(host.open(), Err(UdpError::PoolExhausted)));
+    HostUdpSocket::drop(&mut host, socket).unwrap();
+    assert!(host.open().is_ok());
+}
+
+#[test]
+fn binding_twice_is_an_invalid_state() {
+    let (mut host, _runner) = host(1);
+    let socket = host.open().unwrap();
+    host.bind(handle(&socket), 1000).unwrap();
+    assert_eq!(
+        host.bind(handle(&socket), 1001),
+        Err(UdpError::InvalidState)
+    );
+}
+
+#[test]
+fn datagrams_reach_the_bound_port() {
+    let (mut host, mut runner) = host(2);
+    let sender = host.open().unwrap();
+    let receiver = host.open().unwrap();
+    host.bind(handle(&sender), 1000).unwrap();
+    host.bind(handle(&receiver), 2000).unwrap();
+    assert!(matches!(host.try_recv(handle(&receiver)), Ok(None)));
+
+    let exchange = async {
+        host.send(handle(&sender), b"ping".to_vec(), endpoint(2000))
+            .await
+            .unwrap();
+        host.recv(handle(&receiver), Some(1000)).await
+    };
+    let Either::First(received) = embassy_futures::block_on(select(exchange, runner.run()));
+
+    let (data, metadata) = received.unwrap().expect("no datagram within a second");
+    assert_eq!(data, b"ping");
+    assert_eq!(metadata.endpoint.port, 1000);
+    assert!(matches!(
+        metadata.endpoint.addr,
+        gen_udp::IpAddr::V4(gen_udp::Ipv4Addr {
+            a: 10,
+            b: 0,
+            c: 0,
+            d: 1
+        })
+    ));
+}
+
+#[test]
+fn recv_times_out_without_datagrams() {
+    let (mut host, mut runner) = host(1);
+    let socket = host.open().unwrap();
+    host.bind(handle(&socket), 1000).unwrap();
+
+    let Either::First(received) =
+        embassy_futures::block_on(select(host.recv(handle(&socket), Some(10)), runner.run()));
+    assert!(matches!(received, Ok(None)));
+}